@@ -36,6 +36,9 @@ settings-scaling-linear = Linear
 
 settings-terminal-category = Terminal
 settings-terminal-console-beep-checkbox = Konsole Beep
+settings-terminal-connect-timeout = Verbindungs-Timeout
+settings-terminal-redial-attempts = Wahlwiederholungen
+settings-terminal-redial-delay = Pause zwischen Wahlwiederholungen
 settings-terminal-open-settings-dir-button = Einstellungsverzeichnis öffnen
 
 settings-keybinds-category = Tastatur
@@ -52,18 +55,30 @@ settings-keybinds-quit = Beenden:
 settings-keybinds-show-find = Suchen:
 
 settings-modem-category = Modem
+settings-modem-profile = Profil:
+settings-modem-name = Name:
+settings-modem-add-button = Hinzufügen
+settings-modem-remove-button = Entfernen
+settings-modem-no-ports = Keine seriellen Schnittstellen gefunden
+settings-proxy-category = Proxy
+settings-proxy-kind = Proxy:
+settings-proxy-address = Adresse:
+settings-proxy-user = Benutzer:
+settings-proxy-password = Passwort:
 
 dialing_directory-connect-to = Verbinde zu…
 dialing_directory-new_bbs = Neue BBS
 dialing_directory-name-placeholder = BBS Name
 dialing_directory-filter-placeholder = Namensfilter
 dialing_directory-address = Addresse:
+dialing_directory-command = Befehl:
 dialing_directory-protocol = Protokoll:
 dialing_directory-user = User:
 dialing_directory-password = Passwort:
 dialing_directory-generate = Generieren
 dialing_directory-screen_mode = Bildschirmmodus:
 dialing_directory-terminal_type = Terminaltyp:
+dialing_directory-terminal_type_names = Terminal-IDs:
 dialing_directory-music-option = Ansimusik:
 dialing_directory-autologin = Autologin String:
 dialing_directory-comment = Kommentar:
@@ -83,11 +98,44 @@ dialing_directory-no-entries = Keine passenden Einträge
 dialing_directory-add-bbs-button = Neue BBS
 dialing_directory-version-warning = Die BBS Liste ist mit einer neueren Version von IcyTerm erstellt worden. Bitte neue Version verwenden. Keine Änderungen werden gespeichert.
 dialing_directory-custom-iemsi-login-data = Benutzerdefinierte IEMSI Login-Daten
+dialing_directory-accept-self-signed = Selbstsignierten Zertifikaten beim ersten Verbinden vertrauen
+dialing_directory-websocket-telnet = Telnet-Protokoll
+dialing_directory-websocket-telnet-tooltip = Der Server sendet einen Telnet-Datenstrom in den WebSocket-Frames, z.B. hinter websockify- oder fTelnet-Gateways
+dialing_directory-rlogin-send-password = Passwort als Client-Benutzer senden
+dialing_directory-rlogin-send-password-tooltip = BBS-Server wie Synchronet erwarten das Passwort im Feld für den Client-Benutzer. rlogin überträgt es unverschlüsselt.
+dialing_directory-websocket-protocols = Subprotokolle:
+dialing_directory-websocket-headers = HTTP-Header:
+dialing_directory-ssh-auth-methods = Anmeldung
+dialing_directory-ssh-add-auth-method = Methode hinzufügen
+dialing_directory-ssh-key-file = Privater Schlüssel
+dialing_directory-ssh-key-passphrase = Passphrase
+dialing_directory-ssh-algorithms = Algorithmen
+dialing_directory-ssh-algorithms-secure = Sicher
+dialing_directory-ssh-algorithms-legacy = Veraltet (schwache Algorithmen für alte Systeme)
+dialing_directory-ssh-algorithms-custom = Benutzerdefiniert
+dialing_directory-ssh-key-exchanges = Schlüsselaustausch
+dialing_directory-ssh-host-keys = Host-Schlüssel
+dialing_directory-ssh-ciphers = Verschlüsselung
+dialing_directory-ssh-macs = MACs
+dialing_directory-custom-proxy = Eigene Proxy-Einstellungen verwenden
+dialing_directory-modem-profile = Modem:
+dialing_directory-failover-addresses = Ausweichadressen:
+dialing_directory-failover-addresses-hint = Alternative Adressen, durch Komma getrennt
+dialing_directory-dial-queue = Zur Wählliste hinzufügen
+dialing_directory-redial-button = Wahlwiederholung
+dialing_directory-redial-tooltip = Die Einträge der Wählliste anwählen, bis eine Verbindung zustande kommt
 dialing_directory-open_cache_button=Cache öffnen…
 
 terminal-upload = Upload
 terminal-download = Download
 terminal-hangup = Auflegen
+terminal-line-dcd = Trägersignal erkannt
+terminal-line-dsr = Gegenstelle bereit
+terminal-line-cts = Sendebereitschaft
+terminal-line-ri = Klingelsignal
+terminal-line-dtr = Terminal bereit
+terminal-line-rts = Sendeanforderung
+terminal-send-break = Break senden
 terminal-autologin = Username/Passwort senden
 terminal-dialing_directory = Telefonbuch
 terminal-menu-copy = Kopieren
@@ -154,4 +202,51 @@ delete-bbs-question=Sind Sie sicher, dass '{ $system }' gelöscht werden soll?
 delete-bbs-delete-button=Löschen
 
 show-disconnected-heading=NO CARRIER
-show-disconnected-message=Verbindung beendet zu { $system } nach { $time }
\ No newline at end of file
+show-disconnected-message=Verbindung beendet zu { $system } nach { $time }
+
+unknown-host-title = Unbekannter Host
+unknown-host-changed-title = WARNUNG: Die Identität des Hosts hat sich geändert
+unknown-host-message = Die Identität von { $host } konnte nicht überprüft werden.
+unknown-host-certificate-fingerprint = Fingerabdruck des Zertifikats (SHA-256):
+unknown-host-ssh-fingerprint = Fingerabdruck des Host-Schlüssels (SHA-256):
+unknown-host-known-fingerprint = Bisher vertrauter Fingerabdruck:
+unknown-host-changed-warning = Jemand könnte die Verbindung abhören. Nur vertrauen, wenn der Grund für die Änderung bekannt ist.
+unknown-host-trust-button = Vertrauen
+unknown-host-reject-button = Ablehnen
+
+auth-prompt-title = Anmeldung
+auth-prompt-message = { $host } fragt nach weiteren Anmeldedaten.
+auth-prompt-login-button = Anmelden
+auth-prompt-cancel-button = Abbrechen
+
+redial-heading = Wahlwiederholung { $name }
+redial-attempt = Versuch { $attempt } von { $attempts }
+redial-dialing = Wähle { $address }…
+redial-waiting = Nächster Versuch in { $seconds } s
+redial-cancel-button = Abbrechen
+redial-failed = Keine Verbindung nach { $attempts } Versuchen.
+
+connection-error-invalid-address = Ungültige Adresse: { $details }
+connection-error-host-not-found = Host { $host } nicht gefunden.
+connection-error-refused = Verbindung abgelehnt: { $details }
+connection-error-timed-out = Zeitüberschreitung beim Verbinden: { $details }
+connection-error-proxy = Proxy-Fehler: { $details }
+connection-error-tls = TLS-Fehler: { $details }
+connection-error-unknown-host = Die Identität von { $host } konnte nicht überprüft werden.
+connection-error-host-key-changed = Der Host-Schlüssel von { $host } hat sich geändert (Fingerabdruck { $fingerprint }). Jemand könnte die Verbindung abhören. Entfernen Sie den alten Schlüssel aus { $file }, falls die Änderung erwartet ist.
+connection-error-authentication-failed = Anmeldung fehlgeschlagen: { $details }
+connection-error-canceled = Verbindungsaufbau abgebrochen.
+connection-error-busy = Die Leitung ist besetzt.
+connection-error-no-answer = Keine Antwort.
+connection-error-no-carrier = Kein Trägersignal.
+connection-error-no-dialtone = Kein Wählton.
+connection-error-modem = Modem-Fehler: { $details }
+connection-error-carrier-lost = Trägersignal verloren.
+connection-error-closed = Verbindung von der Gegenstelle beendet.
+connection-error-closed-reason = Verbindung von der Gegenstelle beendet: { $reason }
+connection-error-lost = Verbindung unterbrochen: { $details }
+connection-error-process-exited = Prozess beendet.
+connection-error-process-exited-status = Prozess beendet: { $status }
+connection-error-protocol = Protokollfehler: { $details }
+connection-error-unsupported = Nicht unterstützt: { $details }
+connection-error-io = Fehler: { $details }
@@ -35,6 +35,9 @@ settings-scaling-linear = Linear
 
 settings-terminal-category = Terminal
 settings-terminal-console-beep-checkbox = Console beep
+settings-terminal-connect-timeout = Connect timeout
+settings-terminal-redial-attempts = Redial attempts
+settings-terminal-redial-delay = Delay between redial attempts
 settings-terminal-open-settings-dir-button = Open settings directory
 
 settings-keybinds-category = Keybinds
@@ -56,18 +59,30 @@ settings-modem-baud_rate = Baudrate:
 settings-modem-data_bits = Databits/ Stopbits / Parity:
 settings-modem-init_string = Init string:
 settings-modem_dial_string = Dial string:
+settings-modem-profile = Profile:
+settings-modem-name = Name:
+settings-modem-add-button = Add
+settings-modem-remove-button = Remove
+settings-modem-no-ports = No serial ports found
+settings-proxy-category = Proxy
+settings-proxy-kind = Proxy:
+settings-proxy-address = Address:
+settings-proxy-user = User:
+settings-proxy-password = Password:
 
 dialing_directory-connect-to = Quick connect to…
 dialing_directory-new_bbs = New BBS
 dialing_directory-name-placeholder = BBS name
 dialing_directory-filter-placeholder = Filter names
 dialing_directory-address = Address:
+dialing_directory-command = Command:
 dialing_directory-protocol = Protocol:
 dialing_directory-user = User:
 dialing_directory-password = Password:
 dialing_directory-generate = Generate
 dialing_directory-screen_mode = Screen Mode:
 dialing_directory-terminal_type = Terminal type:
+dialing_directory-terminal_type_names = Terminal IDs:
 dialing_directory-music-option = Ansi music:
 dialing_directory-baud-emulation = Baud emulation:
 dialing_directory-autologin = Autologin String:
@@ -89,11 +104,44 @@ dialing_directory-no-entries = No matching entries
 dialing_directory-add-bbs-button = Add BBS
 dialing_directory-version-warning = This dialing directory was created with a newer version of IcyTerm. Please use newer version. No changes will be saved.
 dialing_directory-custom-iemsi-login-data = Custom IEMSI login data
+dialing_directory-accept-self-signed = Trust self-signed certificates on first use
+dialing_directory-websocket-telnet = Telnet framing
+dialing_directory-websocket-telnet-tooltip = The server sends a telnet stream inside the WebSocket frames, e.g. behind websockify or fTelnet gateways
+dialing_directory-rlogin-send-password = Send password as client user
+dialing_directory-rlogin-send-password-tooltip = BBS servers like Synchronet expect the password in the client user name field. rlogin sends it unencrypted.
+dialing_directory-websocket-protocols = Subprotocols:
+dialing_directory-websocket-headers = HTTP headers:
+dialing_directory-ssh-auth-methods = Authentication
+dialing_directory-ssh-add-auth-method = Add method
+dialing_directory-ssh-key-file = Private key file
+dialing_directory-ssh-key-passphrase = Key passphrase
+dialing_directory-ssh-algorithms = Algorithms
+dialing_directory-ssh-algorithms-secure = Secure
+dialing_directory-ssh-algorithms-legacy = Legacy (weak algorithms for old systems)
+dialing_directory-ssh-algorithms-custom = Custom
+dialing_directory-ssh-key-exchanges = Key exchange
+dialing_directory-ssh-host-keys = Host keys
+dialing_directory-ssh-ciphers = Ciphers
+dialing_directory-ssh-macs = MACs
+dialing_directory-custom-proxy = Use custom proxy settings
+dialing_directory-modem-profile = Modem:
+dialing_directory-failover-addresses = Failover:
+dialing_directory-failover-addresses-hint = Alternative addresses, comma separated
+dialing_directory-dial-queue = Add to the dial queue
+dialing_directory-redial-button = Redial
+dialing_directory-redial-tooltip = Dial the queued entries until one connects
 dialing_directory-open_cache_button=Open cache directory…
 
 terminal-upload = Upload
 terminal-download = Download
 terminal-hangup = Hangup
+terminal-line-dcd = Data carrier detect
+terminal-line-dsr = Data set ready
+terminal-line-cts = Clear to send
+terminal-line-ri = Ring indicator
+terminal-line-dtr = Data terminal ready
+terminal-line-rts = Request to send
+terminal-send-break = Break
 terminal-autologin = Send user name/password
 terminal-dialing_directory = Dialing directory
 terminal-menu = Menu
@@ -160,4 +208,51 @@ delete-bbs-question=Are you sure to you want to delete '{ $system }'?
 delete-bbs-delete-button=Delete  
 
 show-disconnected-heading=NO CARRIER
-show-disconnected-message=Disconnected from { $system } at { $time }
\ No newline at end of file
+show-disconnected-message=Disconnected from { $system } at { $time }
+
+unknown-host-title = Unknown host
+unknown-host-changed-title = WARNING: Host identity has changed
+unknown-host-message = The identity of { $host } could not be verified.
+unknown-host-certificate-fingerprint = Certificate fingerprint (SHA-256):
+unknown-host-ssh-fingerprint = Host key fingerprint (SHA-256):
+unknown-host-known-fingerprint = Previously trusted fingerprint:
+unknown-host-changed-warning = Someone could be intercepting the connection. Only trust the new identity if you know why it has changed.
+unknown-host-trust-button = Trust
+unknown-host-reject-button = Reject
+
+auth-prompt-title = Login
+auth-prompt-message = { $host } asks for additional login information.
+auth-prompt-login-button = Login
+auth-prompt-cancel-button = Cancel
+
+redial-heading = Redialing { $name }
+redial-attempt = Attempt { $attempt } of { $attempts }
+redial-dialing = Dialing { $address }…
+redial-waiting = Next attempt in { $seconds } s
+redial-cancel-button = Cancel
+redial-failed = No connection after { $attempts } attempts.
+
+connection-error-invalid-address = Invalid address: { $details }
+connection-error-host-not-found = Host { $host } not found.
+connection-error-refused = Connection refused: { $details }
+connection-error-timed-out = Connection timed out: { $details }
+connection-error-proxy = Proxy error: { $details }
+connection-error-tls = TLS error: { $details }
+connection-error-unknown-host = The identity of { $host } could not be verified.
+connection-error-host-key-changed = The host key of { $host } has changed (fingerprint { $fingerprint }). Someone could be intercepting the connection. Remove the old key from { $file } if the change is expected.
+connection-error-authentication-failed = Authentication failed: { $details }
+connection-error-canceled = Connecting canceled.
+connection-error-busy = The line is busy.
+connection-error-no-answer = No answer.
+connection-error-no-carrier = No carrier.
+connection-error-no-dialtone = No dial tone.
+connection-error-modem = Modem error: { $details }
+connection-error-carrier-lost = Carrier lost.
+connection-error-closed = Connection closed by remote host.
+connection-error-closed-reason = Connection closed by remote host: { $reason }
+connection-error-lost = Connection lost: { $details }
+connection-error-process-exited = Process exited.
+connection-error-process-exited-status = Process exited: { $status }
+connection-error-protocol = Protocol error: { $details }
+connection-error-unsupported = Not supported: { $details }
+connection-error-io = { $details }
@@ -0,0 +1,234 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, ErrorKind},
+};
+
+use i18n_embed_fl::fl;
+
+use super::{DialError, ResultCode, UnknownHost};
+
+/// Why a connection couldn't be established or got lost.
+///
+/// Every [`super::Com`] implementation reports its errors with this type, so the UI can decide
+/// whether redialing makes sense and show the user what went wrong in their language.
+/// The strings carry details like the OS error message, they aren't translated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionError {
+    /// The address can't be parsed or something required for the connection isn't configured.
+    InvalidAddress(String),
+    /// The host name couldn't be resolved.
+    HostNotFound(String),
+    ConnectionRefused(String),
+    TimedOut(String),
+    /// The proxy couldn't be reached, refused the login or couldn't connect to the target.
+    Proxy(String),
+    Tls(String),
+    /// The identity of the remote side needs to be trusted by the user first.
+    UnknownHost(Box<UnknownHost>),
+    /// The SSH host key differs from the one in the known hosts file, the old key needs to be removed there.
+    HostKeyChanged {
+        host: String,
+        fingerprint: String,
+        known_hosts_file: String,
+    },
+    AuthenticationFailed(String),
+    /// The user canceled a login prompt or the dialing of a modem.
+    Canceled,
+    /// The modem reported an error while dialing.
+    Dial(DialError),
+    /// The carrier detect line dropped.
+    CarrierLost,
+    /// The remote side closed the connection, with the reason if it sent one.
+    ClosedByRemote(Option<String>),
+    ConnectionLost(String),
+    /// The local process exited, with the exit status if it's known.
+    ProcessExited(Option<String>),
+    /// The remote side violated the protocol or the handshake failed.
+    Protocol(String),
+    Unsupported(String),
+    Io(String),
+}
+
+impl ConnectionError {
+    /// Dialing again won't help if the address or the login is wrong, or the user has to decide something first.
+    pub fn can_redial(&self) -> bool {
+        !matches!(
+            self,
+            ConnectionError::InvalidAddress(_)
+                | ConnectionError::Tls(_)
+                | ConnectionError::UnknownHost(_)
+                | ConnectionError::HostKeyChanged { .. }
+                | ConnectionError::AuthenticationFailed(_)
+                | ConnectionError::Canceled
+                | ConnectionError::Unsupported(_)
+        )
+    }
+
+    pub fn get_localized_message(&self) -> String {
+        match self {
+            ConnectionError::InvalidAddress(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-invalid-address", details = details.clone()),
+            ConnectionError::HostNotFound(host) => fl!(crate::LANGUAGE_LOADER, "connection-error-host-not-found", host = host.clone()),
+            ConnectionError::ConnectionRefused(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-refused", details = details.clone()),
+            ConnectionError::TimedOut(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-timed-out", details = details.clone()),
+            ConnectionError::Proxy(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-proxy", details = details.clone()),
+            ConnectionError::Tls(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-tls", details = details.clone()),
+            ConnectionError::UnknownHost(unknown_host) => fl!(crate::LANGUAGE_LOADER, "connection-error-unknown-host", host = unknown_host.host.clone()),
+            ConnectionError::HostKeyChanged {
+                host,
+                fingerprint,
+                known_hosts_file,
+            } => fl!(
+                crate::LANGUAGE_LOADER,
+                "connection-error-host-key-changed",
+                host = host.clone(),
+                fingerprint = fingerprint.clone(),
+                file = known_hosts_file.clone()
+            ),
+            ConnectionError::AuthenticationFailed(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-authentication-failed", details = details.clone()),
+            ConnectionError::Canceled => fl!(crate::LANGUAGE_LOADER, "connection-error-canceled"),
+            ConnectionError::Dial(err) => match err.result {
+                Some(ResultCode::Busy) => fl!(crate::LANGUAGE_LOADER, "connection-error-busy"),
+                Some(ResultCode::NoAnswer) => fl!(crate::LANGUAGE_LOADER, "connection-error-no-answer"),
+                Some(ResultCode::NoCarrier) => fl!(crate::LANGUAGE_LOADER, "connection-error-no-carrier"),
+                Some(ResultCode::NoDialtone) => fl!(crate::LANGUAGE_LOADER, "connection-error-no-dialtone"),
+                _ => fl!(crate::LANGUAGE_LOADER, "connection-error-modem", details = err.to_string()),
+            },
+            ConnectionError::CarrierLost => fl!(crate::LANGUAGE_LOADER, "connection-error-carrier-lost"),
+            ConnectionError::ClosedByRemote(None) => fl!(crate::LANGUAGE_LOADER, "connection-error-closed"),
+            ConnectionError::ClosedByRemote(Some(reason)) => fl!(crate::LANGUAGE_LOADER, "connection-error-closed-reason", reason = reason.clone()),
+            ConnectionError::ConnectionLost(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-lost", details = details.clone()),
+            ConnectionError::ProcessExited(None) => fl!(crate::LANGUAGE_LOADER, "connection-error-process-exited"),
+            ConnectionError::ProcessExited(Some(status)) => fl!(crate::LANGUAGE_LOADER, "connection-error-process-exited-status", status = status.clone()),
+            ConnectionError::Protocol(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-protocol", details = details.clone()),
+            ConnectionError::Unsupported(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-unsupported", details = details.clone()),
+            ConnectionError::Io(details) => fl!(crate::LANGUAGE_LOADER, "connection-error-io", details = details.clone()),
+        }
+    }
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::InvalidAddress(details) => write!(f, "Invalid address: {details}"),
+            ConnectionError::HostNotFound(host) => write!(f, "Can't resolve {host}"),
+            ConnectionError::ConnectionRefused(details) => write!(f, "Connection refused: {details}"),
+            ConnectionError::TimedOut(details) => write!(f, "Connection timed out: {details}"),
+            ConnectionError::Proxy(details) => write!(f, "Proxy error: {details}"),
+            ConnectionError::Tls(details) => write!(f, "TLS error: {details}"),
+            ConnectionError::UnknownHost(unknown_host) => write!(f, "{unknown_host}"),
+            ConnectionError::HostKeyChanged {
+                host,
+                fingerprint,
+                known_hosts_file,
+            } => write!(
+                f,
+                "The host key of {host} has changed (fingerprint {fingerprint}). Someone could be intercepting the connection. Remove the old key from {known_hosts_file} if the change is expected."
+            ),
+            ConnectionError::AuthenticationFailed(details) => write!(f, "Authentication failed: {details}"),
+            ConnectionError::Canceled => write!(f, "Connecting canceled."),
+            ConnectionError::Dial(err) => write!(f, "{err}"),
+            ConnectionError::CarrierLost => write!(f, "Carrier lost."),
+            ConnectionError::ClosedByRemote(None) => write!(f, "Connection closed by remote host"),
+            ConnectionError::ClosedByRemote(Some(reason)) => write!(f, "Connection closed by remote host: {reason}"),
+            ConnectionError::ConnectionLost(details) => write!(f, "Connection lost: {details}"),
+            ConnectionError::ProcessExited(None) => write!(f, "Process exited"),
+            ConnectionError::ProcessExited(Some(status)) => write!(f, "Process exited: {status}"),
+            ConnectionError::Protocol(details) => write!(f, "Protocol error: {details}"),
+            ConnectionError::Unsupported(details) => write!(f, "Not supported: {details}"),
+            ConnectionError::Io(details) => write!(f, "{details}"),
+        }
+    }
+}
+
+impl Error for ConnectionError {}
+
+impl From<io::Error> for ConnectionError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::ConnectionRefused => ConnectionError::ConnectionRefused(err.to_string()),
+            ErrorKind::TimedOut => ConnectionError::TimedOut(err.to_string()),
+            ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::BrokenPipe | ErrorKind::NotConnected | ErrorKind::UnexpectedEof => {
+                ConnectionError::ConnectionLost(err.to_string())
+            }
+            ErrorKind::Unsupported => ConnectionError::Unsupported(err.to_string()),
+            _ => ConnectionError::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<UnknownHost> for ConnectionError {
+    fn from(unknown_host: UnknownHost) -> Self {
+        ConnectionError::UnknownHost(Box::new(unknown_host))
+    }
+}
+
+impl From<DialError> for ConnectionError {
+    fn from(err: DialError) -> Self {
+        ConnectionError::Dial(err)
+    }
+}
+
+impl From<serial::Error> for ConnectionError {
+    fn from(err: serial::Error) -> Self {
+        match err.kind() {
+            serial::ErrorKind::InvalidInput => ConnectionError::Unsupported(err.to_string()),
+            serial::ErrorKind::NoDevice | serial::ErrorKind::Io(_) => ConnectionError::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<rustls::Error> for ConnectionError {
+    fn from(err: rustls::Error) -> Self {
+        ConnectionError::Tls(err.to_string())
+    }
+}
+
+impl From<tungstenite::Error> for ConnectionError {
+    fn from(err: tungstenite::Error) -> Self {
+        match err {
+            tungstenite::Error::Io(err) => err.into(),
+            tungstenite::Error::Tls(err) => ConnectionError::Tls(err.to_string()),
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => ConnectionError::ClosedByRemote(None),
+            tungstenite::Error::Url(err) => ConnectionError::InvalidAddress(err.to_string()),
+            tungstenite::Error::Http(response) => ConnectionError::ConnectionRefused(format!("HTTP {}", response.status())),
+            err => ConnectionError::Protocol(err.to_string()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<libssh_rs::Error> for ConnectionError {
+    fn from(err: libssh_rs::Error) -> Self {
+        ConnectionError::Protocol(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::com::DialState;
+
+    #[test]
+    fn test_from_io_error() {
+        let err = |kind| ConnectionError::from(io::Error::new(kind, "details"));
+        assert_eq!(ConnectionError::ConnectionRefused("details".to_string()), err(ErrorKind::ConnectionRefused));
+        assert_eq!(ConnectionError::TimedOut("details".to_string()), err(ErrorKind::TimedOut));
+        assert_eq!(ConnectionError::ConnectionLost("details".to_string()), err(ErrorKind::ConnectionReset));
+        assert_eq!(ConnectionError::ConnectionLost("details".to_string()), err(ErrorKind::BrokenPipe));
+        assert_eq!(ConnectionError::Io("details".to_string()), err(ErrorKind::PermissionDenied));
+    }
+
+    #[test]
+    fn test_can_redial() {
+        assert!(ConnectionError::TimedOut(String::new()).can_redial());
+        assert!(ConnectionError::CarrierLost.can_redial());
+        assert!(ConnectionError::Dial(DialError {
+            state: DialState::Dialing,
+            result: Some(ResultCode::Busy)
+        })
+        .can_redial());
+        assert!(!ConnectionError::AuthenticationFailed(String::new()).can_redial());
+        assert!(!ConnectionError::Canceled.can_redial());
+    }
+}
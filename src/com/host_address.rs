@@ -0,0 +1,229 @@
+use std::fmt::Display;
+
+use super::{ConnectionError, TermComResult};
+use crate::addresses::Protocol;
+
+/// The remote end of a connection, parsed from the address of a phonebook entry.
+///
+/// Understands 'host', 'host:port', bracketed IPv6 ('[::1]:23'), bare IPv6 without port
+/// and URL forms like 'telnet://host:port/' where the scheme decides the default port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostAddress {
+    /// Host name or IP address, IPv6 addresses without brackets
+    pub host: String,
+    pub port: u16,
+    /// Path and query of an URL including the leading '/' or '?', empty if there is none
+    pub path: String,
+}
+
+impl HostAddress {
+    /// Parses an address, a missing port is taken from the protocol.
+    /// The scheme of an URL has to fit the protocol, 'ssh://host' can't be connected with telnet.
+    pub fn parse(address: &str, protocol: Protocol) -> TermComResult<Self> {
+        if let Some((scheme, _)) = address.trim().split_once("://") {
+            if !scheme_matches_protocol(scheme, protocol) {
+                return Err(invalid_address(address, &format!("the URL scheme doesn't fit the protocol {protocol}")));
+            }
+        }
+        Self::parse_with_default_port(address, protocol.default_port())
+    }
+
+    pub fn parse_with_default_port(address: &str, default_port: Option<u16>) -> TermComResult<Self> {
+        let mut rest = address.trim();
+        let mut default_port = default_port;
+        if let Some((scheme, r)) = rest.split_once("://") {
+            if let Some(port) = get_scheme_default_port(scheme)? {
+                default_port = Some(port);
+            }
+            rest = r;
+        }
+
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        // user names are configured separately, ignore them in 'ssh://user@host' forms
+        let authority = authority.rsplit_once('@').map_or(authority, |(_, a)| a);
+
+        let (host, port) = if let Some(r) = authority.strip_prefix('[') {
+            let Some((host, r)) = r.split_once(']') else {
+                return Err(invalid_address(address, "missing ']'"));
+            };
+            if r.is_empty() {
+                (host, None)
+            } else if let Some(port) = r.strip_prefix(':') {
+                (host, Some(port))
+            } else {
+                return Err(invalid_address(address, "unexpected characters after ']'"));
+            }
+        } else if authority.matches(':').count() > 1 {
+            // bare IPv6 address, can't have a port
+            (authority, None)
+        } else if let Some((host, port)) = authority.split_once(':') {
+            (host, Some(port))
+        } else {
+            (authority, None)
+        };
+
+        if host.is_empty() {
+            return Err(invalid_address(address, "missing host"));
+        }
+        let port = match port {
+            Some(port) => match port.parse::<u16>() {
+                Ok(port) => port,
+                Err(_) => return Err(invalid_address(address, &format!("invalid port '{port}'"))),
+            },
+            None => match default_port {
+                Some(port) => port,
+                None => return Err(invalid_address(address, "missing port")),
+            },
+        };
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    #[must_use]
+    pub fn is_ipv6(&self) -> bool {
+        self.host.contains(':')
+    }
+}
+
+impl Display for HostAddress {
+    /// 'host:port' - can be used for name resolution.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_ipv6() {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// `None` for schemes without a well known port, the protocol default is used then.
+fn get_scheme_default_port(scheme: &str) -> TermComResult<Option<u16>> {
+    match scheme.to_lowercase().as_str() {
+        "telnet" => Ok(Some(23)),
+        "telnets" => Ok(Some(super::TELNETS_PORT)),
+        "ssh" => Ok(Some(22)),
+        "rlogin" => Ok(Some(513)),
+        "ws" => Ok(Some(80)),
+        "wss" => Ok(Some(443)),
+        "raw" => Ok(None),
+        _ => Err(ConnectionError::Unsupported(format!("URL scheme {scheme}"))),
+    }
+}
+
+/// Unknown schemes match any protocol, they get rejected while parsing.
+fn scheme_matches_protocol(scheme: &str, protocol: Protocol) -> bool {
+    match scheme.to_lowercase().as_str() {
+        "telnet" => protocol == Protocol::Telnet,
+        "telnets" => protocol == Protocol::TelnetTls,
+        "ssh" => protocol == Protocol::Ssh,
+        "rlogin" => protocol == Protocol::Rlogin,
+        // the scheme decides whether a websocket uses TLS
+        "ws" | "wss" => matches!(protocol, Protocol::WebSocket(_)),
+        "raw" => matches!(protocol, Protocol::Raw | Protocol::RawTls),
+        _ => true,
+    }
+}
+
+fn invalid_address(address: &str, reason: &str) -> ConnectionError {
+    ConnectionError::InvalidAddress(format!("'{address}' ({reason})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(address: &str, protocol: Protocol) -> (String, u16) {
+        let result = HostAddress::parse(address, protocol).unwrap();
+        (result.host, result.port)
+    }
+
+    #[test]
+    fn test_host_and_port() {
+        assert_eq!(("bbs.example.com".to_string(), 2323), parse("bbs.example.com:2323", Protocol::Telnet));
+        assert_eq!(("10.0.0.1".to_string(), 22), parse("10.0.0.1:22", Protocol::Telnet));
+        assert_eq!(("bbs.example.com".to_string(), 2323), parse("  bbs.example.com:2323 ", Protocol::Telnet));
+    }
+
+    #[test]
+    fn test_default_ports() {
+        assert_eq!(("bbs.example.com".to_string(), 23), parse("bbs.example.com", Protocol::Telnet));
+        assert_eq!(("bbs.example.com".to_string(), 992), parse("bbs.example.com", Protocol::TelnetTls));
+        assert_eq!(("bbs.example.com".to_string(), 22), parse("bbs.example.com", Protocol::Ssh));
+        assert_eq!(("bbs.example.com".to_string(), 443), parse("bbs.example.com", Protocol::WebSocket(true)));
+        assert_eq!(("bbs.example.com".to_string(), 80), parse("bbs.example.com", Protocol::WebSocket(false)));
+        assert!(HostAddress::parse("bbs.example.com", Protocol::Raw).is_err());
+    }
+
+    #[test]
+    fn test_ipv6() {
+        assert_eq!(("::1".to_string(), 22), parse("[::1]:22", Protocol::Telnet));
+        assert_eq!(("::1".to_string(), 23), parse("[::1]", Protocol::Telnet));
+        assert_eq!(("::1".to_string(), 23), parse("::1", Protocol::Telnet));
+        assert_eq!(("2001:db8::23".to_string(), 22), parse("2001:db8::23", Protocol::Ssh));
+        assert!(HostAddress::parse("[::1", Protocol::Telnet).is_err());
+        assert!(HostAddress::parse("[::1]22", Protocol::Telnet).is_err());
+    }
+
+    #[test]
+    fn test_urls() {
+        assert_eq!(("bbs.example.com".to_string(), 2323), parse("telnet://bbs.example.com:2323", Protocol::Telnet));
+        assert_eq!(("bbs.example.com".to_string(), 23), parse("telnet://bbs.example.com/", Protocol::Telnet));
+        assert_eq!(("bbs.example.com".to_string(), 992), parse("TELNETS://bbs.example.com", Protocol::TelnetTls));
+        assert_eq!(("bbs.example.com".to_string(), 22), parse("ssh://sysop@bbs.example.com", Protocol::Ssh));
+        assert_eq!(("::1".to_string(), 2222), parse("ssh://[::1]:2222", Protocol::Ssh));
+        assert_eq!(("bbs.example.com".to_string(), 513), parse("rlogin://bbs.example.com", Protocol::Rlogin));
+        assert_eq!(("bbs.example.com".to_string(), 6400), parse("raw://bbs.example.com:6400", Protocol::Raw));
+
+        let result = HostAddress::parse("wss://bbs.example.com/ws/term?node=1", Protocol::WebSocket(true)).unwrap();
+        assert_eq!("bbs.example.com", result.host);
+        assert_eq!(443, result.port);
+        assert_eq!("/ws/term?node=1", result.path);
+
+        let result = HostAddress::parse("ws://bbs.example.com:8080?node=1", Protocol::WebSocket(true)).unwrap();
+        assert_eq!(8080, result.port);
+        assert_eq!("?node=1", result.path);
+
+        assert!(HostAddress::parse("gopher://bbs.example.com", Protocol::Telnet).is_err());
+    }
+
+    #[test]
+    fn test_url_scheme_mismatch() {
+        for (address, protocol) in [
+            ("ssh://bbs.example.com", Protocol::Telnet),
+            ("telnet://bbs.example.com", Protocol::Ssh),
+            ("telnets://bbs.example.com", Protocol::Telnet),
+            ("telnet://bbs.example.com", Protocol::TelnetTls),
+            ("rlogin://bbs.example.com", Protocol::Telnet),
+            ("wss://bbs.example.com", Protocol::Telnet),
+            ("raw://bbs.example.com:6400", Protocol::Ssh),
+        ] {
+            let result = HostAddress::parse(address, protocol);
+            assert!(matches!(result, Err(ConnectionError::InvalidAddress(_))), "{address} accepted for {protocol}");
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(HostAddress::parse("", Protocol::Telnet).is_err());
+        assert!(HostAddress::parse(":23", Protocol::Telnet).is_err());
+        assert!(HostAddress::parse("bbs.example.com:port", Protocol::Telnet).is_err());
+        assert!(HostAddress::parse("bbs.example.com:65536", Protocol::Telnet).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            "bbs.example.com:23",
+            HostAddress::parse("bbs.example.com", Protocol::Telnet).unwrap().to_string()
+        );
+        assert_eq!("[::1]:23", HostAddress::parse("::1", Protocol::Telnet).unwrap().to_string());
+        assert_eq!("[::1]:2222", HostAddress::parse("ssh://[::1]:2222/", Protocol::Ssh).unwrap().to_string());
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Write},
+    os::fd::{AsRawFd, FromRawFd},
+    os::unix::process::CommandExt,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use icy_engine::Size;
+
+use super::{Com, ConnectionError, OpenConnectionData, TermComResult, READ_BUFFER_SIZE};
+
+/// Time a process gets to exit after the hang up before it's killed.
+const EXIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Runs the command line of the address in a pseudo terminal, like a BBS or door game
+/// that's tested locally without a telnet daemon in between.
+pub struct ComLocalProcessImpl {
+    master: File,
+    child: Child,
+    window_size: Size,
+    /// Terminal settings of the pty before the raw mode got switched on
+    cooked_termios: Option<libc::termios>,
+    read_buffer: Vec<u8>,
+}
+
+impl ComLocalProcessImpl {
+    pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
+        let command_line = connection_data.address.trim();
+        if command_line.is_empty() {
+            return Err(ConnectionError::InvalidAddress("no command line to run".to_string()));
+        }
+
+        let winsize = to_winsize(connection_data.window_size);
+        let mut master = 0;
+        let mut slave = 0;
+        let result = unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &winsize) };
+        if result != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        // openpty doesn't set close-on-exec, the process and its children would inherit the master and a copy of the slave
+        for fd in [master.as_raw_fd(), slave.as_raw_fd()] {
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+
+        // terminfo names are lower case, the telnet terminal type list isn't
+        let terminal_type = connection_data.terminal_type_names.first().map_or("ansi", String::as_str).to_lowercase();
+        let mut command = Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg(command_line)
+            .env("TERM", terminal_type)
+            .envs(connection_data.environment.iter().cloned())
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        // the process gets its own session with the pty as controlling terminal, that's required for job control and SIGWINCH
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn()?;
+        // drops the slave descriptors, reading the master fails once the process exited
+        drop(command);
+
+        if unsafe { libc::fcntl(master.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            master,
+            child,
+            window_size: connection_data.window_size,
+            cooked_termios: None,
+            read_buffer: vec![0; READ_BUFFER_SIZE],
+        })
+    }
+
+    fn exit_error(&mut self) -> ConnectionError {
+        let status = match self.child.try_wait() {
+            Ok(Some(status)) => Some(status.to_string()),
+            _ => None,
+        };
+        ConnectionError::ProcessExited(status)
+    }
+}
+
+fn to_winsize(window_size: Size) -> libc::winsize {
+    libc::winsize {
+        ws_row: window_size.height as u16,
+        ws_col: window_size.width as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+impl Com for ComLocalProcessImpl {
+    fn get_name(&self) -> &'static str {
+        "Local process"
+    }
+
+    fn default_port(&self) -> u16 {
+        0
+    }
+
+    fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
+
+    /// Binary transfers need the line discipline off, otherwise CR/LF and control characters get translated.
+    fn set_raw_mode(&mut self, raw_transfer: bool) {
+        let fd = self.master.as_raw_fd();
+        if raw_transfer {
+            if self.cooked_termios.is_some() {
+                return;
+            }
+            let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+            if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+                log::error!("Can't get the terminal settings: {}", io::Error::last_os_error());
+                return;
+            }
+            self.cooked_termios = Some(termios);
+            unsafe { libc::cfmakeraw(&mut termios) };
+            if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+                log::error!("Can't switch to raw mode: {}", io::Error::last_os_error());
+            }
+        } else if let Some(termios) = self.cooked_termios.take() {
+            if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+                log::error!("Can't restore the terminal settings: {}", io::Error::last_os_error());
+            }
+        }
+    }
+
+    fn set_window_size(&mut self, window_size: Size) -> TermComResult<()> {
+        if self.window_size == window_size {
+            return Ok(());
+        }
+        self.window_size = window_size;
+        // the kernel sends SIGWINCH to the foreground process group
+        let winsize = to_winsize(window_size);
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ as _, &winsize) } == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
+        match self.master.read(&mut self.read_buffer) {
+            Ok(0) => Err(self.exit_error()),
+            Ok(size) => Ok(Some(self.read_buffer[0..size].to_vec())),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            // linux reports EIO once the last process using the pty exited
+            Err(_) => Err(self.exit_error()),
+        }
+    }
+
+    fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
+        let mut data = buf;
+        while !data.is_empty() {
+            match self.master.write(data) {
+                Ok(size) => data = &data[size..],
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(10)),
+                Err(_) => return Err(self.exit_error()),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn disconnect(&mut self) -> TermComResult<()> {
+        if self.child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        // the process is the leader of its own process group
+        unsafe { libc::kill(-(self.child.id() as libc::pid_t), libc::SIGHUP) };
+        let start = std::time::Instant::now();
+        while start.elapsed() < EXIT_TIMEOUT {
+            if self.child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        Some(self.master.as_raw_fd())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{addresses::Protocol, Address, Proxy};
+
+    fn start(command_line: &str) -> ComLocalProcessImpl {
+        let mut address = Address::new("test");
+        address.address = command_line.to_string();
+        address.protocol = Protocol::LocalProcess;
+        let connection_data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        ComLocalProcessImpl::connect(&connection_data).unwrap()
+    }
+
+    fn read_until(com: &mut ComLocalProcessImpl, expected: &str) -> String {
+        let mut output = String::new();
+        let start = std::time::Instant::now();
+        while !output.contains(expected) {
+            assert!(start.elapsed() < Duration::from_secs(5), "'{expected}' not received, got '{output}'");
+            match com.read_data() {
+                Ok(Some(data)) => output.push_str(&String::from_utf8_lossy(&data)),
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(err) => panic!("{err}, got '{output}'"),
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_run_process() {
+        let mut com = start("stty size; echo $TERM; read line; echo \"got $line\"; read line; stty size");
+        let output = read_until(&mut com, "ansi");
+        assert!(output.contains("25 80"), "{output}");
+
+        com.send(b"hello\r").unwrap();
+        read_until(&mut com, "got hello");
+
+        com.set_window_size(Size::new(132, 50)).unwrap();
+        com.send(b"\r").unwrap();
+        read_until(&mut com, "50 132");
+
+        let start = std::time::Instant::now();
+        let err = loop {
+            assert!(start.elapsed() < Duration::from_secs(5), "process didn't exit");
+            match com.read_data() {
+                Ok(_) => std::thread::sleep(Duration::from_millis(10)),
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(err, ConnectionError::ProcessExited(_)), "{err}");
+    }
+
+    #[test]
+    fn test_pty_not_inherited() {
+        let mut com = start("read fd; [ -e /proc/$$/fd/$fd ] && echo leaked || echo closed");
+        com.send(format!("{}\r", com.master.as_raw_fd()).as_bytes()).unwrap();
+        let output = read_until(&mut com, "closed");
+        assert!(!output.contains("leaked"), "{output}");
+    }
+
+    #[test]
+    fn test_raw_mode() {
+        let mut com = start("sleep 10");
+        let fd = com.master.as_raw_fd();
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+
+        com.set_raw_mode(true);
+        assert_eq!(0, unsafe { libc::tcgetattr(fd, &mut termios) });
+        assert_eq!(0, termios.c_lflag & (libc::ICANON | libc::ECHO));
+
+        com.set_raw_mode(false);
+        assert_eq!(0, unsafe { libc::tcgetattr(fd, &mut termios) });
+        assert_ne!(0, termios.c_lflag & libc::ICANON);
+
+        com.disconnect().unwrap();
+        assert!(com.child.try_wait().unwrap().is_some(), "process got hung up");
+    }
+}
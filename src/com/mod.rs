@@ -5,22 +5,59 @@ pub mod tests;
 #[cfg(test)]
 pub use tests::*;
 
+pub mod connection_error;
+pub use connection_error::*;
+
 pub mod telnet;
 pub use telnet::*;
 
+pub mod telnet_parser;
+pub use telnet_parser::*;
+
 pub mod raw;
 pub use raw::*;
 
+pub mod tls;
+pub use tls::*;
+
+pub mod proxy;
+pub use proxy::*;
+
+pub mod tcp;
+pub use tcp::*;
+
+pub mod host_address;
+pub use host_address::*;
+
 pub mod modem;
 pub use modem::*;
 
+pub mod serial_port;
+pub use serial_port::*;
+
+pub mod virtual_modem;
+pub use virtual_modem::*;
+
 pub mod websocket;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod ssh;
 
-use crate::{addresses::Terminal, ui::connect::OpenConnectionData};
-pub type TermComResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rlogin;
+
+#[cfg(target_os = "linux")]
+pub mod local_process;
+
+use crate::{
+    addresses::{CharacterSet, Terminal},
+    ui::connect::OpenConnectionData,
+};
+use icy_engine::Size;
+pub type TermComResult<T> = Result<T, ConnectionError>;
+
+/// Size of the buffer a connection reads into, it's allocated once per connection.
+pub const READ_BUFFER_SIZE: usize = 64 * 1024;
 
 pub trait Com {
     fn get_name(&self) -> &'static str;
@@ -33,58 +70,142 @@ pub trait Com {
     fn disconnect(&mut self) -> TermComResult<()>;
 
     fn set_raw_mode(&mut self, _raw_transfer: bool) {}
-}
-pub struct NullConnection {}
-impl Com for NullConnection {
-    fn get_name(&self) -> &'static str {
-        ""
+
+    /// Tells the remote side about a changed terminal size (NAWS, pty resize…).
+    fn set_window_size(&mut self, _window_size: Size) -> TermComResult<()> {
+        Ok(())
     }
 
-    fn send(&mut self, _buf: &[u8]) -> TermComResult<usize> {
-        Ok(0)
+    /// Returns the character set that got agreed on with the remote side since the last call.
+    fn take_character_set(&mut self) -> Option<CharacterSet> {
+        None
     }
 
-    fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
-        Ok(Some(Vec::new()))
+    /// Whether the remote side performs the telnet option `opt` (see [`telnet_option`]), like ECHO.
+    /// `None` for connections without telnet option negotiation.
+    fn remote_option_enabled(&self, _opt: u8) -> Option<bool> {
+        None
     }
 
-    fn set_terminal_type(&mut self, _terminal: Terminal) {}
+    /// Returns the line speed the connection got established with, like the speed of a modem CONNECT.
+    fn take_connect_speed(&mut self) -> Option<u32> {
+        None
+    }
 
-    fn disconnect(&mut self) -> TermComResult<()> {
+    /// The state of the RS-232 control lines, `None` if the connection doesn't have any.
+    fn line_status(&mut self) -> Option<LineStatus> {
+        None
+    }
+
+    /// Sends a break signal, only serial connections have one.
+    fn send_break(&mut self) -> TermComResult<()> {
         Ok(())
     }
 
-    fn default_port(&self) -> u16 {
-        0
+    /// Sets the DTR and RTS lines of serial connections.
+    fn set_control_lines(&mut self, _dtr: bool, _rts: bool) -> TermComResult<()> {
+        Ok(())
+    }
+
+    /// The socket or device the com thread waits on for incoming data, connections without one get read in intervals.
+    /// [`Com::read_data`] is called until it returns `None` before the thread waits, data that is buffered
+    /// inside the connection at that point needs to show up on the descriptor as well.
+    #[cfg(unix)]
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        None
+    }
+}
+
+/// Control lines the DCE (modem or null-modem cable) signals to the terminal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineStatus {
+    /// Data carrier detect
+    pub dcd: bool,
+    /// Data set ready
+    pub dsr: bool,
+    /// Clear to send
+    pub cts: bool,
+    /// Ring indicator
+    pub ri: bool,
+}
+
+impl LineStatus {
+    /// The carrier got lost - DCD dropped after it was up.
+    pub fn carrier_lost(previous: Option<LineStatus>, current: LineStatus) -> bool {
+        previous.is_some_and(|previous| previous.dcd) && !current.dcd
     }
 }
-/*
-#[derive(Debug, Clone, Copy)]
-pub enum ConnectionError {
-    ConnectionLost,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostIdentity {
+    Certificate,
+    SshHostKey,
+}
+
+/// The identity of the remote side couldn't be verified - it's unknown or has changed.
+/// Connecting fails with this error so the user can decide whether to trust the host,
+/// a trusted fingerprint is passed to the next connection attempt in [`OpenConnectionData`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownHost {
+    pub kind: HostIdentity,
+    /// 'host:port' the identity belongs to
+    pub host: String,
+    pub reason: String,
+    pub fingerprint: String,
+    /// The fingerprint that was trusted before, if the identity has changed.
+    pub known_fingerprint: Option<String>,
 }
 
-impl std::fmt::Display for ConnectionError {
+impl std::fmt::Display for UnknownHost {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConnectionError::ConnectionLost => {
-                write!(f, "connection lost")
-            }
+        if self.known_fingerprint.is_some() {
+            write!(
+                f,
+                "The identity of {} has changed! {} (fingerprint {})",
+                self.host, self.reason, self.fingerprint
+            )
+        } else {
+            write!(
+                f,
+                "The identity of {} is unknown. {} (fingerprint {})",
+                self.host, self.reason, self.fingerprint
+            )
         }
     }
 }
 
-impl Error for ConnectionError {
-    fn description(&self) -> &str {
-        "use std::display"
+impl Error for UnknownHost {}
+
+/// Questions of a keyboard-interactive login that need to be answered by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthPrompt {
+    pub host: String,
+    pub name: String,
+    pub instruction: String,
+    /// Prompt text and whether the answer may be shown while typing.
+    pub prompts: Vec<(String, bool)>,
+}
+
+pub struct NullConnection {}
+impl Com for NullConnection {
+    fn get_name(&self) -> &'static str {
+        ""
+    }
+
+    fn send(&mut self, _buf: &[u8]) -> TermComResult<usize> {
+        Ok(0)
     }
 
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+    fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
+        Ok(None)
     }
 
-    fn cause(&self) -> Option<&dyn Error> {
-        self.source()
+    fn set_terminal_type(&mut self, _terminal: Terminal) {}
+
+    fn disconnect(&mut self) -> TermComResult<()> {
+        Ok(())
+    }
+
+    fn default_port(&self) -> u16 {
+        0
     }
 }
-*/
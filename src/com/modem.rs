@@ -2,33 +2,282 @@
 
 use crate::Modem;
 
-use super::{Com, OpenConnectionData, TermComResult};
-use serial::prelude::*;
-use std::io::{Read, Write};
+use super::{serial_port::open_port, Com, ConnectionError, LineStatus, OpenConnectionData, TermComResult};
+use serial::SerialPort;
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+/// Modems give up on their own after S7 seconds (usually 50), this is just the last resort.
+const DIAL_TIMEOUT: Duration = Duration::from_secs(90);
+/// Silence required before and after the '+++' escape (S12, 1s by default).
+const ESCAPE_GUARD_TIME: Duration = Duration::from_millis(1100);
+const DTR_DROP_TIME: Duration = Duration::from_millis(500);
+
+/// Final result codes of a Hayes compatible modem, verbose (ATV1) or numeric (ATV0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultCode {
+    Ok,
+    /// The connect speed if the modem reported one ('CONNECT 14400').
+    Connect(Option<u32>),
+    Ring,
+    NoCarrier,
+    Error,
+    NoDialtone,
+    Busy,
+    NoAnswer,
+}
+
+impl ResultCode {
+    /// Returns `None` for lines that aren't result codes, like the command echo or 'PROTOCOL: LAPM'.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim().to_ascii_uppercase();
+        match line.as_str() {
+            "OK" | "0" => Some(Self::Ok),
+            "CONNECT" | "1" => Some(Self::Connect(None)),
+            "RING" | "2" => Some(Self::Ring),
+            "NO CARRIER" | "3" => Some(Self::NoCarrier),
+            "ERROR" | "4" => Some(Self::Error),
+            "NO DIALTONE" | "NO DIAL TONE" | "6" => Some(Self::NoDialtone),
+            "BUSY" | "7" => Some(Self::Busy),
+            "NO ANSWER" | "8" => Some(Self::NoAnswer),
+            _ => {
+                // 'CONNECT 14400', 'CONNECT 33600/ARQ/V34/LAPM/V42BIS'
+                let speed = line.strip_prefix("CONNECT ")?;
+                let digits: String = speed.chars().take_while(char::is_ascii_digit).collect();
+                Some(Self::Connect(digits.parse().ok()))
+            }
+        }
+    }
+
+    /// The result code in the numeric form (ATV0).
+    pub fn numeric(&self) -> u8 {
+        match self {
+            ResultCode::Ok => 0,
+            ResultCode::Connect(_) => 1,
+            ResultCode::Ring => 2,
+            ResultCode::NoCarrier => 3,
+            ResultCode::Error => 4,
+            ResultCode::NoDialtone => 6,
+            ResultCode::Busy => 7,
+            ResultCode::NoAnswer => 8,
+        }
+    }
+}
+
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultCode::Ok => write!(f, "OK"),
+            ResultCode::Connect(Some(speed)) => write!(f, "CONNECT {speed}"),
+            ResultCode::Connect(None) => write!(f, "CONNECT"),
+            ResultCode::Ring => write!(f, "RING"),
+            ResultCode::NoCarrier => write!(f, "NO CARRIER"),
+            ResultCode::Error => write!(f, "ERROR"),
+            ResultCode::NoDialtone => write!(f, "NO DIALTONE"),
+            ResultCode::Busy => write!(f, "BUSY"),
+            ResultCode::NoAnswer => write!(f, "NO ANSWER"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialState {
+    Idle,
+    Initializing,
+    Dialing,
+    Connected,
+    HangingUp,
+}
+
+impl fmt::Display for DialState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialState::Idle => write!(f, "idle"),
+            DialState::Initializing => write!(f, "initializing"),
+            DialState::Dialing => write!(f, "dialing"),
+            DialState::Connected => write!(f, "connected"),
+            DialState::HangingUp => write!(f, "hanging up"),
+        }
+    }
+}
+
+/// The modem answered with something else than expected or not at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialError {
+    pub state: DialState,
+    /// `None` if the modem didn't answer in time.
+    pub result: Option<ResultCode>,
+}
+
+impl fmt::Display for DialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.result {
+            Some(ResultCode::Busy) => write!(f, "The line is busy."),
+            Some(ResultCode::NoAnswer) => write!(f, "No answer."),
+            Some(ResultCode::NoCarrier) => write!(f, "No carrier."),
+            Some(ResultCode::NoDialtone) => write!(f, "No dial tone."),
+            Some(result) => write!(f, "Modem answered {result} while {}.", self.state),
+            None => write!(f, "Modem didn't answer while {}.", self.state),
+        }
+    }
+}
+
+impl Error for DialError {}
 
 pub struct ComModemImpl {
     modem: Modem,
-    port: Box<dyn serial::SerialPort>,
+    port: serial::SystemPort,
+    state: DialState,
+    connect_speed: Option<u32>,
+    /// Received bytes that weren't consumed by the result code parser.
+    received: Vec<u8>,
 }
 
 impl ComModemImpl {
-    pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
-        let modem = connection_data.modem.as_ref().unwrap().clone();
-        let mut port = serial::open(&modem.device)?;
-        port.reconfigure(&|settings| {
-            settings.set_baud_rate(serial::BaudRate::from_speed(modem.baud_rate))?;
-            settings.set_char_size(modem.char_size);
-            settings.set_parity(modem.parity);
-            settings.set_stop_bits(modem.stop_bits);
-            settings.set_flow_control(modem.flow_control);
-            Ok(())
-        })?;
-        port.write_all(modem.init_string.as_bytes())?;
-        port.write_all(b"\n")?;
-        port.write_all(modem.dial_string.as_bytes())?;
-        port.write_all(connection_data.address.as_bytes())?;
-        port.write_all(b"\n")?;
-        Ok(Self { modem, port: Box::new(port) })
+    /// Dials the number of the address, `is_canceled` is polled while waiting for the modem and hangs up once it returns `true`.
+    pub fn connect(connection_data: &OpenConnectionData, is_canceled: &mut dyn FnMut() -> bool) -> TermComResult<Self> {
+        let Some(modem) = connection_data.modem.clone() else {
+            return Err(ConnectionError::InvalidAddress("no modem configured".to_string()));
+        };
+        let port = open_port(&modem)?;
+        let mut com = Self {
+            modem,
+            port,
+            state: DialState::Idle,
+            connect_speed: None,
+            received: Vec::new(),
+        };
+        if let Err(err) = com.dial(&connection_data.address, is_canceled) {
+            if matches!(err, ConnectionError::Canceled) {
+                log::info!("Dialing canceled, hanging up");
+                if let Err(err) = com.hang_up() {
+                    log::warn!("Error while hanging up: {err}");
+                }
+            }
+            return Err(err);
+        }
+        Ok(com)
+    }
+
+    fn dial(&mut self, number: &str, is_canceled: &mut dyn FnMut() -> bool) -> TermComResult<()> {
+        self.state = DialState::Initializing;
+        let init_string = self.modem.init_string.trim().to_string();
+        if !init_string.is_empty() {
+            self.send_command(&init_string)?;
+            match self.wait_for_result(COMMAND_TIMEOUT, is_canceled)? {
+                ResultCode::Ok => {}
+                result => return Err(self.dial_error(Some(result))),
+            }
+        }
+
+        self.state = DialState::Dialing;
+        self.send_command(&format!("{}{}", self.modem.dial_string.trim(), number.trim()))?;
+        match self.wait_for_result(DIAL_TIMEOUT, is_canceled)? {
+            ResultCode::Connect(speed) => {
+                log::info!("Modem connected with {}", speed.map_or("unknown speed".to_string(), |s| format!("{s} bps")));
+                self.state = DialState::Connected;
+                self.connect_speed = speed;
+                Ok(())
+            }
+            result => {
+                let err = self.dial_error(Some(result));
+                self.state = DialState::Idle;
+                Err(err)
+            }
+        }
+    }
+
+    fn send_command(&mut self, command: &str) -> TermComResult<()> {
+        log::info!("Modem command: {command}");
+        self.port.write_all(command.as_bytes())?;
+        self.port.write_all(b"\r")?;
+        Ok(())
+    }
+
+    /// Reads from the modem until a final result code arrives, RING and other lines are skipped.
+    /// `is_canceled` is checked after every read timeout.
+    fn wait_for_result(&mut self, timeout: Duration, is_canceled: &mut dyn FnMut() -> bool) -> TermComResult<ResultCode> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0; 256];
+        loop {
+            if let Some(result) = self.next_result_code() {
+                return Ok(result);
+            }
+            if Instant::now() >= deadline {
+                return Err(self.dial_error(None));
+            }
+            if is_canceled() {
+                return Err(ConnectionError::Canceled);
+            }
+            match self.port.read(&mut buf) {
+                Ok(size) => self.received.extend_from_slice(&buf[..size]),
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Takes complete lines out of the received data until a final result code is found.
+    /// Everything after the result code stays - after a CONNECT it's data from the remote side.
+    fn next_result_code(&mut self) -> Option<ResultCode> {
+        while let Some(end) = self.received.iter().position(|b| *b == b'\r' || *b == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            let ends_with_cr = line[end] == b'\r';
+            let line = String::from_utf8_lossy(&line[..end]).to_string();
+            match ResultCode::parse(&line) {
+                Some(ResultCode::Ring) | None => {
+                    if !line.trim().is_empty() {
+                        log::info!("Modem: {line}");
+                    }
+                }
+                Some(result) => {
+                    if ends_with_cr && self.received.first() == Some(&b'\n') {
+                        self.received.remove(0);
+                    }
+                    log::info!("Modem result: {result}");
+                    return Some(result);
+                }
+            }
+        }
+        None
+    }
+
+    fn dial_error(&self, result: Option<ResultCode>) -> ConnectionError {
+        ConnectionError::Dial(DialError { state: self.state, result })
+    }
+
+    /// Drops DTR and escapes to command mode to send ATH, whatever the modem listens to.
+    fn hang_up(&mut self) -> TermComResult<()> {
+        self.state = DialState::HangingUp;
+        match self.port.set_dtr(false) {
+            Ok(()) => {
+                thread::sleep(DTR_DROP_TIME);
+                self.port.set_dtr(true)?;
+            }
+            Err(err) => log::warn!("Can't drop DTR on {}: {err}", self.modem.device),
+        }
+
+        // modems configured with AT&D0 ignore DTR, lines without carrier detect need the escape anyway
+        if self.port.read_cd().unwrap_or(true) {
+            self.received.clear();
+            thread::sleep(ESCAPE_GUARD_TIME);
+            self.port.write_all(b"+++")?;
+            if let Err(err) = self.wait_for_result(ESCAPE_GUARD_TIME * 2, &mut || false) {
+                log::warn!("No answer to the escape sequence: {err}");
+            }
+            self.send_command("ATH0")?;
+            if let Err(err) = self.wait_for_result(COMMAND_TIMEOUT, &mut || false) {
+                log::warn!("No answer to ATH0: {err}");
+            }
+        }
+        self.state = DialState::Idle;
+        Ok(())
     }
 }
 
@@ -44,20 +293,10 @@ impl Com for ComModemImpl {
     fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
 
     fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
-        let mut buf: Vec<u8> = (0..255).collect();
-        match self.port.read(&mut buf[..]) {
-            Ok(size) => {
-                buf.truncate(size);
-                Ok(Some(buf))
-            }
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::TimedOut {
-                    Ok(None)
-                } else {
-                    Err(e.into())
-                }
-            }
+        if !self.received.is_empty() {
+            return Ok(Some(std::mem::take(&mut self.received)));
         }
+        super::serial_port::read_port(&mut self.port)
     }
 
     fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
@@ -66,6 +305,153 @@ impl Com for ComModemImpl {
     }
 
     fn disconnect(&mut self) -> TermComResult<()> {
+        if self.state == DialState::Connected {
+            self.hang_up()?;
+        }
         Ok(())
     }
+
+    fn take_connect_speed(&mut self) -> Option<u32> {
+        self.connect_speed.take()
+    }
+
+    fn line_status(&mut self) -> Option<LineStatus> {
+        super::serial_port::read_line_status(&mut self.port)
+    }
+
+    fn send_break(&mut self) -> TermComResult<()> {
+        super::serial_port::send_break(&mut self.port, &self.modem)
+    }
+
+    fn set_control_lines(&mut self, dtr: bool, rts: bool) -> TermComResult<()> {
+        super::serial_port::set_control_lines(&mut self.port, dtr, rts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Address, Proxy};
+    use icy_engine::Size;
+
+    #[test]
+    fn test_parse_result_codes() {
+        assert_eq!(Some(ResultCode::Ok), ResultCode::parse("OK"));
+        assert_eq!(Some(ResultCode::Ok), ResultCode::parse("0"));
+        assert_eq!(Some(ResultCode::Connect(None)), ResultCode::parse("CONNECT"));
+        assert_eq!(Some(ResultCode::Connect(Some(14400))), ResultCode::parse("CONNECT 14400"));
+        assert_eq!(Some(ResultCode::Connect(Some(33600))), ResultCode::parse("connect 33600/ARQ/V34/LAPM"));
+        assert_eq!(Some(ResultCode::Busy), ResultCode::parse("BUSY"));
+        assert_eq!(Some(ResultCode::NoAnswer), ResultCode::parse("8"));
+        assert_eq!(Some(ResultCode::NoDialtone), ResultCode::parse("NO DIAL TONE"));
+        assert_eq!(Some(ResultCode::NoCarrier), ResultCode::parse(" NO CARRIER "));
+        assert_eq!(None, ResultCode::parse("ATDT5551234"));
+        assert_eq!(None, ResultCode::parse("PROTOCOL: LAPM"));
+        assert_eq!(None, ResultCode::parse(""));
+    }
+
+    /// Opens a pseudo terminal, the modem script runs on the master side until the modem port gets closed.
+    /// Each script entry is the expected command and the answer, all received commands are returned.
+    #[cfg(unix)]
+    fn start_modem_script(script: Vec<(&'static str, &'static str)>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let (mut master, slave, name) = super::super::serial_port::open_test_pty();
+
+        let handle = std::thread::spawn(move || {
+            // reading the master fails while no slave is open, ours is kept until the port sent the first command
+            let mut slave = Some(slave);
+            let mut commands = Vec::new();
+            let mut line = Vec::new();
+            let mut buf = [0; 256];
+            let mut script = script.into_iter();
+            while let Ok(size @ 1..) = master.read(&mut buf) {
+                drop(slave.take());
+                for b in &buf[..size] {
+                    if *b != b'\r' {
+                        line.push(*b);
+                    }
+                    if *b == b'\r' || line == b"+++" {
+                        let command = String::from_utf8_lossy(&line).to_string();
+                        line.clear();
+                        if let Some((expected, answer)) = script.next() {
+                            assert_eq!(expected, command);
+                            master.write_all(answer.as_bytes()).unwrap();
+                        }
+                        commands.push(command);
+                    }
+                }
+            }
+            commands
+        });
+        (name, handle)
+    }
+
+    fn connect(device: String, is_canceled: &mut dyn FnMut() -> bool) -> TermComResult<ComModemImpl> {
+        let modem = Modem {
+            device,
+            init_string: "ATZ".to_string(),
+            dial_string: "ATDT".to_string(),
+            ..Default::default()
+        };
+        let mut address = Address::new("test");
+        address.address = "5551234".to_string();
+        let data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), Some(modem), &Proxy::default());
+        ComModemImpl::connect(&data, is_canceled)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dial() {
+        let (device, script) = start_modem_script(vec![
+            ("ATZ", "ATZ\r\r\nOK\r\n"),
+            ("ATDT5551234", "ATDT5551234\r\r\nCONNECT 14400/ARQ\r\nWelcome"),
+            ("+++", "\r\nOK\r\n"),
+            ("ATH0", "\r\nOK\r\n"),
+        ]);
+        let mut com = connect(device, &mut || false).unwrap();
+        assert_eq!(DialState::Connected, com.state);
+        assert_eq!(Some(14400), com.take_connect_speed());
+        assert_eq!(None, com.take_connect_speed());
+        assert_eq!(b"Welcome".to_vec(), com.read_data().unwrap().unwrap());
+
+        com.disconnect().unwrap();
+        assert_eq!(DialState::Idle, com.state);
+        drop(com);
+        assert_eq!(vec!["ATZ", "ATDT5551234", "+++", "ATH0"], script.join().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dial_busy() {
+        let (device, _script) = start_modem_script(vec![("ATZ", "\r\nOK\r\n"), ("ATDT5551234", "\r\nRING\r\n\r\nBUSY\r\n")]);
+        let err = connect(device, &mut || false).err().unwrap();
+        let ConnectionError::Dial(err) = err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(Some(ResultCode::Busy), err.result);
+        assert_eq!(DialState::Dialing, err.state);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_init_error() {
+        let (device, _script) = start_modem_script(vec![("ATZ", "\r\nERROR\r\n")]);
+        let err = connect(device, &mut || false).err().unwrap();
+        let ConnectionError::Dial(err) = err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(Some(ResultCode::Error), err.result);
+        assert_eq!(DialState::Initializing, err.state);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dial_canceled() {
+        // the modem never answers the dial command
+        let (device, script) = start_modem_script(vec![("ATZ", "\r\nOK\r\n"), ("ATDT5551234", ""), ("+++", "\r\nOK\r\n"), ("ATH0", "\r\nOK\r\n")]);
+        let start = Instant::now();
+        let err = connect(device, &mut || start.elapsed() > Duration::from_millis(300)).err().unwrap();
+        assert!(matches!(err, ConnectionError::Canceled), "unexpected error {err}");
+        assert!(start.elapsed() < DIAL_TIMEOUT);
+        assert_eq!(vec!["ATZ", "ATDT5551234", "+++", "ATH0"], script.join().unwrap());
+    }
 }
@@ -0,0 +1,384 @@
+use std::{
+    io::{Read, Write},
+    net::{IpAddr, TcpStream},
+    time::Duration,
+};
+
+use super::{connect_with_timeout, enable_keepalive, ConnectionError, HostAddress, OpenConnectionData, TermComResult};
+use crate::{Proxy, ProxyKind};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SOCKS_DEFAULT_PORT: u16 = 1080;
+const HTTP_PROXY_DEFAULT_PORT: u16 = 8080;
+
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_AUTH_NONE: u8 = 0;
+const SOCKS_AUTH_PASSWORD: u8 = 2;
+const SOCKS_NO_ACCEPTABLE_METHOD: u8 = 0xFF;
+const SOCKS_CMD_CONNECT: u8 = 1;
+const SOCKS_ATYP_IPV4: u8 = 1;
+const SOCKS_ATYP_DOMAIN: u8 = 3;
+const SOCKS_ATYP_IPV6: u8 = 4;
+
+/// Opens a TCP connection, tunneled through the proxy of the connection if one is configured.
+pub fn connect_tcp(address: &HostAddress, connection_data: &OpenConnectionData) -> TermComResult<TcpStream> {
+    if !connection_data.proxy.is_enabled() {
+        let stream = connect_with_timeout(address, connection_data.timeout)?;
+        enable_keepalive(&stream)?;
+        return Ok(stream);
+    }
+    let proxy = &connection_data.proxy;
+    log::info!("Connecting to {address} through {} proxy {}", proxy.kind, proxy.address);
+
+    let default_port = if proxy.kind == ProxyKind::Socks5 {
+        SOCKS_DEFAULT_PORT
+    } else {
+        HTTP_PROXY_DEFAULT_PORT
+    };
+    let mut stream = connect_with_timeout(
+        &HostAddress::parse_with_default_port(&proxy.address, Some(default_port))?,
+        connection_data.timeout,
+    )
+    .map_err(|err| proxy_error(err.to_string()))?;
+    enable_keepalive(&stream)?;
+    let timeouts = (stream.read_timeout()?, stream.write_timeout()?);
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    match proxy.kind {
+        ProxyKind::Socks5 => socks5_handshake(&mut stream, proxy, address)?,
+        ProxyKind::HttpConnect => http_connect_handshake(&mut stream, proxy, address)?,
+        ProxyKind::None => {}
+    }
+    stream.set_read_timeout(timeouts.0)?;
+    stream.set_write_timeout(timeouts.1)?;
+    Ok(stream)
+}
+
+fn proxy_error(message: impl Into<String>) -> ConnectionError {
+    ConnectionError::Proxy(message.into())
+}
+
+/// RFC 1928 CONNECT, with RFC 1929 username/password authentication if credentials are set.
+fn socks5_handshake(stream: &mut TcpStream, proxy: &Proxy, address: &HostAddress) -> TermComResult<()> {
+    let host = address.host.as_str();
+    let use_password = !proxy.user_name.is_empty();
+    if use_password {
+        stream.write_all(&[SOCKS_VERSION, 2, SOCKS_AUTH_NONE, SOCKS_AUTH_PASSWORD])?;
+    } else {
+        stream.write_all(&[SOCKS_VERSION, 1, SOCKS_AUTH_NONE])?;
+    }
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(proxy_error(format!("{} is not a SOCKS5 proxy", proxy.address)));
+    }
+    match reply[1] {
+        SOCKS_AUTH_NONE => {}
+        SOCKS_AUTH_PASSWORD if use_password => {
+            if proxy.user_name.len() > 255 || proxy.password.len() > 255 {
+                return Err(proxy_error("SOCKS5 user name or password too long"));
+            }
+            let mut request = vec![1, proxy.user_name.len() as u8];
+            request.extend_from_slice(proxy.user_name.as_bytes());
+            request.push(proxy.password.len() as u8);
+            request.extend_from_slice(proxy.password.as_bytes());
+            stream.write_all(&request)?;
+            stream.read_exact(&mut reply)?;
+            if reply[1] != 0 {
+                return Err(proxy_error("SOCKS5 proxy authentication failed"));
+            }
+        }
+        SOCKS_NO_ACCEPTABLE_METHOD if !use_password => return Err(proxy_error("SOCKS5 proxy requires authentication")),
+        _ => return Err(proxy_error("SOCKS5 proxy doesn't accept any offered authentication method")),
+    }
+
+    let mut request = vec![SOCKS_VERSION, SOCKS_CMD_CONNECT, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(SOCKS_ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(SOCKS_ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            // let the proxy resolve the name - the local network may not be able to
+            if host.len() > 255 {
+                return Err(proxy_error(format!("Host name too long: {host}")));
+            }
+            request.push(SOCKS_ATYP_DOMAIN);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&address.port.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[1] != 0 {
+        return Err(proxy_error(format!("SOCKS5 proxy can't connect to {address}: {}", get_socks_error(reply[1]))));
+    }
+    // skip the bound address
+    let address_len = match reply[3] {
+        SOCKS_ATYP_IPV4 => 4,
+        SOCKS_ATYP_IPV6 => 16,
+        SOCKS_ATYP_DOMAIN => {
+            let mut len = [0];
+            stream.read_exact(&mut len)?;
+            len[0] as usize
+        }
+        atyp => return Err(proxy_error(format!("Invalid SOCKS5 address type {atyp}"))),
+    };
+    let mut bound_address = vec![0; address_len + 2];
+    stream.read_exact(&mut bound_address)?;
+    Ok(())
+}
+
+fn get_socks_error(reply: u8) -> &'static str {
+    match reply {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn http_connect_handshake(stream: &mut TcpStream, proxy: &Proxy, address: &HostAddress) -> TermComResult<()> {
+    let target = address.to_string();
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if !proxy.user_name.is_empty() {
+        let credentials = encode_base64(format!("{}:{}", proxy.user_name, proxy.password).as_bytes());
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    // read byte by byte, everything after the header already belongs to the tunneled connection
+    let mut header = Vec::new();
+    let mut b = [0];
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() > 8192 {
+            return Err(proxy_error("HTTP proxy response header too long"));
+        }
+        stream.read_exact(&mut b)?;
+        header.push(b[0]);
+    }
+    let header = String::from_utf8_lossy(&header);
+    let status_line = header.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        Some("407") => Err(proxy_error("HTTP proxy authentication failed")),
+        _ => Err(proxy_error(format!("HTTP proxy can't connect to {target}: {status_line}"))),
+    }
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{addresses::Protocol, Address};
+    use icy_engine::Size;
+    use std::{
+        net::{Shutdown, TcpListener},
+        thread,
+    };
+
+    /// Accepts one connection and echoes everything back.
+    fn start_echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = stream.try_clone().unwrap();
+            let _ = io::copy(&mut reader, &mut stream);
+        });
+        address
+    }
+
+    fn relay(client: TcpStream, target: &str) {
+        let server = TcpStream::connect(target).unwrap();
+        let (mut client_reader, mut server_writer) = (client.try_clone().unwrap(), server.try_clone().unwrap());
+        thread::spawn(move || {
+            let _ = io::copy(&mut client_reader, &mut server_writer);
+            let _ = server_writer.shutdown(Shutdown::Write);
+        });
+        let (mut server_reader, mut client_writer) = (server, client);
+        let _ = io::copy(&mut server_reader, &mut client_writer);
+    }
+
+    /// Minimal SOCKS5 proxy for a single connection, `credentials` enables username/password authentication.
+    fn start_socks5_proxy(credentials: Option<(&'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 2];
+            stream.read_exact(&mut buf).unwrap();
+            let mut methods = vec![0; buf[1] as usize];
+            stream.read_exact(&mut methods).unwrap();
+            if let Some((user, password)) = credentials {
+                if !methods.contains(&SOCKS_AUTH_PASSWORD) {
+                    stream.write_all(&[SOCKS_VERSION, SOCKS_NO_ACCEPTABLE_METHOD]).unwrap();
+                    return;
+                }
+                stream.write_all(&[SOCKS_VERSION, SOCKS_AUTH_PASSWORD]).unwrap();
+                stream.read_exact(&mut buf).unwrap();
+                let mut received_user = vec![0; buf[1] as usize];
+                stream.read_exact(&mut received_user).unwrap();
+                stream.read_exact(&mut buf[..1]).unwrap();
+                let mut received_password = vec![0; buf[0] as usize];
+                stream.read_exact(&mut received_password).unwrap();
+                if received_user != user.as_bytes() || received_password != password.as_bytes() {
+                    stream.write_all(&[1, 1]).unwrap();
+                    return;
+                }
+                stream.write_all(&[1, 0]).unwrap();
+            } else {
+                stream.write_all(&[SOCKS_VERSION, SOCKS_AUTH_NONE]).unwrap();
+            }
+
+            let mut request = [0; 4];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(SOCKS_ATYP_DOMAIN, request[3]);
+            stream.read_exact(&mut buf[..1]).unwrap();
+            let mut host = vec![0; buf[0] as usize];
+            stream.read_exact(&mut host).unwrap();
+            stream.read_exact(&mut buf).unwrap();
+            let target = format!(
+                "{}:{}",
+                String::from_utf8(host).unwrap().replace("localhost", "127.0.0.1"),
+                u16::from_be_bytes(buf)
+            );
+            stream.write_all(&[SOCKS_VERSION, 0, 0, SOCKS_ATYP_IPV4, 127, 0, 0, 1, 0, 0]).unwrap();
+            relay(stream, &target);
+        });
+        address
+    }
+
+    /// Minimal HTTP CONNECT proxy for a single connection, `authorization` is the expected Proxy-Authorization header.
+    fn start_http_proxy(authorization: Option<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = Vec::new();
+            let mut b = [0];
+            while !header.ends_with(b"\r\n\r\n") {
+                stream.read_exact(&mut b).unwrap();
+                header.push(b[0]);
+            }
+            let header = String::from_utf8(header).unwrap();
+            if let Some(authorization) = authorization {
+                if !header.contains(&format!("Proxy-Authorization: {authorization}\r\n")) {
+                    stream.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").unwrap();
+                    return;
+                }
+            }
+            let target = header.split_whitespace().nth(1).unwrap().replace("localhost", "127.0.0.1");
+            stream.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap();
+            relay(stream, &target);
+        });
+        address
+    }
+
+    fn connection_data(kind: ProxyKind, proxy_address: String, user_name: &str, password: &str) -> OpenConnectionData {
+        let proxy = Proxy {
+            kind,
+            address: proxy_address,
+            user_name: user_name.to_string(),
+            password: password.to_string(),
+        };
+        OpenConnectionData::from(&Address::new("test"), Duration::from_secs(1), Size::new(80, 25), None, &proxy)
+    }
+
+    fn check_echo(mut stream: TcpStream) {
+        stream.write_all(b"Hello BBS").unwrap();
+        let mut buf = [0; 9];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(b"Hello BBS", &buf);
+    }
+
+    fn get_target(echo_address: &str) -> HostAddress {
+        HostAddress::parse(&echo_address.replace("127.0.0.1", "localhost"), Protocol::Raw).unwrap()
+    }
+
+    #[test]
+    fn test_socks5() {
+        let target = get_target(&start_echo_server());
+        let data = connection_data(ProxyKind::Socks5, start_socks5_proxy(None), "", "");
+        check_echo(connect_tcp(&target, &data).unwrap());
+    }
+
+    #[test]
+    fn test_socks5_auth() {
+        let target = get_target(&start_echo_server());
+        let data = connection_data(ProxyKind::Socks5, start_socks5_proxy(Some(("user", "secret"))), "user", "secret");
+        check_echo(connect_tcp(&target, &data).unwrap());
+
+        let data = connection_data(ProxyKind::Socks5, start_socks5_proxy(Some(("user", "secret"))), "user", "wrong");
+        let err = connect_tcp(&target, &data).unwrap_err();
+        assert!(err.to_string().contains("authentication failed"), "{err}");
+
+        let data = connection_data(ProxyKind::Socks5, start_socks5_proxy(Some(("user", "secret"))), "", "");
+        let err = connect_tcp(&target, &data).unwrap_err();
+        assert!(matches!(err, ConnectionError::Proxy(_)), "{err:?}");
+        assert!(err.to_string().contains("requires authentication"), "{err}");
+    }
+
+    #[test]
+    fn test_http_connect() {
+        let target = get_target(&start_echo_server());
+        let data = connection_data(ProxyKind::HttpConnect, start_http_proxy(None), "", "");
+        check_echo(connect_tcp(&target, &data).unwrap());
+    }
+
+    #[test]
+    fn test_http_connect_auth() {
+        let target = get_target(&start_echo_server());
+        let data = connection_data(ProxyKind::HttpConnect, start_http_proxy(Some("Basic dXNlcjpzZWNyZXQ=")), "user", "secret");
+        check_echo(connect_tcp(&target, &data).unwrap());
+
+        let data = connection_data(ProxyKind::HttpConnect, start_http_proxy(Some("Basic dXNlcjpzZWNyZXQ=")), "user", "wrong");
+        let err = connect_tcp(&target, &data).unwrap_err();
+        assert!(err.to_string().contains("authentication failed"), "{err}");
+    }
+
+    #[test]
+    fn test_direct() {
+        let target = HostAddress::parse(&start_echo_server(), Protocol::Raw).unwrap();
+        check_echo(connect_tcp(&target, &connection_data(ProxyKind::None, String::new(), "", "")).unwrap());
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!("", encode_base64(b""));
+        assert_eq!("Zg==", encode_base64(b"f"));
+        assert_eq!("Zm8=", encode_base64(b"fo"));
+        assert_eq!("Zm9v", encode_base64(b"foo"));
+        assert_eq!("dXNlcjpzZWNyZXQ=", encode_base64(b"user:secret"));
+    }
+}
@@ -1,31 +1,37 @@
 #![allow(dead_code)]
 
-use super::{Com, OpenConnectionData, TermComResult};
+use super::{connect_tcp, Com, ComStream, ConnectionError, HostAddress, OpenConnectionData, TermComResult, READ_BUFFER_SIZE};
+use crate::addresses::Protocol;
 use std::{
-    io::{self, ErrorKind, Read, Write},
-    net::{TcpStream, ToSocketAddrs},
+    io::{self, Read, Write},
     time::Duration,
 };
 
 pub struct ComRawImpl {
-    tcp_stream: TcpStream,
+    tcp_stream: ComStream,
+    read_buffer: Vec<u8>,
 }
 
 impl ComRawImpl {
     pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
-        let addr = connection_data.address.to_string();
+        let is_tls = connection_data.protocol == Protocol::RawTls;
+        let addr = HostAddress::parse(&connection_data.address, connection_data.protocol)?;
 
-        let Some(a) = connection_data.address.to_socket_addrs()?.next() else {
-            return Err(Box::new(io::Error::new(ErrorKind::InvalidInput, format!("Invalid address: {addr}"))));
-        };
-
-        let tcp_stream = TcpStream::connect_timeout(&a, Duration::from_millis(500))?;
+        let tcp_stream = connect_tcp(&addr, connection_data)?;
 
         tcp_stream.set_write_timeout(Some(Duration::from_millis(500)))?;
         tcp_stream.set_read_timeout(Some(Duration::from_millis(500)))?;
         tcp_stream.set_nonblocking(false)?;
+        let tcp_stream = if is_tls {
+            ComStream::connect_tls(tcp_stream, &addr, connection_data)?
+        } else {
+            ComStream::Plain(tcp_stream)
+        };
 
-        Ok(Self { tcp_stream })
+        Ok(Self {
+            tcp_stream,
+            read_buffer: vec![0; READ_BUFFER_SIZE],
+        })
     }
 }
 
@@ -41,20 +47,22 @@ impl Com for ComRawImpl {
     fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
 
     fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
-        let mut buf = [0; 1024 * 256];
         self.tcp_stream.set_nonblocking(true)?;
-        match self.tcp_stream.read(&mut buf) {
-            Ok(size) => Ok(Some(buf[0..size].to_vec())),
+        match self.tcp_stream.read(&mut self.read_buffer) {
+            Ok(0) => Err(ConnectionError::ClosedByRemote(None)),
+            Ok(size) => Ok(Some(self.read_buffer[0..size].to_vec())),
             Err(ref e) => {
                 if e.kind() == io::ErrorKind::WouldBlock {
                     return Ok(None);
                 }
-                Err(Box::new(io::Error::new(ErrorKind::ConnectionAborted, format!("Connection aborted: {e}"))))
+                Err(ConnectionError::ConnectionLost(e.to_string()))
             }
         }
     }
 
     fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
+        // the socket is non-blocking after a read, large writes would fail half way
+        self.tcp_stream.set_nonblocking(false)?;
         let r = self.tcp_stream.write_all(buf);
 
         match r {
@@ -64,13 +72,18 @@ impl Com for ComRawImpl {
                     std::thread::sleep(Duration::from_millis(100));
                     return self.send(buf);
                 }
-                Err(Box::new(io::Error::new(ErrorKind::ConnectionAborted, format!("Connection aborted: {e}"))))
+                Err(ConnectionError::ConnectionLost(e.to_string()))
             }
         }
     }
 
     fn disconnect(&mut self) -> TermComResult<()> {
-        self.tcp_stream.shutdown(std::net::Shutdown::Both)?;
+        self.tcp_stream.shutdown()?;
         Ok(())
     }
+
+    #[cfg(unix)]
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        Some(std::os::fd::AsRawFd::as_raw_fd(&self.tcp_stream))
+    }
 }
@@ -0,0 +1,248 @@
+use std::{
+    io::{self, Read, Write},
+    mem::MaybeUninit,
+    net::TcpStream,
+    time::Duration,
+};
+
+use icy_engine::Size;
+use socket2::SockRef;
+
+use super::{connect_tcp, Com, ConnectionError, HostAddress, OpenConnectionData, TermComResult, READ_BUFFER_SIZE};
+
+/// Speed reported in the handshake without baud emulation, rlogind only accepts the classic tty speeds.
+const TERMINAL_SPEED: u32 = 38400;
+
+mod control {
+    /// Discard the output up to the urgent mark
+    pub const FLUSH_OUTPUT: u8 = 0x02;
+    /// Flow control keys are handled by the server (raw mode)
+    pub const NO_FLOW_CONTROL: u8 = 0x10;
+    /// Flow control keys are handled locally (cooked mode)
+    pub const FLOW_CONTROL: u8 = 0x20;
+    /// The server wants window size messages
+    pub const WINDOW_SIZE_REQUEST: u8 = 0x80;
+}
+
+/// Rlogin connection as specified in RFC 1282.
+///
+/// Control messages from the server arrive as TCP urgent data, the window size is sent
+/// once the server asked for it and on every resize afterwards.
+pub struct ComRloginImpl {
+    tcp_stream: TcpStream,
+    window_size: Size,
+    send_window_size: bool,
+    /// Data the server sent instead of the handshake acknowledge, usually an error message
+    pending_data: Vec<u8>,
+    read_buffer: Vec<u8>,
+}
+
+impl ComRloginImpl {
+    pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
+        let addr = HostAddress::parse(&connection_data.address, connection_data.protocol)?;
+        let mut tcp_stream = connect_tcp(&addr, connection_data)?;
+        tcp_stream.set_write_timeout(Some(Duration::from_millis(500)))?;
+        tcp_stream.set_read_timeout(Some(connection_data.timeout))?;
+        tcp_stream.set_nonblocking(false)?;
+
+        let terminal_type = connection_data.terminal_type_names.first().map_or("ansi", String::as_str);
+        let speed = if connection_data.baud_rate > 0 {
+            connection_data.baud_rate
+        } else {
+            TERMINAL_SPEED
+        };
+        tcp_stream.write_all(&handshake(client_user(connection_data), &connection_data.user_name, terminal_type, speed))?;
+
+        let mut ack = [0; 1];
+        tcp_stream.read_exact(&mut ack)?;
+        let pending_data = if ack[0] == 0 { Vec::new() } else { ack.to_vec() };
+        tcp_stream.set_read_timeout(Some(Duration::from_millis(500)))?;
+
+        Ok(Self {
+            tcp_stream,
+            window_size: connection_data.window_size,
+            send_window_size: false,
+            pending_data,
+            read_buffer: vec![0; READ_BUFFER_SIZE],
+        })
+    }
+
+    /// Reads the pending urgent byte, `None` if there is none.
+    fn read_control_byte(&self) -> Option<u8> {
+        let mut buf = [MaybeUninit::<u8>::uninit()];
+        // fails with EINVAL if no urgent data is pending
+        match SockRef::from(&self.tcp_stream).recv_out_of_band(&mut buf) {
+            Ok(1) => Some(unsafe { buf[0].assume_init() }),
+            _ => None,
+        }
+    }
+
+    fn handle_control_byte(&mut self, control: u8) -> TermComResult<()> {
+        if control & control::WINDOW_SIZE_REQUEST != 0 && !self.send_window_size {
+            self.send_window_size = true;
+            self.tcp_stream.write_all(&window_size_message(self.window_size))?;
+        }
+        if control & (control::FLUSH_OUTPUT | control::NO_FLOW_CONTROL | control::FLOW_CONTROL) != 0 {
+            log::debug!("rlogin: ignoring control message {control:#04x}");
+        }
+        Ok(())
+    }
+}
+
+/// BBS rlogin servers like Synchronet take the password as client user name, that's only sent if the address asks for it.
+fn client_user(connection_data: &OpenConnectionData) -> &str {
+    if connection_data.rlogin_send_password && !connection_data.password.is_empty() {
+        &connection_data.password
+    } else {
+        &connection_data.user_name
+    }
+}
+
+/// Builds the initial message: `\0client-user\0server-user\0terminal/speed\0`.
+fn handshake(client_user: &str, server_user: &str, terminal_type: &str, speed: u32) -> Vec<u8> {
+    let mut result = vec![0];
+    for field in [client_user, server_user, &format!("{terminal_type}/{speed}")] {
+        result.extend_from_slice(field.as_bytes());
+        result.push(0);
+    }
+    result
+}
+
+/// Window change message: magic cookie, 's', 's' followed by rows, columns, x and y pixels in network byte order.
+fn window_size_message(window_size: Size) -> Vec<u8> {
+    let mut result = vec![0xFF, 0xFF, b's', b's'];
+    result.extend_from_slice(&(window_size.height as u16).to_be_bytes());
+    result.extend_from_slice(&(window_size.width as u16).to_be_bytes());
+    result.extend_from_slice(&0u16.to_be_bytes());
+    result.extend_from_slice(&0u16.to_be_bytes());
+    result
+}
+
+impl Com for ComRloginImpl {
+    fn get_name(&self) -> &'static str {
+        "Rlogin"
+    }
+
+    fn default_port(&self) -> u16 {
+        513
+    }
+
+    fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
+
+    fn set_window_size(&mut self, window_size: Size) -> TermComResult<()> {
+        if self.window_size == window_size {
+            return Ok(());
+        }
+        self.window_size = window_size;
+        if self.send_window_size {
+            self.tcp_stream.write_all(&window_size_message(window_size))?;
+        }
+        Ok(())
+    }
+
+    fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
+        if !self.pending_data.is_empty() {
+            return Ok(Some(std::mem::take(&mut self.pending_data)));
+        }
+        self.tcp_stream.set_nonblocking(true)?;
+        if let Some(control) = self.read_control_byte() {
+            self.handle_control_byte(control)?;
+        }
+        match self.tcp_stream.read(&mut self.read_buffer) {
+            Ok(0) => Err(ConnectionError::ClosedByRemote(None)),
+            Ok(size) => Ok(Some(self.read_buffer[0..size].to_vec())),
+            Err(ref e) => {
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    return Ok(None);
+                }
+                Err(ConnectionError::ConnectionLost(e.to_string()))
+            }
+        }
+    }
+
+    fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
+        self.tcp_stream.set_nonblocking(false)?;
+        match self.tcp_stream.write_all(buf) {
+            Ok(()) => Ok(buf.len()),
+            Err(e) => Err(ConnectionError::ConnectionLost(e.to_string())),
+        }
+    }
+
+    fn disconnect(&mut self) -> TermComResult<()> {
+        self.tcp_stream.shutdown(std::net::Shutdown::Both)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        Some(std::os::fd::AsRawFd::as_raw_fd(&self.tcp_stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{addresses::Protocol, Address, Proxy};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_handshake() {
+        assert_eq!(b"\0pass\0sysop\0ansi-bbs/38400\0".to_vec(), handshake("pass", "sysop", "ansi-bbs", 38400));
+        assert_eq!(vec![0xFF, 0xFF, b's', b's', 0, 25, 0, 80, 0, 0, 0, 0], window_size_message(Size::new(80, 25)));
+    }
+
+    #[test]
+    fn test_client_user() {
+        let mut address = Address::new("test");
+        address.user_name = "sysop".to_string();
+        address.password = "secret".to_string();
+        let connection_data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        assert_eq!("sysop", client_user(&connection_data));
+
+        address.rlogin_send_password = true;
+        let connection_data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        assert_eq!("secret", client_user(&connection_data));
+    }
+
+    #[test]
+    fn test_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 24];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(b"\0sysop\0sysop\0vt100/2400\0", &buf);
+            stream.write_all(b"\0Welcome").unwrap();
+            SockRef::from(&stream).send_out_of_band(&[control::WINDOW_SIZE_REQUEST]).unwrap();
+            let mut buf = [0; 12];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(window_size_message(Size::new(80, 25)).as_slice(), &buf);
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(window_size_message(Size::new(132, 50)).as_slice(), &buf);
+        });
+
+        let mut address = Address::new("test");
+        address.address = format!("127.0.0.1:{port}");
+        address.protocol = Protocol::Rlogin;
+        address.user_name = "sysop".to_string();
+        address.password = "secret".to_string();
+        address.terminal_type_names = vec!["vt100".to_string()];
+        address.baud_emulation = icy_engine::ansi::BaudEmulation::Rate(2400);
+        let connection_data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        let mut com = ComRloginImpl::connect(&connection_data).unwrap();
+
+        let mut received = Vec::new();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !com.send_window_size || received.len() < 7 {
+            assert!(std::time::Instant::now() < deadline, "window size wasn't requested");
+            if let Some(data) = com.read_data().unwrap() {
+                received.extend(data);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(b"Welcome".to_vec(), received);
+        com.set_window_size(Size::new(132, 50)).unwrap();
+        server.join().unwrap();
+    }
+}
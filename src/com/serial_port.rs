@@ -0,0 +1,211 @@
+#![allow(dead_code)]
+
+use crate::Modem;
+
+use super::{Com, ConnectionError, LineStatus, OpenConnectionData, TermComResult};
+use serial::prelude::*;
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+pub(crate) const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// A NUL byte at 110 baud keeps the line low for ~90ms, longer than the 'at least 2 characters' a break needs.
+#[cfg(not(unix))]
+const BREAK_BAUD_RATE: serial::BaudRate = serial::BaudRate::Baud110;
+#[cfg(not(unix))]
+const BREAK_TIME: Duration = Duration::from_millis(150);
+
+/// Opens the serial port with the line settings of the modem profile and raises DTR.
+pub(crate) fn open_port(modem: &Modem) -> TermComResult<serial::SystemPort> {
+    let mut port = serial::open(&modem.device)?;
+    configure_port(&mut port, modem, serial::BaudRate::from_speed(modem.baud_rate))?;
+    port.set_timeout(READ_TIMEOUT)?;
+    if let Err(err) = port.set_dtr(true) {
+        log::warn!("Can't raise DTR on {}: {err}", modem.device);
+    }
+    Ok(port)
+}
+
+fn configure_port(port: &mut dyn SerialPort, modem: &Modem, baud_rate: serial::BaudRate) -> TermComResult<()> {
+    port.reconfigure(&|settings| {
+        settings.set_baud_rate(baud_rate)?;
+        settings.set_char_size(modem.char_size);
+        settings.set_parity(modem.parity);
+        settings.set_stop_bits(modem.stop_bits);
+        settings.set_flow_control(modem.flow_control);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Sends a break once the queued output is on the line.
+#[cfg(unix)]
+pub(crate) fn send_break(port: &mut serial::SystemPort, _modem: &Modem) -> TermComResult<()> {
+    use std::os::fd::AsRawFd;
+
+    let fd = port.as_raw_fd();
+    // a duration of 0 holds the line low for 0.25 to 0.5 seconds
+    if unsafe { libc::tcdrain(fd) } == -1 || unsafe { libc::tcsendbreak(fd, 0) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Holds the line low by sending a NUL byte at a very low speed, the serial crate has no break support.
+#[cfg(not(unix))]
+pub(crate) fn send_break(port: &mut serial::SystemPort, modem: &Modem) -> TermComResult<()> {
+    configure_port(port, modem, BREAK_BAUD_RATE)?;
+    let result = port.write_all(&[0]);
+    std::thread::sleep(BREAK_TIME);
+    configure_port(port, modem, serial::BaudRate::from_speed(modem.baud_rate))?;
+    Ok(result?)
+}
+
+pub(crate) fn read_line_status(port: &mut dyn SerialPort) -> Option<LineStatus> {
+    // ports without modem control lines (like a pty) fail to report them
+    Some(LineStatus {
+        dcd: port.read_cd().ok()?,
+        dsr: port.read_dsr().ok()?,
+        cts: port.read_cts().ok()?,
+        ri: port.read_ri().ok()?,
+    })
+}
+
+pub(crate) fn set_control_lines(port: &mut dyn SerialPort, dtr: bool, rts: bool) -> TermComResult<()> {
+    port.set_dtr(dtr)?;
+    port.set_rts(rts)?;
+    Ok(())
+}
+
+pub(crate) fn read_port(port: &mut dyn SerialPort) -> TermComResult<Option<Vec<u8>>> {
+    let mut buf = vec![0; 256];
+    match port.read(&mut buf[..]) {
+        Ok(size) => {
+            buf.truncate(size);
+            Ok(Some(buf))
+        }
+        Err(e) => {
+            if e.kind() == io::ErrorKind::TimedOut {
+                Ok(None)
+            } else {
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// A direct serial connection like a null-modem cable, the port is used as it is without any AT dialing.
+pub struct ComSerialImpl {
+    modem: Modem,
+    port: serial::SystemPort,
+}
+
+impl ComSerialImpl {
+    pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
+        let Some(modem) = connection_data.modem.clone() else {
+            return Err(ConnectionError::InvalidAddress("no serial port configured".to_string()));
+        };
+        let port = open_port(&modem)?;
+        log::info!("Opened {} with {} baud", modem.device, modem.baud_rate);
+        Ok(Self { modem, port })
+    }
+}
+
+impl Com for ComSerialImpl {
+    fn get_name(&self) -> &'static str {
+        "Serial"
+    }
+
+    fn default_port(&self) -> u16 {
+        0
+    }
+
+    fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
+
+    fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
+        read_port(&mut self.port)
+    }
+
+    fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
+        self.port.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn disconnect(&mut self) -> TermComResult<()> {
+        // tells the other side that the terminal is gone
+        if let Err(err) = self.port.set_dtr(false) {
+            log::warn!("Can't drop DTR on {}: {err}", self.modem.device);
+        }
+        Ok(())
+    }
+
+    fn line_status(&mut self) -> Option<LineStatus> {
+        read_line_status(&mut self.port)
+    }
+
+    fn send_break(&mut self) -> TermComResult<()> {
+        send_break(&mut self.port, &self.modem)
+    }
+
+    fn set_control_lines(&mut self, dtr: bool, rts: bool) -> TermComResult<()> {
+        set_control_lines(&mut self.port, dtr, rts)
+    }
+}
+
+/// Opens a pseudo terminal for the tests, returns the master, the slave and the device name of the slave.
+#[cfg(all(test, unix))]
+pub(crate) fn open_test_pty() -> (std::fs::File, std::fs::File, String) {
+    use std::{ffi::CStr, fs::File, os::fd::AsRawFd, os::fd::FromRawFd};
+
+    let mut master = 0;
+    let mut slave = 0;
+    let result = unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), std::ptr::null()) };
+    assert_eq!(0, result, "openpty failed");
+    let (master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+    // ttyname returns a static buffer, the tests run in parallel
+    let mut name = [0; 256];
+    let result = unsafe { libc::ttyname_r(slave.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+    assert_eq!(0, result, "ttyname_r failed");
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) }.to_str().unwrap().to_string();
+    (master, slave, name)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{addresses::Protocol, Address, Proxy};
+    use icy_engine::Size;
+    use std::io::Read;
+
+    #[test]
+    fn test_direct_connection() {
+        let (mut master, _slave, device) = open_test_pty();
+
+        let modem = Modem {
+            device,
+            baud_rate: 115_200,
+            ..Default::default()
+        };
+        let mut address = Address::new("test");
+        address.protocol = Protocol::Serial;
+        let data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), Some(modem), &Proxy::default());
+        let mut com = ComSerialImpl::connect(&data).unwrap();
+
+        master.write_all(b"READY.\r").unwrap();
+        let mut received = Vec::new();
+        while received.len() < 7 {
+            received.extend(com.read_data().unwrap().unwrap_or_default());
+        }
+        assert_eq!(b"READY.\r".to_vec(), received);
+
+        // nothing like an init string got sent before the data
+        com.send(b"LOAD\r").unwrap();
+        // the queued output gets sent before the break, without a NUL byte after it
+        com.send_break().unwrap();
+        com.send(b"RUN\r").unwrap();
+        let mut buf = [0; 9];
+        master.read_exact(&mut buf).unwrap();
+        assert_eq!(b"LOAD\rRUN\r", &buf);
+    }
+}
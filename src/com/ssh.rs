@@ -1,39 +1,59 @@
 #![allow(dead_code)]
 
-use super::{Com, OpenConnectionData, TermComResult};
-use libssh_rs::{Channel, Session, SshOption};
+use super::{connect_tcp, AuthPrompt, Com, ConnectionError, HostAddress, HostIdentity, OpenConnectionData, TermComResult, UnknownHost, READ_BUFFER_SIZE};
+use crate::addresses::{SshAlgorithms, SshAuthMethod};
+use icy_engine::Size;
+use libssh_rs::{AuthMethods, AuthStatus, Channel, KnownHosts, LogLevel, PublicKeyHashType, Session, SshKey, SshOption};
 use std::{
-    io::ErrorKind,
     io::{Read, Write},
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 use web_time::Duration;
 pub struct SSHComImpl {
     session: Session,
     channel: Arc<Mutex<Channel>>,
+    /// The socket libssh reads from
+    #[cfg(unix)]
+    socket_fd: std::os::unix::io::RawFd,
+    read_buffer: Vec<u8>,
 }
 
-const SUPPORTED_CIPHERS: &str = "aes128-ctr,aes192-ctr,aes256-ctr,aes128-gcm,aes128-gcm@openssh.com,aes256-gcm,aes256-gcm@openssh.com,aes256-cbc,aes192-cbc,aes128-cbc,blowfish-cbc,3des-cbc,arcfour256,arcfour128,cast128-cbc,arcfour";
-const SUPPORTED_KEY_EXCHANGES: &str = "ecdh-sha2-nistp256,ecdh-sha2-nistp384,ecdh-sha2-nistp521,diffie-hellman-group14-sha1,diffie-hellman-group1-sha1";
-
 impl SSHComImpl {
-    pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
+    /// Connects and logs in, `prompt` gets called for keyboard-interactive questions the stored password can't answer.
+    pub fn connect(connection_data: &OpenConnectionData, prompt: &mut dyn FnMut(AuthPrompt) -> Option<Vec<String>>) -> TermComResult<Self> {
         let session = Session::new()?;
-        let (host, port) = Self::parse_address(&connection_data.address)?;
-
-        session.set_option(SshOption::Hostname(host))?;
-        session.set_option(SshOption::Port(port))?;
-        session.set_option(SshOption::KeyExchange(SUPPORTED_KEY_EXCHANGES.to_string()))?;
-        session.set_option(SshOption::CiphersCS(SUPPORTED_CIPHERS.to_string()))?;
-        session.set_option(SshOption::CiphersSC(SUPPORTED_CIPHERS.to_string()))?;
+        let addr = HostAddress::parse(&connection_data.address, connection_data.protocol)?;
+
+        session.set_option(SshOption::Hostname(addr.host.clone()))?;
+        session.set_option(SshOption::Port(addr.port))?;
+        // connect the socket ourselves so a configured proxy gets used, libssh takes ownership of it
+        let tcp_stream = connect_tcp(&addr, connection_data)?;
+        #[cfg(unix)]
+        let socket_fd = std::os::unix::io::IntoRawFd::into_raw_fd(tcp_stream);
+        #[cfg(unix)]
+        session.set_option(SshOption::Socket(socket_fd))?;
+        #[cfg(windows)]
+        session.set_option(SshOption::Socket(std::os::windows::io::IntoRawSocket::into_raw_socket(tcp_stream)))?;
+        Self::set_algorithms(&session, &connection_data.ssh_algorithms)?;
         session.set_option(SshOption::Timeout(Duration::from_millis(5000)))?;
-        session.set_option(SshOption::LogLevel(libssh_rs::LogLevel::Warning))?;
-
-        session.connect()?;
+        if let Some(known_hosts) = Self::get_known_hosts_file() {
+            session.set_option(SshOption::KnownHosts(Some(known_hosts.to_string_lossy().to_string())))?;
+        }
 
-        //  :TODO: SECURITY: verify_known_hosts() implemented here -- ie: user must accept & we save somewhere
+        // libssh-rs 0.2 has no getters for the negotiated kex, host key, cipher and mac. libssh logs them
+        // ("Negotiated ...") at protocol level during the key exchange, so it's raised just for the handshake.
+        session.set_option(SshOption::LogLevel(LogLevel::Protocol))?;
+        let connected = session.connect();
+        session.set_option(SshOption::LogLevel(LogLevel::Warning))?;
+        connected?;
+        match session.get_server_banner() {
+            Ok(banner) => log::info!("Connected to SSH server {banner}"),
+            Err(err) => log::warn!("Can't read the SSH server banner: {err}"),
+        }
+        Self::verify_host_key(&session, connection_data)?;
 
-        session.userauth_password(Some(connection_data.user_name.as_str()), Some(connection_data.password.as_str()))?;
+        Self::authenticate(&session, connection_data, prompt)?;
 
         let chan = session.new_channel()?;
         chan.open_session()?;
@@ -49,6 +69,9 @@ impl SSHComImpl {
         Ok(Self {
             session,
             channel: Arc::new(Mutex::new(chan)),
+            #[cfg(unix)]
+            socket_fd,
+            read_buffer: vec![0; READ_BUFFER_SIZE],
         })
     }
 
@@ -56,19 +79,179 @@ impl SSHComImpl {
         22
     }
 
-    fn parse_address(addr: &str) -> TermComResult<(String, u16)> {
-        let components: Vec<&str> = addr.split(':').collect();
-        match components.first() {
-            Some(host) => match components.get(1) {
-                Some(port_str) => {
-                    let port = port_str.parse()?;
-                    Ok(((*host).to_string(), port))
+    fn set_algorithms(session: &Session, algorithms: &SshAlgorithms) -> TermComResult<()> {
+        log::info!(
+            "Offering SSH algorithms - kex: {}, host keys: {}, ciphers: {}, macs: {}",
+            algorithms.key_exchanges,
+            algorithms.host_keys,
+            algorithms.ciphers,
+            algorithms.macs
+        );
+        session.set_option(SshOption::KeyExchange(algorithms.key_exchanges.clone()))?;
+        session.set_option(SshOption::HostKeys(algorithms.host_keys.clone()))?;
+        session.set_option(SshOption::CiphersCS(algorithms.ciphers.clone()))?;
+        session.set_option(SshOption::CiphersSC(algorithms.ciphers.clone()))?;
+        session.set_option(SshOption::HmacCS(algorithms.macs.clone()))?;
+        session.set_option(SshOption::HmacSC(algorithms.macs.clone()))?;
+        Ok(())
+    }
+
+    fn get_known_hosts_file() -> Option<PathBuf> {
+        let proj_dirs = directories::ProjectDirs::from("com", "GitHub", "icy_term")?;
+        if !proj_dirs.config_dir().exists() && std::fs::create_dir_all(proj_dirs.config_dir()).is_err() {
+            log::error!("Can't create configuration directory {:?}", proj_dirs.config_dir());
+            return None;
+        }
+        Some(proj_dirs.config_dir().join("known_hosts"))
+    }
+
+    /// Checks the server key against the known hosts. Unknown keys need to be trusted by the user first,
+    /// changed keys are always refused - the old entry needs to be removed from the known_hosts file manually.
+    fn verify_host_key(session: &Session, connection_data: &OpenConnectionData) -> TermComResult<()> {
+        let fingerprint = session.get_server_public_key()?.get_public_key_hash_hexa(PublicKeyHashType::Sha256)?;
+        match session.is_known_server()? {
+            KnownHosts::Ok => Ok(()),
+            KnownHosts::Changed | KnownHosts::Other => {
+                let known_hosts_file = Self::get_known_hosts_file().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+                Err(ConnectionError::HostKeyChanged {
+                    host: connection_data.address.clone(),
+                    fingerprint,
+                    known_hosts_file,
+                })
+            }
+            KnownHosts::NotFound | KnownHosts::Unknown => {
+                if connection_data.trusted_fingerprint.as_ref() == Some(&fingerprint) {
+                    log::info!("Adding host key of {} to known hosts: {fingerprint}", connection_data.address);
+                    session.update_known_hosts_file()?;
+                    return Ok(());
+                }
+                Err(ConnectionError::from(UnknownHost {
+                    kind: HostIdentity::SshHostKey,
+                    host: connection_data.address.clone(),
+                    reason: "The host key is not in the known hosts file.".to_string(),
+                    fingerprint,
+                    known_fingerprint: None,
+                }))
+            }
+        }
+    }
+
+    /// Tries the configured authentication methods in order until one succeeds.
+    /// Methods the server doesn't offer are skipped, a partial success continues with the remaining methods.
+    fn authenticate(session: &Session, connection_data: &OpenConnectionData, prompt: &mut dyn FnMut(AuthPrompt) -> Option<Vec<String>>) -> TermComResult<()> {
+        let user = Some(connection_data.user_name.as_str());
+        if session.userauth_none(user)? == AuthStatus::Success {
+            return Ok(());
+        }
+
+        let mut tried = Vec::new();
+        for method in &connection_data.ssh_auth_methods {
+            let supported = session.userauth_list(user)?;
+            let status = match method {
+                SshAuthMethod::PublicKey => {
+                    if connection_data.ssh_key_file.is_empty() || !supported.contains(AuthMethods::PUBLIC_KEY) {
+                        continue;
+                    }
+                    let key_file = Self::expand_home(&connection_data.ssh_key_file);
+                    let passphrase = if connection_data.ssh_key_passphrase.is_empty() {
+                        None
+                    } else {
+                        Some(connection_data.ssh_key_passphrase.as_str())
+                    };
+                    match SshKey::from_privkey_file(&key_file, passphrase) {
+                        Ok(key) => session.userauth_publickey(user, &key)?,
+                        Err(err) => {
+                            log::error!("Can't load private key {key_file}: {err}");
+                            tried.push(format!("{method} ({err})"));
+                            continue;
+                        }
+                    }
+                }
+                SshAuthMethod::Agent => {
+                    if !supported.contains(AuthMethods::PUBLIC_KEY) {
+                        continue;
+                    }
+                    match session.userauth_agent(user) {
+                        Ok(status) => status,
+                        Err(err) => {
+                            log::error!("SSH agent authentication failed: {err}");
+                            tried.push(format!("{method} ({err})"));
+                            continue;
+                        }
+                    }
+                }
+                SshAuthMethod::KeyboardInteractive => {
+                    if !supported.contains(AuthMethods::INTERACTIVE) {
+                        continue;
+                    }
+                    Self::keyboard_interactive(session, connection_data, prompt)?
+                }
+                SshAuthMethod::Password => {
+                    if !supported.contains(AuthMethods::PASSWORD) {
+                        continue;
+                    }
+                    session.userauth_password(user, Some(connection_data.password.as_str()))?
                 }
-                _ => Ok(((*host).to_string(), Self::default_port())),
-            },
-            _ => Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid address"))),
+            };
+            log::info!("SSH authentication with {method}: {status:?}");
+            match status {
+                AuthStatus::Success => return Ok(()),
+                AuthStatus::Partial => tried.push(format!("{method} (partial)")),
+                _ => tried.push(method.to_string()),
+            }
+        }
+
+        let message = if tried.is_empty() {
+            "none of the configured methods is offered by the server".to_string()
+        } else {
+            format!("tried {}", tried.join(", "))
+        };
+        Err(ConnectionError::AuthenticationFailed(message))
+    }
+
+    fn keyboard_interactive(
+        session: &Session,
+        connection_data: &OpenConnectionData,
+        prompt: &mut dyn FnMut(AuthPrompt) -> Option<Vec<String>>,
+    ) -> TermComResult<AuthStatus> {
+        let user = Some(connection_data.user_name.as_str());
+        // servers usually ask for the password with a single hidden prompt, answer that one once from the phonebook
+        let mut password_used = connection_data.password.is_empty();
+        loop {
+            let status = session.userauth_keyboard_interactive(user, None)?;
+            if status != AuthStatus::Info {
+                return Ok(status);
+            }
+            let info = session.userauth_keyboard_interactive_info()?;
+            let answers = if info.prompts.is_empty() {
+                Vec::new()
+            } else if !password_used && info.prompts.len() == 1 && !info.prompts[0].echo {
+                password_used = true;
+                vec![connection_data.password.clone()]
+            } else {
+                let auth_prompt = AuthPrompt {
+                    host: connection_data.address.clone(),
+                    name: info.name,
+                    instruction: info.instruction,
+                    prompts: info.prompts.into_iter().map(|p| (p.prompt, p.echo)).collect(),
+                };
+                let Some(answers) = prompt(auth_prompt) else {
+                    return Err(ConnectionError::Canceled);
+                };
+                answers
+            };
+            session.userauth_keyboard_interactive_set_answers(&answers)?;
         }
     }
+
+    fn expand_home(file_name: &str) -> String {
+        if let Some(rest) = file_name.strip_prefix("~/") {
+            if let Some(dirs) = directories::BaseDirs::new() {
+                return dirs.home_dir().join(rest).to_string_lossy().to_string();
+            }
+        }
+        file_name.to_string()
+    }
 }
 
 impl Com for SSHComImpl {
@@ -82,25 +265,34 @@ impl Com for SSHComImpl {
 
     fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
 
+    fn set_window_size(&mut self, window_size: Size) -> TermComResult<()> {
+        match self.channel.lock() {
+            Ok(locked) => {
+                locked.change_pty_size(window_size.width as u32, window_size.height as u32)?;
+                Ok(())
+            }
+            Err(err) => Err(ConnectionError::ConnectionLost(format!("Can't lock channel: {err}"))),
+        }
+    }
+
     fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
-        let mut buf = [0; 1024 * 256];
         match self.channel.lock() {
             Ok(locked) => {
                 let mut stdout = locked.stdout();
-                match stdout.read(&mut buf) {
-                    Ok(size) => Ok(Some(buf[0..size].to_vec())),
+                match stdout.read(&mut self.read_buffer) {
+                    // a non-blocking read returns 0 if there is nothing to read
+                    Ok(0) if locked.is_eof() => Err(ConnectionError::ClosedByRemote(None)),
+                    Ok(0) => Ok(None),
+                    Ok(size) => Ok(Some(self.read_buffer[0..size].to_vec())),
                     Err(e) => {
                         if e.kind() == std::io::ErrorKind::WouldBlock {
                             return Ok(None);
                         }
-                        Err(Box::new(std::io::Error::new(ErrorKind::ConnectionAborted, format!("Connection aborted: {e}"))))
+                        Err(ConnectionError::ConnectionLost(e.to_string()))
                     }
                 }
             }
-            Err(err) => Err(Box::new(std::io::Error::new(
-                ErrorKind::ConnectionAborted,
-                format!("Can't lock channel: {err}"),
-            ))),
+            Err(err) => Err(ConnectionError::ConnectionLost(format!("Can't lock channel: {err}"))),
         }
     }
 
@@ -110,10 +302,7 @@ impl Com for SSHComImpl {
                 locked.stdin().write_all(buf)?;
                 Ok(buf.len())
             }
-            Err(err) => Err(Box::new(std::io::Error::new(
-                ErrorKind::ConnectionAborted,
-                format!("Can't lock channel: {err}"),
-            ))),
+            Err(err) => Err(ConnectionError::ConnectionLost(format!("Can't lock channel: {err}"))),
         }
     }
 
@@ -121,6 +310,11 @@ impl Com for SSHComImpl {
         self.session.disconnect();
         Ok(())
     }
+
+    #[cfg(unix)]
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        Some(self.socket_fd)
+    }
 }
 
 /* Trushh:
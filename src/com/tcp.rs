@@ -0,0 +1,181 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{ConnectionError, HostAddress, TermComResult};
+
+/// Used if no connect timeout is configured.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before the next address is tried while the previous attempt is still pending (RFC 8305).
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+#[cfg(not(target_arch = "wasm32"))]
+const KEEPALIVE_TIME: Duration = Duration::from_secs(30);
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Time unacknowledged data may stay in the send queue before the connection is considered dead.
+#[cfg(target_os = "linux")]
+const USER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Connects to the first address of the host that answers ("happy eyeballs").
+///
+/// All resolved addresses are tried alternating between IPv6 and IPv4, a new attempt starts
+/// whenever the previous one failed or didn't succeed within a short delay.
+/// The whole connect is limited by `timeout`, the error lists the result of every attempt.
+pub fn connect_with_timeout(address: &HostAddress, timeout: Duration) -> TermComResult<TcpStream> {
+    let timeout = if timeout.is_zero() { DEFAULT_CONNECT_TIMEOUT } else { timeout };
+    let deadline = Instant::now() + timeout;
+    let addresses = match address.to_string().to_socket_addrs() {
+        Ok(addresses) => interleave_families(addresses.collect()),
+        Err(err) => {
+            log::warn!("Resolving {address} failed: {err}");
+            Vec::new()
+        }
+    };
+    if addresses.is_empty() {
+        return Err(ConnectionError::HostNotFound(address.host.clone()));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut next = 0;
+    let mut pending = 0;
+    let mut errors = Vec::new();
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            errors.push(format!("timed out after {}s", timeout.as_secs_f32()));
+            break;
+        }
+        if next < addresses.len() {
+            let a = addresses[next];
+            next += 1;
+            pending += 1;
+            let tx = tx.clone();
+            thread::spawn(move || {
+                // the receiver may be gone if another attempt succeeded, the stream gets dropped then
+                let _ = tx.send((a, TcpStream::connect_timeout(&a, remaining)));
+            });
+        }
+
+        let wait = if next < addresses.len() { ATTEMPT_DELAY.min(remaining) } else { remaining };
+        match rx.recv_timeout(wait) {
+            Ok((a, Ok(stream))) => {
+                log::info!("Connected to {address} using {a}");
+                return Ok(stream);
+            }
+            Ok((a, Err(err))) => {
+                log::warn!("Connecting to {a} failed: {err}");
+                errors.push(format!("{a}: {err}"));
+                pending -= 1;
+                if pending == 0 && next >= addresses.len() {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    let details = format!("{address} ({})", errors.join(", "));
+    if pending > 0 {
+        Err(ConnectionError::TimedOut(details))
+    } else {
+        Err(ConnectionError::ConnectionRefused(details))
+    }
+}
+
+/// Orders the addresses alternating between the address families, starting with the family of the first one.
+fn interleave_families(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addresses.first() else {
+        return addresses;
+    };
+    let first_is_v6 = first.is_ipv6();
+    let len = addresses.len();
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses.into_iter().partition(|a| a.is_ipv6() == first_is_v6);
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    let mut result = Vec::with_capacity(len);
+    while result.len() < len {
+        result.extend(preferred.next());
+        result.extend(other.next());
+    }
+    result
+}
+
+/// Enables TCP keepalive so a connection to a vanished host fails instead of waiting forever for data.
+pub fn enable_keepalive(stream: &TcpStream) -> io::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let socket = socket2::SockRef::from(stream);
+        let keepalive = socket2::TcpKeepalive::new().with_time(KEEPALIVE_TIME);
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+        let keepalive = keepalive.with_interval(KEEPALIVE_INTERVAL);
+        socket.set_tcp_keepalive(&keepalive)?;
+        #[cfg(target_os = "linux")]
+        socket.set_tcp_user_timeout(Some(USER_TIMEOUT))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addresses::Protocol;
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+    fn v4(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), port))
+    }
+
+    fn v6(port: u16) -> SocketAddr {
+        SocketAddr::from((Ipv6Addr::LOCALHOST, port))
+    }
+
+    #[test]
+    fn test_interleave_families() {
+        assert_eq!(vec![v6(1), v4(3), v6(2), v4(4)], interleave_families(vec![v6(1), v6(2), v4(3), v4(4)]));
+        assert_eq!(vec![v4(1), v6(2), v4(3), v4(4)], interleave_families(vec![v4(1), v6(2), v4(3), v4(4)]));
+        assert_eq!(vec![v4(1), v4(2)], interleave_families(vec![v4(1), v4(2)]));
+        assert!(interleave_families(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = HostAddress::parse(&listener.local_addr().unwrap().to_string(), Protocol::Raw).unwrap();
+        let stream = connect_with_timeout(&address, Duration::from_secs(1)).unwrap();
+        enable_keepalive(&stream).unwrap();
+    }
+
+    #[test]
+    fn test_connect_tries_all_addresses() {
+        // 'localhost' may resolve to ::1 first, that attempt fails because only IPv4 is listening
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let address = HostAddress::parse(&format!("localhost:{port}"), Protocol::Raw).unwrap();
+        connect_with_timeout(&address, Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn test_host_not_found() {
+        let address = HostAddress::parse("unknown.invalid:23", Protocol::Raw).unwrap();
+        let err = connect_with_timeout(&address, Duration::from_secs(1)).unwrap_err();
+        assert_eq!(ConnectionError::HostNotFound("unknown.invalid".to_string()), err);
+    }
+
+    #[test]
+    fn test_connect_reports_attempts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let address = HostAddress::parse(&format!("127.0.0.1:{port}"), Protocol::Raw).unwrap();
+        let err = connect_with_timeout(&address, Duration::from_secs(1)).unwrap_err();
+        assert!(matches!(err, ConnectionError::ConnectionRefused(_)), "{err:?}");
+        assert!(err.to_string().contains(&format!("127.0.0.1:{port}: ")), "{err}");
+    }
+}
@@ -0,0 +1,1260 @@
+use crate::addresses::{CharacterSet, Terminal};
+
+use super::OpenConnectionData;
+use icy_engine::Size;
+
+/// The telnet protocol without the transport: received data is parsed into terminal data,
+/// negotiation replies are collected until they're fetched with `take_reply`.
+#[derive(Debug)]
+pub struct TelnetParser {
+    state: ParserState,
+    options: OptionTable,
+    sub_negotiation: Vec<u8>,
+    environment: Vec<(String, String)>,
+    /// agreed character set that wasn't yet reported by `take_character_set`
+    character_set: Option<CharacterSet>,
+    window_size: Size, // width, height
+    terminal: Terminal,
+    terminal_type_names: Vec<String>,
+    terminal_type_index: usize,
+    use_raw_transfer: bool,
+    /// Data for the server produced by the negotiation
+    reply: Vec<u8>,
+}
+
+#[derive(Debug)]
+enum ParserState {
+    Data,
+    Iac,
+    Negotiation(u8),
+    SubNegotiation,
+    SubNegotiationIac,
+}
+
+mod terminal_type {
+    pub const IS: u8 = 0x00;
+    pub const SEND: u8 = 0x01;
+    // pub const MAXLN: usize = 40;
+}
+
+/// <https://www.rfc-editor.org/rfc/rfc2066>
+mod charset {
+    pub const REQUEST: u8 = 0x01;
+    pub const ACCEPTED: u8 = 0x02;
+    pub const REJECTED: u8 = 0x03;
+    pub const TTABLE_IS: u8 = 0x04;
+    pub const TTABLE_REJECTED: u8 = 0x05;
+
+    pub const TTABLE: &[u8] = b"[TTABLE]";
+}
+
+/// <https://www.rfc-editor.org/rfc/rfc1572>
+mod new_environ {
+    pub const IS: u8 = 0x00;
+    pub const SEND: u8 = 0x01;
+    pub const INFO: u8 = 0x02;
+
+    pub const VAR: u8 = 0x00;
+    pub const VALUE: u8 = 0x01;
+    pub const ESC: u8 = 0x02;
+    pub const USERVAR: u8 = 0x03;
+
+    /// Variables that are sent as VAR, everything else is a USERVAR.
+    pub const WELL_KNOWN_VARS: [&str; 6] = ["USER", "JOB", "ACCT", "PRINTER", "SYSTEMTYPE", "DISPLAY"];
+
+    pub fn get_type(name: &str) -> u8 {
+        if WELL_KNOWN_VARS.contains(&name) {
+            VAR
+        } else {
+            USERVAR
+        }
+    }
+}
+
+mod telnet_cmd {
+    use crate::com::{ConnectionError, TermComResult};
+
+    /// End of subnegotiation parameters.
+    pub const SE: u8 = 0xF0;
+
+    /// No operation.
+    pub const Nop: u8 = 0xF1;
+
+    /// The data stream portion of a Synch.
+    /// This should always be accompanied
+    /// by a TCP Urgent notification.
+    pub const DataMark: u8 = 0xF2;
+
+    /// NVT character BRK
+    pub const Break: u8 = 0xF3;
+
+    /// The function Interrupt Process
+    pub const IP: u8 = 0xF4;
+
+    // The function Abort output
+    pub const AO: u8 = 0xF5;
+
+    // The function Are You There
+    pub const Ayt: u8 = 0xF6;
+
+    // The function Erase character
+    pub const EC: u8 = 0xF7;
+
+    // The function Erase line
+    pub const EL: u8 = 0xF8;
+
+    // The Go ahead signal.
+    pub const GA: u8 = 0xF9;
+
+    // Indicates that what follows is subnegotiation of the indicated option.
+    pub const SB: u8 = 0xFA;
+
+    ///  (option code)
+    /// Indicates the desire to begin performing, or confirmation that you are now performing, the indicated option.
+    pub const Will: u8 = 0xFB;
+
+    /// (option code)
+    /// Indicates the refusal to perform, or continue performing, the indicated option.
+    pub const Wont: u8 = 0xFC;
+
+    /// (option code)
+    /// Indicates the request that the other party perform, or confirmation that you are expecting
+    /// the other party to perform, the indicated option.
+    pub const DO: u8 = 0xFD;
+
+    /// (option code)
+    /// Indicates the demand that the other party stop performing,
+    /// or confirmation that you are no longer expecting the other party
+    /// to perform, the indicated option.
+    pub const Dont: u8 = 0xFE;
+
+    /// Data Byte 255.
+    pub const Iac: u8 = 0xFF;
+
+    pub fn make_cmd(byte: u8) -> [u8; 2] {
+        [Iac, byte]
+    }
+
+    pub fn make_cmd_with_option(byte: u8, option: u8) -> [u8; 3] {
+        [Iac, byte, option]
+    }
+
+    pub fn check(byte: u8) -> TermComResult<u8> {
+        match byte {
+            0xF0..=0xFF => Ok(byte),
+            _ => Err(ConnectionError::Protocol(format!("unknown IAC: {byte}/x{byte:02X}"))),
+        }
+    }
+
+    pub fn to_string(byte: u8) -> &'static str {
+        match byte {
+            SE => "SE",
+            Nop => "Nop",
+            DataMark => "DataMark",
+            Break => "Break",
+            IP => "IP",
+            AO => "AO",
+            Ayt => "Ayt",
+            EC => "EC",
+            EL => "EL",
+            GA => "GA",
+            SB => "SB",
+            Will => "Will",
+            Wont => "Wont",
+            DO => "DO",
+            Dont => "Dont",
+            Iac => "Iac",
+            _ => "unknown",
+        }
+    }
+}
+
+/**
+<http://www.iana.org/assignments/telnet-options/telnet-options.xhtml>
+*/
+pub mod telnet_option {
+    /// <https://www.rfc-editor.org/rfc/rfc856>
+    pub const TransmitBinary: u8 = 0x00;
+    /// <https://www.rfc-editor.org/rfc/rfc857>
+    pub const Echo: u8 = 0x01;
+    /// ???
+    pub const Reconnection: u8 = 0x02;
+    /// <https://www.rfc-editor.org/rfc/rfc858>
+    pub const SuppressGoAhead: u8 = 0x03;
+    /// <https://www.rfc-editor.org/rfc/rfc859>
+    pub const Status: u8 = 0x05;
+    /// <https://www.rfc-editor.org/rfc/rfc860>
+    pub const TimingMark: u8 = 0x06;
+    /// <https://www.rfc-editor.org/rfc/rfc726.html>
+    pub const RemoteControlledTransAndEcho: u8 = 0x07;
+    /// ???
+    pub const OutputLineWidth: u8 = 0x08;
+    /// ???
+    pub const OutputPageSize: u8 = 0x09;
+    ///<https://www.rfc-editor.org/rfc/RFC652>
+    pub const OutputCarriageReturnDisposition: u8 = 10;
+    ///<https://www.rfc-editor.org/rfc/RFC653>
+    pub const OutputHorizontalTabStops: u8 = 11;
+    ///<https://www.rfc-editor.org/rfc/RFC654>
+    pub const OutputHorizontalTabDisposition: u8 = 12;
+    ///<https://www.rfc-editor.org/rfc/RFC655>
+    pub const OutputFormfeedDisposition: u8 = 13;
+    ///<https://www.rfc-editor.org/rfc/RFC656>
+    pub const OutputVerticalTabstops: u8 = 14;
+    ///<https://www.rfc-editor.org/rfc/RFC657>
+    pub const OutputVerticalTabDisposition: u8 = 15;
+    ///<https://www.rfc-editor.org/rfc/RFC658>
+    pub const OutputLinefeedDisposition: u8 = 16;
+    ///<https://www.rfc-editor.org/rfc/RFC698>
+    pub const ExtendedASCII: u8 = 17;
+    ///<https://www.rfc-editor.org/rfc/RFC727>
+    pub const Logout: u8 = 18;
+    ///<https://www.rfc-editor.org/rfc/RFC735>
+    pub const ByteMacro: u8 = 19;
+    ///<https://www.rfc-editor.org/rfc/RFC1043][RFC732>
+    pub const DataEntryTerminal: u8 = 20;
+    ///<https://www.rfc-editor.org/rfc/RFC736][RFC734>
+    pub const SupDup: u8 = 21;
+    ///<https://www.rfc-editor.org/rfc/RFC749>
+    pub const SupDupOutput: u8 = 22;
+    ///<https://www.rfc-editor.org/rfc/RFC779>
+    pub const SendLocation: u8 = 23;
+    /// <https://www.rfc-editor.org/rfc/rfc1091>
+    pub const TerminalType: u8 = 24;
+    /// <https://www.rfc-editor.org/rfc/rfc885>
+    pub const EndOfRecord: u8 = 25;
+    /// <https://www.rfc-editor.org/rfc/rfc1073>
+    pub const NegotiateAboutWindowSize: u8 = 31;
+    /// <https://www.rfc-editor.org/rfc/rfc1079>
+    pub const TerminalSpeed: u8 = 32;
+    /// <https://www.rfc-editor.org/rfc/rfc1372>
+    pub const ToggleFlowControl: u8 = 33;
+    /// <https://www.rfc-editor.org/rfc/rfc1184>
+    pub const LineMode: u8 = 34;
+    /// <https://www.rfc-editor.org/rfc/rfc1096>
+    pub const XDisplayLocation: u8 = 35;
+    /// <https://www.rfc-editor.org/rfc/rfc1408>
+    pub const EnvironmentOption: u8 = 36;
+    /// <https://www.rfc-editor.org/rfc/rfc2941>
+    pub const Authentication: u8 = 37;
+    /// <https://www.rfc-editor.org/rfc/rfc2946>
+    pub const Encrypt: u8 = 38;
+    /// <https://www.rfc-editor.org/rfc/rfc1572>
+    pub const NewEnviron: u8 = 39;
+    ///<https://www.rfc-editor.org/rfc/RFC2355>
+    pub const TN3270E: u8 = 40;
+    ///<https://www.rfc-editor.org/rfc/Rob_Earhart>
+    pub const XAuth: u8 = 41;
+    ///<https://www.rfc-editor.org/rfc/RFC2066>
+    pub const CharSet: u8 = 42;
+    ///<https://www.rfc-editor.org/rfc/Robert_Barnes>
+    pub const TelnetRemoteSerialPortRSP: u8 = 43;
+    ///<https://www.rfc-editor.org/rfc/RFC2217>
+    pub const ComPortControlOption: u8 = 44;
+    ///<https://www.rfc-editor.org/rfc/Wirt_Atmar>
+    pub const TelnetSuppressLocalEcho: u8 = 45;
+    ///<https://www.rfc-editor.org/rfc/Michael_Boe>
+    pub const TelnetStartTLS: u8 = 46;
+    ///<https://www.rfc-editor.org/rfc/RFC2840>
+    pub const Kermit: u8 = 47;
+    ///<https://www.rfc-editor.org/rfc/David_Croft>
+    pub const SendURL: u8 = 48;
+    ///<https://www.rfc-editor.org/rfc/Jeffrey_Altman>
+    pub const ForwardX: u8 = 49;
+    // 50-137 	Unassigned
+    pub const TelOptPragmaLogon: u8 = 138;
+    ///<https://www.rfc-editor.org/rfc/Steve_McGregory>
+    pub const TelOptSSPILogon: u8 = 139;
+    ///<https://www.rfc-editor.org/rfc/Steve_McGregory>
+    pub const TelOptPragmaHeartbeat: u8 = 140;
+    ///<https://www.rfc-editor.org/rfc/Steve_McGregory>
+    // 141-254 	Unassigned
+    /// <https://www.rfc-editor.org/rfc/rfc861>
+    pub const ExtendedOptionsList: u8 = 0xFF;
+
+    pub fn to_string(byte: u8) -> &'static str {
+        match byte {
+            TransmitBinary => "TransmitBinary",
+            Echo => "Echo",
+            Reconnection => "Reconnection",
+            SuppressGoAhead => "SuppressGoAhead",
+            Status => "Status",
+            TimingMark => "TimingMark",
+            RemoteControlledTransAndEcho => "RemoteControlledTransAndEcho",
+            OutputLineWidth => "OutputLineWidth",
+            OutputPageSize => "OutputPageSize",
+            OutputCarriageReturnDisposition => "OutputCarriageReturnDisposition",
+            OutputHorizontalTabStops => "OutputHorizontalTabStops",
+            OutputHorizontalTabDisposition => "OutputHorizontalTabDisposition",
+            OutputFormfeedDisposition => "OutputFormfeedDisposition",
+            OutputVerticalTabstops => "OutputVerticalTabstops",
+            OutputVerticalTabDisposition => "OutputVerticalTabDisposition",
+            OutputLinefeedDisposition => "OutputLinefeedDisposition",
+            ExtendedASCII => "ExtendedASCII",
+            Logout => "Logout",
+            ByteMacro => "ByteMacro",
+            DataEntryTerminal => "DataEntryTerminal",
+            SupDup => "SupDup",
+            SupDupOutput => "SupDupOutput",
+            SendLocation => "SendLocation",
+            TerminalType => "TerminalType",
+            EndOfRecord => "EndOfRecord",
+            NegotiateAboutWindowSize => "NegotiateAboutWindowSize",
+            TerminalSpeed => "TerminalSpeed",
+            ToggleFlowControl => "ToggleFlowControl",
+            LineMode => "LineMode",
+            XDisplayLocation => "XDisplayLocation",
+            EnvironmentOption => "EnvironmentOption",
+            Authentication => "Authentication",
+            Encrypt => "Encrypt",
+            NewEnviron => "NewEnviron",
+            TN3270E => "TN3270E",
+            XAuth => "XAuth",
+            CharSet => "CharSet",
+            TelnetRemoteSerialPortRSP => "TelnetRemoteSerialPortRSP",
+            ComPortControlOption => "ComPortControlOption",
+            TelnetSuppressLocalEcho => "TelnetSuppressLocalEcho",
+            TelnetStartTLS => "TelnetStartTLS",
+            Kermit => "Kermit",
+            SendURL => "SendURL",
+            ForwardX => "ForwardX",
+            TelOptPragmaLogon => "TelOptPragmaLogon",
+            TelOptSSPILogon => "TelOptSSPILogon",
+            TelOptPragmaHeartbeat => "TelOptPragmaHeartbeat",
+            ExtendedOptionsList => "ExtendedOptionsList",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// State of one side of an option as described in RFC 1143 ("Q method").
+/// <https://www.rfc-editor.org/rfc/rfc1143>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionState {
+    #[default]
+    No,
+    Yes,
+    WantNo,
+    WantYes,
+}
+
+/// Queue bit of the Q method - `Opposite` means that the opposite state was requested
+/// while a negotiation was still in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptionQueue {
+    #[default]
+    Empty,
+    Opposite,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct OptionSide {
+    state: OptionState,
+    queue: OptionQueue,
+}
+
+impl OptionSide {
+    /// Handles a received WILL (remote side) or DO (local side).
+    fn receive_enable(&mut self, supported: bool, positive: u8, negative: u8, opt: u8, out: &mut Vec<u8>) {
+        match self.state {
+            OptionState::No => {
+                if supported {
+                    self.state = OptionState::Yes;
+                    out.extend_from_slice(&telnet_cmd::make_cmd_with_option(positive, opt));
+                } else {
+                    out.extend_from_slice(&telnet_cmd::make_cmd_with_option(negative, opt));
+                }
+            }
+            OptionState::Yes => {}
+            OptionState::WantNo => {
+                log::warn!("{} answered by enable request", telnet_option::to_string(opt));
+                if self.queue == OptionQueue::Empty {
+                    self.state = OptionState::No;
+                } else {
+                    self.state = OptionState::Yes;
+                    self.queue = OptionQueue::Empty;
+                }
+            }
+            OptionState::WantYes => {
+                if self.queue == OptionQueue::Empty {
+                    self.state = OptionState::Yes;
+                } else {
+                    self.state = OptionState::WantNo;
+                    self.queue = OptionQueue::Empty;
+                    out.extend_from_slice(&telnet_cmd::make_cmd_with_option(negative, opt));
+                }
+            }
+        }
+    }
+
+    /// Handles a received WONT (remote side) or DONT (local side).
+    fn receive_disable(&mut self, positive: u8, negative: u8, opt: u8, out: &mut Vec<u8>) {
+        match self.state {
+            OptionState::No => {}
+            OptionState::Yes => {
+                self.state = OptionState::No;
+                out.extend_from_slice(&telnet_cmd::make_cmd_with_option(negative, opt));
+            }
+            OptionState::WantNo => {
+                if self.queue == OptionQueue::Empty {
+                    self.state = OptionState::No;
+                } else {
+                    self.state = OptionState::WantYes;
+                    self.queue = OptionQueue::Empty;
+                    out.extend_from_slice(&telnet_cmd::make_cmd_with_option(positive, opt));
+                }
+            }
+            OptionState::WantYes => {
+                self.state = OptionState::No;
+                self.queue = OptionQueue::Empty;
+            }
+        }
+    }
+
+    /// Starts a negotiation initiated by our side.
+    fn request(&mut self, enable: bool, positive: u8, negative: u8, opt: u8, out: &mut Vec<u8>) {
+        match (self.state, enable) {
+            (OptionState::No, true) => {
+                self.state = OptionState::WantYes;
+                out.extend_from_slice(&telnet_cmd::make_cmd_with_option(positive, opt));
+            }
+            (OptionState::Yes, false) => {
+                self.state = OptionState::WantNo;
+                out.extend_from_slice(&telnet_cmd::make_cmd_with_option(negative, opt));
+            }
+            (OptionState::WantNo, true) | (OptionState::WantYes, false) => {
+                self.queue = OptionQueue::Opposite;
+            }
+            (OptionState::WantNo, false) | (OptionState::WantYes, true) => {
+                self.queue = OptionQueue::Empty;
+            }
+            (OptionState::No, false) | (OptionState::Yes, true) => {}
+        }
+    }
+}
+
+/// Per option negotiation state of both sides of a telnet connection.
+/// "local" options are the ones we perform (WILL/WONT), "remote" options the ones the server performs (DO/DONT).
+#[derive(Debug)]
+pub struct OptionTable {
+    local: [OptionSide; 256],
+    remote: [OptionSide; 256],
+    local_supported: [bool; 256],
+    remote_supported: [bool; 256],
+}
+
+impl Default for OptionTable {
+    fn default() -> Self {
+        let mut res = Self {
+            local: [OptionSide::default(); 256],
+            remote: [OptionSide::default(); 256],
+            local_supported: [false; 256],
+            remote_supported: [false; 256],
+        };
+        res.set_local_supported(telnet_option::TransmitBinary, true);
+        res.set_local_supported(telnet_option::TerminalType, true);
+        res.set_local_supported(telnet_option::NegotiateAboutWindowSize, true);
+        res.set_local_supported(telnet_option::NewEnviron, true);
+        res.set_local_supported(telnet_option::CharSet, true);
+
+        res.set_remote_supported(telnet_option::TransmitBinary, true);
+        res.set_remote_supported(telnet_option::Echo, true);
+        res.set_remote_supported(telnet_option::SuppressGoAhead, true);
+        res.set_remote_supported(telnet_option::CharSet, true);
+        res
+    }
+}
+
+impl OptionTable {
+    pub fn local_state(&self, opt: u8) -> OptionState {
+        self.local[opt as usize].state
+    }
+
+    pub fn remote_state(&self, opt: u8) -> OptionState {
+        self.remote[opt as usize].state
+    }
+
+    /// Returns true if we perform the option.
+    pub fn is_local_enabled(&self, opt: u8) -> bool {
+        self.local_state(opt) == OptionState::Yes
+    }
+
+    /// Returns true if the server performs the option, e.g. `is_remote_enabled(telnet_option::Echo)` for remote echo.
+    pub fn is_remote_enabled(&self, opt: u8) -> bool {
+        self.remote_state(opt) == OptionState::Yes
+    }
+
+    pub fn set_local_supported(&mut self, opt: u8, supported: bool) {
+        self.local_supported[opt as usize] = supported;
+    }
+
+    pub fn set_remote_supported(&mut self, opt: u8, supported: bool) {
+        self.remote_supported[opt as usize] = supported;
+    }
+
+    /// Processes a received WILL/WONT/DO/DONT and appends the reply (if any) to `out`.
+    pub fn receive(&mut self, cmd: u8, opt: u8, out: &mut Vec<u8>) {
+        let i = opt as usize;
+        match cmd {
+            telnet_cmd::Will => self.remote[i].receive_enable(self.remote_supported[i], telnet_cmd::DO, telnet_cmd::Dont, opt, out),
+            telnet_cmd::Wont => self.remote[i].receive_disable(telnet_cmd::DO, telnet_cmd::Dont, opt, out),
+            telnet_cmd::DO => self.local[i].receive_enable(self.local_supported[i], telnet_cmd::Will, telnet_cmd::Wont, opt, out),
+            telnet_cmd::Dont => self.local[i].receive_disable(telnet_cmd::Will, telnet_cmd::Wont, opt, out),
+            _ => log::error!("invalid negotiation command: {}", telnet_cmd::to_string(cmd)),
+        }
+    }
+
+    /// Asks to enable or disable an option on our side. The request is appended to `out`.
+    pub fn request_local(&mut self, opt: u8, enable: bool, out: &mut Vec<u8>) {
+        self.local[opt as usize].request(enable, telnet_cmd::Will, telnet_cmd::Wont, opt, out);
+    }
+
+    /// Asks the server to enable or disable an option. The request is appended to `out`.
+    pub fn request_remote(&mut self, opt: u8, enable: bool, out: &mut Vec<u8>) {
+        self.remote[opt as usize].request(enable, telnet_cmd::DO, telnet_cmd::Dont, opt, out);
+    }
+}
+
+impl TelnetParser {
+    pub fn new(connection_data: &OpenConnectionData) -> Self {
+        Self {
+            state: ParserState::Data,
+            options: OptionTable::default(),
+            sub_negotiation: Vec::new(),
+            environment: Self::get_environment(connection_data),
+            character_set: None,
+            window_size: connection_data.window_size,
+            terminal: connection_data.terminal,
+            terminal_type_names: connection_data.terminal_type_names.clone(),
+            terminal_type_index: 0,
+            use_raw_transfer: false,
+            reply: Vec::new(),
+        }
+    }
+
+    /// Returns the negotiation state of all telnet options.
+    pub fn options(&self) -> &OptionTable {
+        &self.options
+    }
+
+    /// Returns the data that needs to be sent to the server and clears it.
+    pub fn take_reply(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.reply)
+    }
+
+    pub fn take_character_set(&mut self) -> Option<CharacterSet> {
+        self.character_set.take()
+    }
+
+    pub fn set_terminal_type(&mut self, terminal: Terminal) {
+        self.terminal = terminal;
+    }
+
+    /// In raw mode data is passed through unchanged, used for file transfers.
+    pub fn set_raw_mode(&mut self, raw_transfer: bool) {
+        self.use_raw_transfer = raw_transfer;
+    }
+
+    /// Asks the server whether we may perform `opt` (sends WILL/WONT).
+    /// Nothing in the client starts a negotiation yet, the connection only reports the state of the server's options.
+    #[allow(dead_code)]
+    pub fn request_local_option(&mut self, opt: u8, enable: bool) {
+        self.options.request_local(opt, enable, &mut self.reply);
+    }
+
+    /// Asks the server to perform `opt` (sends DO/DONT).
+    #[allow(dead_code)]
+    pub fn request_remote_option(&mut self, opt: u8, enable: bool) {
+        self.options.request_remote(opt, enable, &mut self.reply);
+    }
+
+    /// The new size is sent to the server if NAWS is enabled.
+    pub fn set_window_size(&mut self, window_size: Size) {
+        if self.window_size == window_size {
+            return;
+        }
+        self.window_size = window_size;
+        if self.options.is_local_enabled(telnet_option::NegotiateAboutWindowSize) {
+            let mut buf = Vec::new();
+            self.write_window_size(&mut buf);
+            self.reply.extend(buf);
+        }
+    }
+
+    /// Escapes IAC bytes of data that's sent to the server.
+    pub fn encode(&self, data: &[u8]) -> Vec<u8> {
+        if self.use_raw_transfer {
+            return data.to_vec();
+        }
+        let mut result = Vec::with_capacity(data.len());
+        for b in data {
+            if *b == telnet_cmd::Iac {
+                result.extend_from_slice(&[telnet_cmd::Iac, telnet_cmd::Iac]);
+            } else {
+                result.push(*b);
+            }
+        }
+        result
+    }
+
+    fn get_environment(connection_data: &OpenConnectionData) -> Vec<(String, String)> {
+        let mut result = Vec::new();
+        if !connection_data.user_name.is_empty() {
+            result.push(("USER".to_string(), connection_data.user_name.clone()));
+        }
+        for (name, value) in &connection_data.environment {
+            if name != "USER" || connection_data.user_name.is_empty() {
+                result.push((name.clone(), value.clone()));
+            }
+        }
+        result
+    }
+
+    /// Sets an environment variable for NEW-ENVIRON, the server is informed about the change if the option is enabled.
+    pub fn set_environment_variable(&mut self, name: &str, value: &str) {
+        if let Some(var) = self.environment.iter_mut().find(|(n, _)| n == name) {
+            var.1 = value.to_string();
+        } else {
+            self.environment.push((name.to_string(), value.to_string()));
+        }
+
+        if self.options.is_local_enabled(telnet_option::NewEnviron) {
+            let mut buf = telnet_cmd::make_cmd_with_option(telnet_cmd::SB, telnet_option::NewEnviron).to_vec();
+            buf.push(new_environ::INFO);
+            write_environment_variable(&mut buf, name, Some(value));
+            buf.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::SE));
+            self.reply.extend(buf);
+        }
+    }
+
+    fn handle_sub_negotiation(&mut self) {
+        let mut reply = Vec::new();
+        let sub_negotiation = std::mem::take(&mut self.sub_negotiation);
+        // RFC 2066: CHARSET subnegotiations are only valid once the option is enabled in either direction
+        let charset_enabled = self.options.is_local_enabled(telnet_option::CharSet) || self.options.is_remote_enabled(telnet_option::CharSet);
+        match sub_negotiation.first() {
+            Some(&telnet_option::TerminalType) => {
+                if sub_negotiation.get(1) == Some(&terminal_type::SEND) {
+                    self.write_terminal_type(&mut reply);
+                }
+            }
+            Some(&telnet_option::NewEnviron) => {
+                if sub_negotiation.get(1) == Some(&new_environ::SEND) {
+                    self.write_environment(&sub_negotiation[2..], &mut reply);
+                }
+            }
+            Some(&telnet_option::CharSet) if !charset_enabled => {
+                log::warn!("ignoring CHARSET subnegotiation, the option isn't enabled");
+            }
+            Some(&telnet_option::CharSet) => match sub_negotiation.get(1) {
+                Some(&charset::REQUEST) => {
+                    let offered = parse_charset_request(&sub_negotiation[2..]);
+                    self.write_charset_reply(&offered, &mut reply);
+                }
+                Some(&charset::ACCEPTED) => {
+                    let name = String::from_utf8_lossy(&sub_negotiation[2..]).into_owned();
+                    log::info!("server accepted character set {name}");
+                    self.character_set = CharacterSet::from_name(&name);
+                }
+                Some(&charset::REJECTED) => {
+                    log::info!("server rejected all offered character sets");
+                }
+                Some(&charset::TTABLE_IS) => {
+                    reply.extend_from_slice(&[
+                        telnet_cmd::Iac,
+                        telnet_cmd::SB,
+                        telnet_option::CharSet,
+                        charset::TTABLE_REJECTED,
+                        telnet_cmd::Iac,
+                        telnet_cmd::SE,
+                    ]);
+                }
+                _ => {}
+            },
+            Some(opt) => {
+                log::warn!("unsupported subnegotiation {}", telnet_option::to_string(*opt));
+            }
+            None => {}
+        }
+        self.sub_negotiation = sub_negotiation;
+        self.reply.extend(reply);
+    }
+
+    /// Answers TTYPE SEND with the next name of the list (RFC 1091). After the last name it's repeated once
+    /// to mark the end of the list, the next request starts again with the first name.
+    fn write_terminal_type(&mut self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&[telnet_cmd::Iac, telnet_cmd::SB, telnet_option::TerminalType, terminal_type::IS]);
+        let len = self.terminal_type_names.len();
+        if len > 0 {
+            let name = &self.terminal_type_names[self.terminal_type_index.min(len - 1)];
+            log::info!("sending terminal type {name}");
+            for b in name.as_bytes() {
+                if *b == telnet_cmd::Iac {
+                    out.push(telnet_cmd::Iac);
+                }
+                out.push(*b);
+            }
+            self.terminal_type_index = if self.terminal_type_index >= len { 0 } else { self.terminal_type_index + 1 };
+        }
+        out.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::SE));
+    }
+
+    /// Answers a NEW-ENVIRON SEND request with an IS subnegotiation.
+    /// An empty request or a VAR/USERVAR without name means all variables of that kind.
+    fn write_environment(&self, request: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(&telnet_cmd::make_cmd_with_option(telnet_cmd::SB, telnet_option::NewEnviron));
+        out.push(new_environ::IS);
+
+        let requested = parse_environment_request(request);
+        if requested.is_empty() {
+            for (name, value) in &self.environment {
+                write_environment_variable(out, name, Some(value));
+            }
+        }
+        for (var_type, name) in requested {
+            if name.is_empty() {
+                for (name, value) in self.environment.iter().filter(|(n, _)| new_environ::get_type(n) == var_type) {
+                    write_environment_variable(out, name, Some(value));
+                }
+            } else {
+                let value = self.environment.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str());
+                write_environment_variable(out, &name, value);
+            }
+        }
+        out.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::SE));
+    }
+
+    /// Accepts the offered character set we like most or rejects them all.
+    fn write_charset_reply(&mut self, offered: &[String], out: &mut Vec<u8>) {
+        let supported = CharacterSet::get_supported(self.terminal);
+        let selected = supported
+            .iter()
+            .find_map(|cs| offered.iter().find(|name| CharacterSet::from_name(name) == Some(*cs)));
+
+        out.extend_from_slice(&telnet_cmd::make_cmd_with_option(telnet_cmd::SB, telnet_option::CharSet));
+        if let Some(name) = selected {
+            log::info!("accepted character set {name}");
+            self.character_set = CharacterSet::from_name(name);
+            out.push(charset::ACCEPTED);
+            out.extend_from_slice(name.as_bytes());
+        } else {
+            log::info!("rejected character sets {offered:?}");
+            out.push(charset::REJECTED);
+        }
+        out.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::SE));
+    }
+
+    /// Offers the character sets supported by the current terminal, preferred first.
+    fn write_charset_request(&self, out: &mut Vec<u8>) {
+        let supported = CharacterSet::get_supported(self.terminal);
+        if supported.is_empty() {
+            return;
+        }
+        out.extend_from_slice(&telnet_cmd::make_cmd_with_option(telnet_cmd::SB, telnet_option::CharSet));
+        out.push(charset::REQUEST);
+        for cs in supported {
+            out.push(b';');
+            out.extend_from_slice(cs.get_name().as_bytes());
+        }
+        out.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::SE));
+    }
+
+    /// Called when one of our options switched to enabled.
+    fn local_option_enabled(&mut self, opt: u8, out: &mut Vec<u8>) {
+        match opt {
+            telnet_option::NegotiateAboutWindowSize => self.write_window_size(out),
+            telnet_option::CharSet => self.write_charset_request(out),
+            _ => {}
+        }
+    }
+
+    /// NAWS subnegotiation - width & height as 16 bit values, 255 needs to be escaped.
+    fn write_window_size(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&telnet_cmd::make_cmd_with_option(telnet_cmd::SB, telnet_option::NegotiateAboutWindowSize));
+        for b in (self.window_size.width as u16)
+            .to_be_bytes()
+            .iter()
+            .chain((self.window_size.height as u16).to_be_bytes().iter())
+        {
+            if *b == telnet_cmd::Iac {
+                out.push(telnet_cmd::Iac);
+            }
+            out.push(*b);
+        }
+        out.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::SE));
+    }
+
+    /// Returns the terminal data of `data`, negotiation replies are added to the reply.
+    pub fn parse(&mut self, data: &[u8]) -> Vec<u8> {
+        if self.use_raw_transfer {
+            return data.to_vec();
+        }
+        let mut buf = Vec::with_capacity(data.len());
+        for b in data {
+            match self.state {
+                ParserState::Data => {
+                    if *b == telnet_cmd::Iac {
+                        self.state = ParserState::Iac;
+                    } else {
+                        buf.push(*b);
+                    }
+                }
+
+                ParserState::SubNegotiation => {
+                    if *b == telnet_cmd::Iac {
+                        self.state = ParserState::SubNegotiationIac;
+                    } else {
+                        self.sub_negotiation.push(*b);
+                    }
+                }
+                ParserState::SubNegotiationIac => match *b {
+                    telnet_cmd::Iac => {
+                        self.sub_negotiation.push(0xFF);
+                        self.state = ParserState::SubNegotiation;
+                    }
+                    telnet_cmd::SE => {
+                        self.state = ParserState::Data;
+                        self.handle_sub_negotiation();
+                    }
+                    cmd => {
+                        log::error!("unexpected IAC {} in subnegotiation", telnet_cmd::to_string(cmd));
+                        self.state = ParserState::Data;
+                    }
+                },
+                ParserState::Iac => match telnet_cmd::check(*b) {
+                    Ok(telnet_cmd::Ayt) => {
+                        self.state = ParserState::Data;
+                        self.reply.extend_from_slice(&telnet_cmd::make_cmd(telnet_cmd::Nop));
+                    }
+                    Ok(telnet_cmd::SE | telnet_cmd::Nop | telnet_cmd::GA) => {
+                        self.state = ParserState::Data;
+                    }
+                    Ok(telnet_cmd::Iac) => {
+                        buf.push(0xFF);
+                        self.state = ParserState::Data;
+                    }
+                    Ok(cmd @ (telnet_cmd::Will | telnet_cmd::Wont | telnet_cmd::DO | telnet_cmd::Dont)) => {
+                        self.state = ParserState::Negotiation(cmd);
+                    }
+                    Ok(telnet_cmd::SB) => {
+                        self.sub_negotiation.clear();
+                        self.state = ParserState::SubNegotiation;
+                    }
+                    Err(err) => {
+                        log::error!("error parsing IAC: {}", err);
+                        self.state = ParserState::Data;
+                    }
+                    Ok(cmd) => {
+                        log::error!("unsupported IAC: {}", telnet_cmd::to_string(cmd));
+                        self.state = ParserState::Data;
+                    }
+                },
+                ParserState::Negotiation(cmd) => {
+                    self.state = ParserState::Data;
+                    let opt = *b;
+                    log::info!("{} {}", telnet_cmd::to_string(cmd), telnet_option::to_string(opt));
+                    let was_enabled = self.options.is_local_enabled(opt);
+                    let mut reply = Vec::new();
+                    self.options.receive(cmd, opt, &mut reply);
+                    if !was_enabled && self.options.is_local_enabled(opt) {
+                        self.local_option_enabled(opt, &mut reply);
+                    }
+                    self.reply.extend(reply);
+                }
+            }
+        }
+        buf
+    }
+}
+
+/// Returns the character set names of a CHARSET REQUEST: `[ "[TTABLE]" version ] sep name sep name…`
+fn parse_charset_request(request: &[u8]) -> Vec<String> {
+    let request = if request.starts_with(charset::TTABLE) {
+        request.get(charset::TTABLE.len() + 1..).unwrap_or_default()
+    } else {
+        request
+    };
+    let Some((sep, names)) = request.split_first() else {
+        return Vec::new();
+    };
+    names
+        .split(|b| b == sep)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+/// Splits the payload of a NEW-ENVIRON SEND into (VAR/USERVAR, name) pairs.
+fn parse_environment_request(request: &[u8]) -> Vec<(u8, String)> {
+    let mut result: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut escape = false;
+    for b in request {
+        if escape {
+            escape = false;
+        } else if *b == new_environ::ESC {
+            escape = true;
+            continue;
+        } else if *b == new_environ::VAR || *b == new_environ::USERVAR {
+            result.push((*b, Vec::new()));
+            continue;
+        }
+        if let Some((_, name)) = result.last_mut() {
+            name.push(*b);
+        }
+    }
+    result.into_iter().map(|(t, name)| (t, String::from_utf8_lossy(&name).into_owned())).collect()
+}
+
+/// Writes 'VAR/USERVAR name [VALUE value]', a missing value tells the server that the variable is undefined.
+fn write_environment_variable(out: &mut Vec<u8>, name: &str, value: Option<&str>) {
+    out.push(new_environ::get_type(name));
+    write_environment_string(out, name.as_bytes());
+    if let Some(value) = value {
+        out.push(new_environ::VALUE);
+        write_environment_string(out, value.as_bytes());
+    }
+}
+
+fn write_environment_string(out: &mut Vec<u8>, s: &[u8]) {
+    for b in s {
+        match *b {
+            new_environ::VAR | new_environ::VALUE | new_environ::ESC | new_environ::USERVAR => {
+                out.push(new_environ::ESC);
+                out.push(*b);
+            }
+            telnet_cmd::Iac => {
+                out.push(telnet_cmd::Iac);
+                out.push(telnet_cmd::Iac);
+            }
+            _ => out.push(*b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Address, Proxy};
+    use web_time::Duration;
+
+    const IAC: u8 = telnet_cmd::Iac;
+    const WILL: u8 = telnet_cmd::Will;
+    const WONT: u8 = telnet_cmd::Wont;
+    const DO: u8 = telnet_cmd::DO;
+    const DONT: u8 = telnet_cmd::Dont;
+
+    fn new_parser() -> TelnetParser {
+        new_parser_for(Address::new("test"))
+    }
+
+    fn new_parser_for(address: Address) -> TelnetParser {
+        let data = OpenConnectionData::from(&address, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        TelnetParser::new(&data)
+    }
+
+    #[test]
+    fn test_recorded_login_negotiation() {
+        let mut parser = new_parser();
+        let data = parser.parse(&[IAC, WILL, 0x01, IAC, WILL, 0x03, IAC, DO, 0x18, b'H', b'i', IAC, IAC, IAC, DO, 0x23]);
+        assert_eq!(vec![b'H', b'i', 0xFF], data);
+        assert_eq!(vec![IAC, DO, 0x01, IAC, DO, 0x03, IAC, WILL, 0x18, IAC, WONT, 0x23], parser.take_reply());
+        assert!(parser.options().is_remote_enabled(telnet_option::Echo));
+        assert!(parser.options().is_remote_enabled(telnet_option::SuppressGoAhead));
+        assert!(parser.options().is_local_enabled(telnet_option::TerminalType));
+        assert!(!parser.options().is_local_enabled(telnet_option::XDisplayLocation));
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut parser = new_parser();
+        assert_eq!(vec![b'A', IAC, IAC, b'B'], parser.encode(&[b'A', IAC, b'B']));
+        parser.set_raw_mode(true);
+        assert_eq!(vec![b'A', IAC, b'B'], parser.encode(&[b'A', IAC, b'B']));
+        assert_eq!(vec![IAC, IAC], parser.parse(&[IAC, IAC]));
+    }
+
+    #[test]
+    fn test_are_you_there() {
+        let mut parser = new_parser();
+        assert!(parser.parse(&[IAC, telnet_cmd::Ayt]).is_empty());
+        assert_eq!(vec![IAC, telnet_cmd::Nop], parser.take_reply());
+    }
+
+    #[test]
+    fn test_repeated_requests_are_not_answered() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, WILL, 0x01, IAC, DO, 0x00]);
+        assert_eq!(vec![IAC, DO, 0x01, IAC, WILL, 0x00], parser.take_reply());
+
+        parser.parse(&[IAC, WILL, 0x01, IAC, DO, 0x00, IAC, WILL, 0x01]);
+        assert!(parser.take_reply().is_empty());
+    }
+
+    #[test]
+    fn test_refused_options_stay_refused() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, WILL, 0x22, IAC, DO, 200]);
+        assert_eq!(vec![IAC, DONT, 0x22, IAC, WONT, 200], parser.take_reply());
+        assert_eq!(OptionState::No, parser.options().remote_state(0x22));
+        assert_eq!(OptionState::No, parser.options().local_state(200));
+
+        parser.parse(&[IAC, WONT, 0x22, IAC, DONT, 200]);
+        assert!(parser.take_reply().is_empty());
+    }
+
+    #[test]
+    fn test_disable_option() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, WILL, 0x01]);
+        parser.take_reply();
+
+        parser.parse(&[IAC, WONT, 0x01]);
+        assert_eq!(vec![IAC, DONT, 0x01], parser.take_reply());
+        assert!(!parser.options().is_remote_enabled(telnet_option::Echo));
+    }
+
+    #[test]
+    fn test_naws() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, DO, 0x1F]);
+        assert_eq!(
+            vec![IAC, WILL, 0x1F, IAC, telnet_cmd::SB, 0x1F, 0, 80, 0, 25, IAC, telnet_cmd::SE],
+            parser.take_reply()
+        );
+
+        parser.parse(&[IAC, DO, 0x1F]);
+        assert!(parser.take_reply().is_empty());
+    }
+
+    #[test]
+    fn test_window_size_change() {
+        let mut parser = new_parser();
+        parser.set_window_size(Size::new(132, 52));
+        assert!(parser.take_reply().is_empty());
+
+        parser.parse(&[IAC, DO, 0x1F]);
+        assert_eq!(
+            vec![IAC, WILL, 0x1F, IAC, telnet_cmd::SB, 0x1F, 0, 132, 0, 52, IAC, telnet_cmd::SE],
+            parser.take_reply()
+        );
+
+        parser.set_window_size(Size::new(80, 255));
+        assert_eq!(vec![IAC, telnet_cmd::SB, 0x1F, 0, 80, 0, 255, 255, IAC, telnet_cmd::SE], parser.take_reply());
+    }
+
+    #[test]
+    fn test_request_remote_option() {
+        let mut parser = new_parser();
+        parser.request_remote_option(telnet_option::Echo, true);
+        assert_eq!(vec![IAC, DO, 0x01], parser.take_reply());
+        assert_eq!(OptionState::WantYes, parser.options().remote_state(telnet_option::Echo));
+
+        parser.parse(&[IAC, WILL, 0x01]);
+        assert!(parser.take_reply().is_empty());
+        assert!(parser.options().is_remote_enabled(telnet_option::Echo));
+
+        parser.request_remote_option(telnet_option::Echo, false);
+        assert_eq!(vec![IAC, DONT, 0x01], parser.take_reply());
+        parser.parse(&[IAC, WONT, 0x01]);
+        assert!(parser.take_reply().is_empty());
+        assert_eq!(OptionState::No, parser.options().remote_state(telnet_option::Echo));
+    }
+
+    #[test]
+    fn test_request_queue() {
+        let mut parser = new_parser();
+        parser.request_local_option(telnet_option::TransmitBinary, true);
+        parser.request_local_option(telnet_option::TransmitBinary, false);
+        assert_eq!(vec![IAC, WILL, 0x00], parser.take_reply());
+
+        // the queued disable is sent as soon as the server agrees
+        parser.parse(&[IAC, DO, 0x00]);
+        assert_eq!(vec![IAC, WONT, 0x00], parser.take_reply());
+        assert_eq!(OptionState::WantNo, parser.options().local_state(telnet_option::TransmitBinary));
+
+        parser.parse(&[IAC, DONT, 0x00]);
+        assert!(parser.take_reply().is_empty());
+        assert_eq!(OptionState::No, parser.options().local_state(telnet_option::TransmitBinary));
+    }
+
+    const SB: u8 = telnet_cmd::SB;
+    const SE: u8 = telnet_cmd::SE;
+
+    fn new_environ_parser() -> TelnetParser {
+        let mut address = Address::new("test");
+        address.user_name = "sysop".to_string();
+        address.environment.push(("SYSTEMTYPE".to_string(), "LINUX".to_string()));
+        address.environment.push(("ICY\x01TERM".to_string(), "1".to_string()));
+        let mut parser = new_parser_for(address);
+        parser.parse(&[IAC, DO, 0x27]);
+        assert_eq!(vec![IAC, WILL, 0x27], parser.take_reply());
+        parser
+    }
+
+    #[test]
+    fn test_new_environ_send_all() {
+        let mut parser = new_environ_parser();
+        parser.parse(&[IAC, SB, 0x27, new_environ::SEND, IAC, SE]);
+        let mut expected = vec![IAC, SB, 0x27, new_environ::IS];
+        expected.push(new_environ::VAR);
+        expected.extend_from_slice(b"USER");
+        expected.push(new_environ::VALUE);
+        expected.extend_from_slice(b"sysop");
+        expected.push(new_environ::VAR);
+        expected.extend_from_slice(b"SYSTEMTYPE");
+        expected.push(new_environ::VALUE);
+        expected.extend_from_slice(b"LINUX");
+        expected.push(new_environ::USERVAR);
+        expected.extend_from_slice(&[b'I', b'C', b'Y', new_environ::ESC, 0x01, b'T', b'E', b'R', b'M']);
+        expected.push(new_environ::VALUE);
+        expected.push(b'1');
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(expected, parser.take_reply());
+    }
+
+    #[test]
+    fn test_new_environ_send_selected() {
+        let mut parser = new_environ_parser();
+        let mut request = vec![IAC, SB, 0x27, new_environ::SEND, new_environ::VAR];
+        request.extend_from_slice(b"USER");
+        request.push(new_environ::USERVAR);
+        request.extend_from_slice(b"COLUMNS");
+        request.extend_from_slice(&[IAC, SE]);
+        parser.parse(&request);
+
+        let mut expected = vec![IAC, SB, 0x27, new_environ::IS, new_environ::VAR];
+        expected.extend_from_slice(b"USER");
+        expected.push(new_environ::VALUE);
+        expected.extend_from_slice(b"sysop");
+        // undefined variables are sent without value
+        expected.push(new_environ::USERVAR);
+        expected.extend_from_slice(b"COLUMNS");
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(expected, parser.take_reply());
+    }
+
+    #[test]
+    fn test_new_environ_send_all_user_vars() {
+        let mut parser = new_environ_parser();
+        parser.parse(&[IAC, SB, 0x27, new_environ::SEND, new_environ::USERVAR, IAC, SE]);
+
+        let mut expected = vec![IAC, SB, 0x27, new_environ::IS, new_environ::USERVAR];
+        expected.extend_from_slice(&[b'I', b'C', b'Y', new_environ::ESC, 0x01, b'T', b'E', b'R', b'M']);
+        expected.push(new_environ::VALUE);
+        expected.push(b'1');
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(expected, parser.take_reply());
+    }
+
+    #[test]
+    fn test_new_environ_info() {
+        let mut parser = new_environ_parser();
+        parser.set_environment_variable("USER", "guest");
+
+        let mut expected = vec![IAC, SB, 0x27, new_environ::INFO, new_environ::VAR];
+        expected.extend_from_slice(b"USER");
+        expected.push(new_environ::VALUE);
+        expected.extend_from_slice(b"guest");
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(expected, parser.take_reply());
+    }
+
+    #[test]
+    fn test_terminal_type_in_split_packets() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, DO, 0x18, IAC, SB]);
+        assert_eq!(vec![IAC, WILL, 0x18], parser.take_reply());
+        parser.parse(&[0x18, terminal_type::SEND, IAC]);
+        assert!(parser.take_reply().is_empty());
+        parser.parse(&[SE]);
+
+        let mut expected = vec![IAC, SB, 0x18, terminal_type::IS];
+        expected.extend_from_slice(b"ANSI");
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(expected, parser.take_reply());
+    }
+
+    #[test]
+    fn test_terminal_type_cycling() {
+        let mut address = Address::new("test");
+        address.terminal_type_names = vec!["XTERM".to_string(), " ".to_string(), "VT100".to_string()];
+        let mut parser = new_parser_for(address);
+        parser.parse(&[IAC, DO, 0x18]);
+        assert_eq!(vec![IAC, WILL, 0x18], parser.take_reply());
+
+        for name in ["XTERM", "VT100", "VT100", "XTERM"] {
+            parser.parse(&[IAC, SB, 0x18, terminal_type::SEND, IAC, SE]);
+            let mut expected = vec![IAC, SB, 0x18, terminal_type::IS];
+            expected.extend_from_slice(name.as_bytes());
+            expected.extend_from_slice(&[IAC, SE]);
+            assert_eq!(expected, parser.take_reply());
+        }
+    }
+
+    fn charset_sub_negotiation(cmd: u8, payload: &[u8]) -> Vec<u8> {
+        let mut result = vec![IAC, SB, 0x2A, cmd];
+        result.extend_from_slice(payload);
+        result.extend_from_slice(&[IAC, SE]);
+        result
+    }
+
+    #[test]
+    fn test_charset_request_on_enable() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, DO, 0x2A]);
+        let mut expected = vec![IAC, WILL, 0x2A];
+        expected.extend(charset_sub_negotiation(charset::REQUEST, b";IBM437;ISO-8859-1;UTF-8"));
+        assert_eq!(expected, parser.take_reply());
+        assert_eq!(None, parser.take_character_set());
+
+        parser.parse(&charset_sub_negotiation(charset::ACCEPTED, b"UTF-8"));
+        assert!(parser.take_reply().is_empty());
+        assert_eq!(Some(CharacterSet::Utf8), parser.take_character_set());
+        assert_eq!(None, parser.take_character_set());
+    }
+
+    #[test]
+    fn test_charset_accept_preferred() {
+        let mut parser = new_parser();
+        parser.parse(&[IAC, WILL, 0x2A]);
+        assert_eq!(vec![IAC, DO, 0x2A], parser.take_reply());
+
+        parser.parse(&charset_sub_negotiation(charset::REQUEST, b" UTF-8 KOI8-R cp437"));
+        assert_eq!(charset_sub_negotiation(charset::ACCEPTED, b"cp437"), parser.take_reply());
+        assert_eq!(Some(CharacterSet::Cp437), parser.take_character_set());
+    }
+
+    #[test]
+    fn test_charset_ttable_request() {
+        let mut address = Address::new("test");
+        address.terminal_type = Terminal::PETscii;
+        let mut parser = new_parser_for(address);
+        parser.parse(&[IAC, WILL, 0x2A]);
+        assert_eq!(vec![IAC, DO, 0x2A], parser.take_reply());
+
+        parser.parse(&charset_sub_negotiation(charset::REQUEST, b"[TTABLE]\x01;UTF-8;PETSCII"));
+        assert_eq!(charset_sub_negotiation(charset::ACCEPTED, b"PETSCII"), parser.take_reply());
+        assert_eq!(Some(CharacterSet::Petscii), parser.take_character_set());
+    }
+
+    #[test]
+    fn test_charset_request_without_option() {
+        let mut parser = new_parser();
+        parser.parse(&charset_sub_negotiation(charset::REQUEST, b";UTF-8;IBM437"));
+        assert!(parser.take_reply().is_empty());
+        assert_eq!(None, parser.take_character_set());
+    }
+
+    #[test]
+    fn test_charset_reject() {
+        let mut address = Address::new("test");
+        address.terminal_type = Terminal::PETscii;
+        let mut parser = new_parser_for(address);
+        parser.parse(&[IAC, WILL, 0x2A]);
+        assert_eq!(vec![IAC, DO, 0x2A], parser.take_reply());
+
+        parser.parse(&charset_sub_negotiation(charset::REQUEST, b";UTF-8;IBM437"));
+        assert_eq!(charset_sub_negotiation(charset::REJECTED, b""), parser.take_reply());
+        assert_eq!(None, parser.take_character_set());
+    }
+}
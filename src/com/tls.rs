@@ -0,0 +1,483 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme, StreamOwned,
+};
+use sha2::{Digest, Sha256};
+
+use super::{ConnectionError, HostAddress, HostIdentity, OpenConnectionData, TermComResult, UnknownHost};
+use crate::KnownCertificates;
+
+/// Default port for implicit TLS telnet ("telnets").
+pub const TELNETS_PORT: u16 = 992;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A TCP stream that is optionally wrapped in a TLS session.
+#[derive(Debug)]
+pub enum ComStream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl ComStream {
+    /// Performs the TLS handshake on an already connected stream. The server certificate is checked against the webpki roots,
+    /// certificates that aren't signed by a known authority need to be in the known certificates store.
+    pub fn connect_tls(tcp_stream: TcpStream, address: &HostAddress, connection_data: &OpenConnectionData) -> TermComResult<Self> {
+        let verifier = TofuVerifier::new(&address.to_string(), get_webpki_roots(), KnownCertificates::load(), connection_data)?;
+        Self::connect_tls_with_verifier(tcp_stream, address, &verifier)
+    }
+
+    pub fn connect_tls_with_verifier(mut tcp_stream: TcpStream, address: &HostAddress, verifier: &Arc<TofuVerifier>) -> TermComResult<Self> {
+        let host = address.host.as_str();
+        let Ok(server_name) = ServerName::try_from(host.to_string()) else {
+            return Err(ConnectionError::InvalidAddress(format!("'{host}' (not a valid TLS server name)")));
+        };
+
+        let mut conn = ClientConnection::new(verifier.create_client_config(), server_name)?;
+
+        // do the handshake up front, otherwise certificate errors would show up as a random read error later.
+        let read_timeout = tcp_stream.read_timeout()?;
+        tcp_stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        while conn.is_handshaking() {
+            if let Err(err) = conn.complete_io(&mut tcp_stream) {
+                return verifier.check_handshake(Err(get_tls_error(host, err)));
+            }
+        }
+        verifier.check_handshake(Ok(()))?;
+        tcp_stream.set_read_timeout(read_timeout)?;
+        if let Some(protocol) = conn.protocol_version() {
+            log::info!("TLS connection to {host} established using {protocol:?}");
+        }
+        Ok(Self::Tls(Box::new(StreamOwned::new(conn, tcp_stream))))
+    }
+
+    fn get_tcp_stream(&self) -> &TcpStream {
+        match self {
+            ComStream::Plain(stream) => stream,
+            ComStream::Tls(stream) => &stream.sock,
+        }
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.get_tcp_stream().set_nonblocking(nonblocking)
+    }
+
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        if let ComStream::Tls(stream) = self {
+            stream.conn.send_close_notify();
+            // best effort - the connection gets closed anyways.
+            let _ = stream.conn.complete_io(&mut stream.sock);
+        }
+        self.get_tcp_stream().shutdown(Shutdown::Both)
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for ComStream {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.get_tcp_stream().as_raw_fd()
+    }
+}
+
+impl Read for ComStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ComStream::Plain(stream) => stream.read(buf),
+            ComStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ComStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ComStream::Plain(stream) => stream.write(buf),
+            ComStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            ComStream::Plain(stream) => stream.write_all(buf),
+            ComStream::Tls(stream) => {
+                stream.write_all(buf)?;
+                stream.flush()
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ComStream::Plain(stream) => stream.flush(),
+            ComStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+pub fn get_webpki_roots() -> RootCertStore {
+    let mut root_store = RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    root_store
+}
+
+/// Checks server certificates against the root certificates first. Certificates that fail (self-signed ones for example)
+/// are accepted if their fingerprint got trusted by the user before - like ssh does it with known_hosts.
+#[derive(Debug)]
+pub struct TofuVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    address: String,
+    known_certificates: Mutex<KnownCertificates>,
+    accept_new: bool,
+    trusted_fingerprint: Option<String>,
+    untrusted_fingerprint: Mutex<Option<String>>,
+    /// Set if the certificate check failed, other handshake errors aren't a question of trust.
+    rejected: Mutex<bool>,
+}
+
+impl TofuVerifier {
+    /// `address` is the 'host:port' the certificate is stored for. With `accept_self_signed` certificates of unknown hosts
+    /// get stored without asking, changed certificates are only accepted if the user trusted them (`trusted_fingerprint`).
+    pub fn new(address: &str, roots: RootCertStore, known_certificates: KnownCertificates, connection_data: &OpenConnectionData) -> TermComResult<Arc<Self>> {
+        Ok(Arc::new(Self {
+            inner: WebPkiServerVerifier::builder(Arc::new(roots))
+                .build()
+                .map_err(|err| ConnectionError::Tls(err.to_string()))?,
+            address: address.to_string(),
+            known_certificates: Mutex::new(known_certificates),
+            accept_new: connection_data.accept_self_signed,
+            trusted_fingerprint: connection_data.trusted_fingerprint.clone(),
+            untrusted_fingerprint: Mutex::new(None),
+            rejected: Mutex::new(false),
+        }))
+    }
+
+    pub fn create_client_config(self: &Arc<Self>) -> Arc<ClientConfig> {
+        let verifier: Arc<dyn ServerCertVerifier> = self.clone();
+        Arc::new(
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(verifier)
+                .with_no_client_auth(),
+        )
+    }
+
+    fn get_known_fingerprint(&self) -> Option<String> {
+        self.known_certificates.lock().unwrap().get(&self.address).cloned()
+    }
+
+    /// Needs to be called with the result of the handshake. Stores certificates that got accepted on first use
+    /// and turns a rejected certificate into an [`UnknownHost`] error the user can decide on, other errors are passed on.
+    pub fn check_handshake<T>(&self, result: TermComResult<T>) -> TermComResult<T> {
+        let Some(fingerprint) = self.untrusted_fingerprint.lock().unwrap().take() else {
+            return result;
+        };
+        let known_fingerprint = self.get_known_fingerprint();
+        let rejected = std::mem::take(&mut *self.rejected.lock().unwrap());
+        match result {
+            Ok(result) => {
+                if known_fingerprint.as_ref() != Some(&fingerprint) {
+                    log::info!("Trusting new certificate of {}: {fingerprint}", self.address);
+                    let mut known_certificates = self.known_certificates.lock().unwrap();
+                    known_certificates.insert(self.address.clone(), fingerprint);
+                    if let Err(err) = known_certificates.store() {
+                        log::error!("Error storing known certificates: {err}");
+                    }
+                }
+                Ok(result)
+            }
+            Err(ConnectionError::Tls(reason)) if rejected => {
+                log::warn!("Certificate of {} not trusted: {reason}", self.address);
+                Err(ConnectionError::from(UnknownHost {
+                    kind: HostIdentity::Certificate,
+                    host: self.address.clone(),
+                    reason,
+                    fingerprint,
+                    known_fingerprint,
+                }))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl ServerCertVerifier for TofuVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = get_fingerprint(end_entity);
+        let known_fingerprint = self.get_known_fingerprint();
+        if known_fingerprint.as_ref() == Some(&fingerprint) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        if self.trusted_fingerprint.as_ref() == Some(&fingerprint) {
+            *self.untrusted_fingerprint.lock().unwrap() = Some(fingerprint);
+            return Ok(ServerCertVerified::assertion());
+        }
+        match self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now) {
+            Err(rustls::Error::InvalidCertificate(err)) => {
+                *self.untrusted_fingerprint.lock().unwrap() = Some(fingerprint);
+                if self.accept_new && known_fingerprint.is_none() {
+                    return Ok(ServerCertVerified::assertion());
+                }
+                *self.rejected.lock().unwrap() = true;
+                Err(rustls::Error::InvalidCertificate(err))
+            }
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// SHA-256 fingerprint in the usual 'AB:CD:…' notation.
+pub fn get_fingerprint(cert: &CertificateDer<'_>) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+/// Turns a failed handshake into a readable error.
+fn get_tls_error(host: &str, err: io::Error) -> ConnectionError {
+    let Some(rustls_error) = err.get_ref().and_then(|e| e.downcast_ref::<rustls::Error>()) else {
+        return ConnectionError::ConnectionLost(format!("TLS handshake with {host} failed: {err}"));
+    };
+    let msg = match rustls_error {
+        rustls::Error::InvalidCertificate(cert_error) => {
+            let reason = match cert_error {
+                CertificateError::Expired => "it has expired".to_string(),
+                CertificateError::NotValidYet => "it is not valid yet".to_string(),
+                CertificateError::Revoked => "it has been revoked".to_string(),
+                CertificateError::UnknownIssuer => "it is self-signed or issued by an unknown authority".to_string(),
+                CertificateError::NotValidForName => format!("it is not valid for '{host}'"),
+                CertificateError::BadSignature => "its signature is invalid".to_string(),
+                CertificateError::BadEncoding => "it is not correctly encoded".to_string(),
+                err => format!("{err:?}"),
+            };
+            format!("The TLS certificate of {host} was rejected because {reason}.")
+        }
+        err => format!("TLS handshake with {host} failed: {err}"),
+    };
+    ConnectionError::Tls(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{addresses::Protocol, Address, Proxy};
+    use icy_engine::Size;
+    use std::{
+        net::TcpListener,
+        path::PathBuf,
+        thread::{self, JoinHandle},
+    };
+
+    use rustls::{
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+        ServerConfig, ServerConnection,
+    };
+
+    /// Starts a TLS server with a self signed certificate for `name` that echoes the first packet.
+    fn start_server(name: &str) -> (String, CertificateDer<'static>, JoinHandle<()>) {
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        let cert_der = CertificateDer::from(cert.serialize_der().unwrap());
+        let key_der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.serialize_private_key_der()));
+        let config = Arc::new(
+            ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(vec![cert_der.clone()], key_der)
+                .unwrap(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let mut stream = StreamOwned::new(ServerConnection::new(config).unwrap(), tcp);
+            let mut buf = [0; 64];
+            if let Ok(size) = stream.read(&mut buf) {
+                let _ = stream.write_all(&buf[..size]);
+                let _ = stream.flush();
+            }
+        });
+        (format!("localhost:{}", addr.port()), cert_der, handle)
+    }
+
+    fn get_store_file(name: &str) -> PathBuf {
+        let file_name = std::env::temp_dir().join(format!("icy_term_tls_{name}_{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&file_name);
+        file_name
+    }
+
+    fn connect(address: &str, roots: RootCertStore, known_certificates: KnownCertificates, accept_self_signed: bool) -> TermComResult<ComStream> {
+        connect_trusted(address, roots, known_certificates, accept_self_signed, None)
+    }
+
+    fn connect_trusted(
+        address: &str,
+        roots: RootCertStore,
+        known_certificates: KnownCertificates,
+        accept_self_signed: bool,
+        trusted_fingerprint: Option<String>,
+    ) -> TermComResult<ComStream> {
+        let mut address_data = Address::new("test");
+        address_data.address = address.to_string();
+        address_data.accept_self_signed = accept_self_signed;
+        let mut connection_data = OpenConnectionData::from(&address_data, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        connection_data.trusted_fingerprint = trusted_fingerprint;
+        let verifier = TofuVerifier::new(address, roots, known_certificates, &connection_data)?;
+        let host_address = HostAddress::parse(address, Protocol::TelnetTls)?;
+        ComStream::connect_tls_with_verifier(TcpStream::connect(address)?, &host_address, &verifier)
+    }
+
+    fn assert_echo(stream: &mut ComStream) {
+        stream.write_all(b"HELLO").unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(b"HELLO", &buf);
+        stream.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_tls_roundtrip() {
+        let (address, cert, handle) = start_server("localhost");
+        let mut roots = RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let mut stream = connect(&address, roots, KnownCertificates::default(), false).unwrap();
+        assert_echo(&mut stream);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tls_unknown_certificate() {
+        let (address, cert, _) = start_server("localhost");
+        let Err(err) = connect(&address, get_webpki_roots(), KnownCertificates::default(), false) else {
+            panic!("self signed certificate accepted");
+        };
+        let ConnectionError::UnknownHost(unknown_host) = &err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(address, unknown_host.host);
+        assert_eq!(get_fingerprint(&cert), unknown_host.fingerprint);
+        assert!(unknown_host.known_fingerprint.is_none());
+    }
+
+    #[test]
+    fn test_tls_known_certificate() {
+        let (address, cert, handle) = start_server("localhost");
+        let mut known_certificates = KnownCertificates::default();
+        known_certificates.insert(address.clone(), get_fingerprint(&cert));
+        let mut stream = connect(&address, get_webpki_roots(), known_certificates, false).unwrap();
+        assert_echo(&mut stream);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tls_accept_self_signed() {
+        let file_name = get_store_file("accept");
+        let (address, cert, handle) = start_server("localhost");
+        let mut stream = connect(&address, get_webpki_roots(), KnownCertificates::load_from(file_name.clone()), true).unwrap();
+        assert_echo(&mut stream);
+        handle.join().unwrap();
+
+        let known_certificates = KnownCertificates::load_from(file_name.clone());
+        std::fs::remove_file(file_name).unwrap();
+        assert_eq!(Some(&get_fingerprint(&cert)), known_certificates.get(&address));
+    }
+
+    #[test]
+    fn test_tls_changed_certificate() {
+        let (address, cert, _) = start_server("localhost");
+        let mut known_certificates = KnownCertificates::default();
+        known_certificates.insert(address.clone(), "AB:CD");
+        let Err(err) = connect(&address, get_webpki_roots(), known_certificates, true) else {
+            panic!("changed certificate accepted");
+        };
+        let ConnectionError::UnknownHost(unknown_host) = &err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(get_fingerprint(&cert), unknown_host.fingerprint);
+        assert_eq!(Some("AB:CD".to_string()), unknown_host.known_fingerprint);
+    }
+
+    #[test]
+    fn test_tls_trusted_by_user() {
+        let file_name = get_store_file("trusted");
+        let (address, cert, handle) = start_server("localhost");
+        let mut known_certificates = KnownCertificates::load_from(file_name.clone());
+        known_certificates.insert(address.clone(), "AB:CD");
+        let mut stream = connect_trusted(&address, get_webpki_roots(), known_certificates, false, Some(get_fingerprint(&cert))).unwrap();
+        assert_echo(&mut stream);
+        handle.join().unwrap();
+
+        let known_certificates = KnownCertificates::load_from(file_name.clone());
+        std::fs::remove_file(file_name).unwrap();
+        assert_eq!(Some(&get_fingerprint(&cert)), known_certificates.get(&address));
+    }
+
+    #[test]
+    fn test_tls_wrong_name() {
+        let (address, cert, _) = start_server("bbs.example.com");
+        let mut roots = RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let Err(err) = connect(&address, roots, KnownCertificates::default(), false) else {
+            panic!("certificate for wrong name accepted");
+        };
+        let ConnectionError::UnknownHost(unknown_host) = &err else {
+            panic!("unexpected error {err}");
+        };
+        assert!(unknown_host.reason.contains("not valid for 'localhost'"), "{}", unknown_host.reason);
+    }
+
+    #[test]
+    fn test_tls_other_handshake_errors() {
+        let mut address_data = Address::new("test");
+        address_data.address = "localhost:992".to_string();
+        let mut connection_data = OpenConnectionData::from(&address_data, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        connection_data.trusted_fingerprint = Some("AB:CD".to_string());
+        let verifier = TofuVerifier::new("localhost:992", get_webpki_roots(), KnownCertificates::default(), &connection_data).unwrap();
+
+        // the certificate got accepted, the connection broke afterwards
+        *verifier.untrusted_fingerprint.lock().unwrap() = Some("AB:CD".to_string());
+        let err = verifier
+            .check_handshake::<()>(Err(ConnectionError::Tls("bad record mac".to_string())))
+            .unwrap_err();
+        assert!(
+            matches!(&err, ConnectionError::Tls(details) if details == "bad record mac"),
+            "unexpected error {err}"
+        );
+
+        *verifier.untrusted_fingerprint.lock().unwrap() = Some("AB:CD".to_string());
+        let err = verifier
+            .check_handshake::<()>(Err(ConnectionError::ConnectionLost("reset".to_string())))
+            .unwrap_err();
+        assert!(matches!(err, ConnectionError::ConnectionLost(_)), "unexpected error {err}");
+    }
+}
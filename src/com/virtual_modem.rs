@@ -0,0 +1,534 @@
+use web_time::{Duration, Instant};
+
+use super::{ConnectionError, ResultCode};
+use crate::addresses::Protocol;
+
+/// S-register defaults of a Hayes modem after ATZ.
+const DEFAULT_REGISTERS: [(usize, u8); 13] = [
+    (0, 0),   // rings to auto answer
+    (1, 0),   // ring counter
+    (2, 43),  // escape character '+'
+    (3, 13),  // command line terminator CR
+    (4, 10),  // response formatting LF
+    (5, 8),   // backspace
+    (6, 2),   // wait for dial tone
+    (7, 50),  // wait for carrier
+    (8, 2),   // comma pause time
+    (9, 6),   // carrier detect response time
+    (10, 14), // carrier loss disconnect time
+    (11, 95), // DTMF tone duration
+    (12, 50), // escape guard time in 1/50 s
+];
+
+/// What the connection has to do after the modem processed the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModemAction {
+    /// Connect to the address, the outcome is reported back with [`VirtualModem::dial_result`].
+    Dial(String),
+    HangUp,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ModemOutput {
+    /// Echo, result codes and information text for the terminal
+    pub terminal: Vec<u8>,
+    /// Data for the remote side while online
+    pub remote: Vec<u8>,
+    pub action: Option<ModemAction>,
+}
+
+/// Interprets the Hayes AT command set locally like a WiFi modem does,
+/// a dial command opens a network connection instead of calling a phone number.
+pub struct VirtualModem {
+    registers: [u8; 256],
+    echo: bool,
+    verbose: bool,
+    quiet: bool,
+    /// A connection is established
+    carrier: bool,
+    /// Input goes to the remote side, otherwise it's interpreted as commands
+    data_mode: bool,
+    line: Vec<u8>,
+    last_command: String,
+    last_input: Option<Instant>,
+    escape_count: usize,
+    /// Time the escape sequence got completed, command mode starts after the guard time
+    escape_time: Option<Instant>,
+}
+
+impl Default for VirtualModem {
+    fn default() -> Self {
+        let mut modem = Self {
+            registers: [0; 256],
+            echo: true,
+            verbose: true,
+            quiet: false,
+            carrier: false,
+            data_mode: false,
+            line: Vec::new(),
+            last_command: String::new(),
+            last_input: None,
+            escape_count: 0,
+            escape_time: None,
+        };
+        modem.reset();
+        modem
+    }
+}
+
+impl VirtualModem {
+    /// True while data is exchanged with the remote side.
+    pub fn is_online(&self) -> bool {
+        self.data_mode
+    }
+
+    /// Processes the keyboard input of the terminal.
+    pub fn input(&mut self, data: &[u8], now: Instant) -> ModemOutput {
+        let mut output = ModemOutput::default();
+        for &b in data {
+            if self.data_mode {
+                self.detect_escape(b, now);
+                output.remote.push(b);
+            } else {
+                self.command_input(b, &mut output);
+            }
+            self.last_input = Some(now);
+            if output.action.is_some() {
+                // the rest is dropped, a real modem would abort dialing on it. Dialing blocks the com thread and
+                // can't be canceled, keys typed while it runs arrive at the remote side once connected.
+                break;
+            }
+        }
+        output
+    }
+
+    /// Switches to command mode once the guard time after a '+++' escape passed.
+    pub fn poll(&mut self, now: Instant) -> ModemOutput {
+        let mut output = ModemOutput::default();
+        if let Some(escape_time) = self.escape_time {
+            if now.duration_since(escape_time) >= self.guard_time() {
+                self.escape_count = 0;
+                self.escape_time = None;
+                self.data_mode = false;
+                self.result(ResultCode::Ok, &mut output.terminal);
+            }
+        }
+        output
+    }
+
+    /// When [`VirtualModem::poll`] needs to be called next, `None` while no escape is pending.
+    pub fn next_poll(&self) -> Option<Instant> {
+        self.escape_time.map(|escape_time| escape_time + self.guard_time())
+    }
+
+    /// Reports the outcome of a [`ModemAction::Dial`], returns the output for the terminal.
+    pub fn dial_result(&mut self, result: ResultCode) -> Vec<u8> {
+        let connected = matches!(result, ResultCode::Connect(_));
+        self.carrier = connected;
+        self.data_mode = connected;
+        self.last_input = None;
+        let mut terminal = Vec::new();
+        self.result(result, &mut terminal);
+        terminal
+    }
+
+    /// The remote side closed the connection, returns the output for the terminal.
+    pub fn carrier_lost(&mut self) -> Vec<u8> {
+        let mut terminal = Vec::new();
+        if self.carrier {
+            self.carrier = false;
+            self.data_mode = false;
+            self.escape_count = 0;
+            self.escape_time = None;
+            self.result(ResultCode::NoCarrier, &mut terminal);
+        }
+        terminal
+    }
+
+    fn reset(&mut self) {
+        self.registers = [0; 256];
+        for (register, value) in DEFAULT_REGISTERS {
+            self.registers[register] = value;
+        }
+        self.echo = true;
+        self.verbose = true;
+        self.quiet = false;
+    }
+
+    fn guard_time(&self) -> Duration {
+        Duration::from_millis(self.registers[12] as u64 * 20)
+    }
+
+    /// The escape sequence is three escape characters with the guard time of silence before and after them.
+    fn detect_escape(&mut self, b: u8, now: Instant) {
+        let escape_char = self.registers[2];
+        // S2 > 127 disables the escape sequence
+        if escape_char <= 127 && b == escape_char && self.escape_count < 3 {
+            let silent = self.last_input.is_none_or(|last_input| now.duration_since(last_input) >= self.guard_time());
+            if self.escape_count > 0 || silent {
+                self.escape_count += 1;
+                if self.escape_count == 3 {
+                    self.escape_time = Some(now);
+                }
+                return;
+            }
+        }
+        self.escape_count = 0;
+        self.escape_time = None;
+    }
+
+    fn command_input(&mut self, b: u8, output: &mut ModemOutput) {
+        if b == self.registers[3] {
+            if self.echo {
+                output.terminal.push(b);
+            }
+            let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).to_string();
+            self.execute(&line, output);
+        } else if b == self.registers[5] || b == 0x7F {
+            if self.line.pop().is_some() && self.echo {
+                output.terminal.extend_from_slice(b"\x08 \x08");
+            }
+        } else if b != self.registers[4] {
+            if self.echo {
+                output.terminal.push(b);
+            }
+            self.line.push(b);
+            // 'A/' repeats the last command without a terminator
+            if self.line.eq_ignore_ascii_case(b"A/") {
+                self.line.clear();
+                if self.echo {
+                    output.terminal.push(self.registers[3]);
+                }
+                let command = self.last_command.clone();
+                self.execute(&command, output);
+            }
+        }
+    }
+
+    fn execute(&mut self, line: &str, output: &mut ModemOutput) {
+        let line = line.trim();
+        if !line.get(..2).is_some_and(|at| at.eq_ignore_ascii_case("AT")) {
+            return;
+        }
+        self.last_command = line.to_string();
+        if let Some(result) = self.execute_commands(&line[2..], output) {
+            self.result(result, &mut output.terminal);
+        }
+    }
+
+    /// Returns the final result code, `None` if it's reported later like for dialing.
+    fn execute_commands(&mut self, commands: &str, output: &mut ModemOutput) -> Option<ResultCode> {
+        let mut chars = commands.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c.to_ascii_uppercase() {
+                ' ' => {}
+                'D' => {
+                    let number: String = chars.collect();
+                    let number = strip_dial_modifier(&number);
+                    if self.carrier || number.is_empty() {
+                        return Some(ResultCode::Error);
+                    }
+                    output.action = Some(ModemAction::Dial(number.to_string()));
+                    return None;
+                }
+                'H' => {
+                    if parse_number(&mut chars).unwrap_or(0) > 1 {
+                        return Some(ResultCode::Error);
+                    }
+                    if self.carrier {
+                        self.carrier = false;
+                        output.action = Some(ModemAction::HangUp);
+                    }
+                }
+                'O' => {
+                    parse_number(&mut chars);
+                    if !self.carrier {
+                        return Some(ResultCode::NoCarrier);
+                    }
+                    self.data_mode = true;
+                    return Some(ResultCode::Connect(None));
+                }
+                'Z' => {
+                    parse_number(&mut chars);
+                    self.reset();
+                }
+                'E' => match parse_number(&mut chars).unwrap_or(0) {
+                    0 => self.echo = false,
+                    1 => self.echo = true,
+                    _ => return Some(ResultCode::Error),
+                },
+                'V' => match parse_number(&mut chars).unwrap_or(0) {
+                    0 => self.verbose = false,
+                    1 => self.verbose = true,
+                    _ => return Some(ResultCode::Error),
+                },
+                'Q' => match parse_number(&mut chars).unwrap_or(0) {
+                    0 => self.quiet = false,
+                    1 => self.quiet = true,
+                    _ => return Some(ResultCode::Error),
+                },
+                'I' => {
+                    parse_number(&mut chars);
+                    self.text(&format!("iCY TERM virtual modem {}", env!("CARGO_PKG_VERSION")), &mut output.terminal);
+                }
+                'S' => {
+                    let Some(register) = parse_number(&mut chars).filter(|r| *r < 256) else {
+                        return Some(ResultCode::Error);
+                    };
+                    match chars.next() {
+                        Some('=') => match parse_number(&mut chars).unwrap_or(0) {
+                            value @ 0..=255 => self.registers[register as usize] = value as u8,
+                            _ => return Some(ResultCode::Error),
+                        },
+                        Some('?') => {
+                            self.text(&format!("{:03}", self.registers[register as usize]), &mut output.terminal);
+                        }
+                        _ => return Some(ResultCode::Error),
+                    }
+                }
+                '&' => match chars.next().map(|c| c.to_ascii_uppercase()) {
+                    Some('F') => {
+                        parse_number(&mut chars);
+                        self.reset();
+                    }
+                    // carrier detect, DTR, flow control and storing the profile don't apply
+                    Some('C' | 'D' | 'K' | 'S' | 'W') => {
+                        parse_number(&mut chars);
+                    }
+                    _ => return Some(ResultCode::Error),
+                },
+                // speaker, result code sets and other settings without a meaning for a network connection
+                'B' | 'C' | 'L' | 'M' | 'N' | 'P' | 'T' | 'W' | 'X' | 'Y' => {
+                    parse_number(&mut chars);
+                }
+                _ => return Some(ResultCode::Error),
+            }
+        }
+        Some(ResultCode::Ok)
+    }
+
+    fn result(&self, result: ResultCode, terminal: &mut Vec<u8>) {
+        if self.quiet {
+            return;
+        }
+        if self.verbose {
+            terminal.extend_from_slice(format!("\r\n{result}\r\n").as_bytes());
+        } else {
+            terminal.extend_from_slice(format!("{}\r", result.numeric()).as_bytes());
+        }
+    }
+
+    fn text(&self, text: &str, terminal: &mut Vec<u8>) {
+        if self.verbose {
+            terminal.extend_from_slice(b"\r\n");
+        }
+        terminal.extend_from_slice(text.as_bytes());
+        terminal.extend_from_slice(b"\r\n");
+    }
+}
+
+fn parse_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<u32> {
+    let mut number: Option<u32> = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        chars.next();
+        number = Some(number.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    }
+    number
+}
+
+fn url_scheme(address: &str) -> Option<&str> {
+    address
+        .split_once("://")
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Removes the tone/pulse modifier of 'ATDT host', an URL like 'telnet://host' starts with a T too.
+fn strip_dial_modifier(number: &str) -> &str {
+    let number = number.trim();
+    if url_scheme(number).is_some() {
+        return number;
+    }
+    match number.chars().next() {
+        Some('T' | 't' | 'P' | 'p') => number[1..].trim(),
+        _ => number,
+    }
+}
+
+/// The protocol a dialed address is connected with, telnet unless the URL scheme says otherwise.
+pub fn dial_protocol(address: &str) -> Protocol {
+    match url_scheme(address).map(str::to_ascii_lowercase).as_deref() {
+        Some("telnets") => Protocol::TelnetTls,
+        Some("ssh") => Protocol::Ssh,
+        Some("rlogin") => Protocol::Rlogin,
+        Some("raw") => Protocol::Raw,
+        Some("ws") => Protocol::WebSocket(false),
+        Some("wss") => Protocol::WebSocket(true),
+        _ => Protocol::Telnet,
+    }
+}
+
+/// Maps a failed connect to the result code a modem would report.
+pub fn dial_result_for_error(err: &ConnectionError) -> ResultCode {
+    match err {
+        ConnectionError::ConnectionRefused(_) => ResultCode::Busy,
+        ConnectionError::TimedOut(_) => ResultCode::NoAnswer,
+        _ => ResultCode::NoCarrier,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(modem: &mut VirtualModem, input: &str) -> ModemOutput {
+        modem.input(input.as_bytes(), Instant::now())
+    }
+
+    fn terminal(output: &ModemOutput) -> String {
+        String::from_utf8_lossy(&output.terminal).to_string()
+    }
+
+    fn online_modem() -> VirtualModem {
+        let mut modem = VirtualModem::default();
+        command(&mut modem, "ATE0\r");
+        assert_eq!(
+            Some(ModemAction::Dial("bbs.example.org:23".to_string())),
+            command(&mut modem, "ATDT bbs.example.org:23\r").action
+        );
+        assert_eq!(b"\r\nCONNECT 38400\r\n".to_vec(), modem.dial_result(ResultCode::Connect(Some(38400))));
+        assert!(modem.is_online());
+        modem
+    }
+
+    #[test]
+    fn test_echo_and_result_codes() {
+        let mut modem = VirtualModem::default();
+        assert_eq!("AT\r\r\nOK\r\n", terminal(&command(&mut modem, "AT\r")));
+        assert_eq!("ate0\r\r\nOK\r\n", terminal(&command(&mut modem, "ate0\r")));
+        assert_eq!("\r\nOK\r\n", terminal(&command(&mut modem, "ATV1\r")));
+        assert_eq!("0\r", terminal(&command(&mut modem, "ATV0\r")));
+        assert_eq!("4\r", terminal(&command(&mut modem, "ATJ\r")));
+        assert_eq!("", terminal(&command(&mut modem, "ATQ1\r")));
+        assert_eq!("\r\nOK\r\n", terminal(&command(&mut modem, "ATZ\r")).trim_start_matches("ATZ\r"));
+        // lines without AT are ignored
+        assert_eq!("hello\r", terminal(&command(&mut modem, "hello\r")));
+    }
+
+    #[test]
+    fn test_line_editing() {
+        let mut modem = VirtualModem::default();
+        assert_eq!("ATX\x08 \x08I\r", terminal(&command(&mut modem, "ATX\x08I\r")).split("\r\n").next().unwrap());
+        command(&mut modem, "ATE0\r");
+        assert_eq!(String::new(), terminal(&command(&mut modem, "ATS7?\r")).split("\r\n").next().unwrap());
+        assert_eq!("\r\n050\r\n\r\nOK\r\n", terminal(&command(&mut modem, "A/")));
+    }
+
+    #[test]
+    fn test_s_registers() {
+        let mut modem = VirtualModem::default();
+        command(&mut modem, "ATE0\r");
+        assert_eq!("\r\n043\r\n\r\nOK\r\n", terminal(&command(&mut modem, "ATS2?\r")));
+        assert_eq!("\r\nOK\r\n", terminal(&command(&mut modem, "ATS12=25 S7=30\r")));
+        assert_eq!("\r\n025\r\n\r\nOK\r\n", terminal(&command(&mut modem, "ATS12?\r")));
+        assert_eq!("\r\nERROR\r\n", terminal(&command(&mut modem, "ATS7=300\r")));
+        assert_eq!("\r\nERROR\r\n", terminal(&command(&mut modem, "ATS300?\r")));
+        command(&mut modem, "ATZ\r");
+        assert_eq!(
+            "\r\n050\r\n\r\nOK\r\n",
+            terminal(&command(&mut modem, "ATS12?\r")).trim_start_matches("ATS12?\r")
+        );
+    }
+
+    #[test]
+    fn test_info() {
+        let mut modem = VirtualModem::default();
+        command(&mut modem, "ATE0\r");
+        assert!(terminal(&command(&mut modem, "ATI\r")).contains("virtual modem"));
+    }
+
+    #[test]
+    fn test_dial() {
+        let mut modem = VirtualModem::default();
+        command(&mut modem, "ATE0\r");
+        assert_eq!("\r\nERROR\r\n", terminal(&command(&mut modem, "ATDT\r")));
+        assert_eq!(
+            Some(ModemAction::Dial("telnet://bbs.example.org".to_string())),
+            command(&mut modem, "ATD telnet://bbs.example.org\r").action
+        );
+        assert_eq!(b"\r\nBUSY\r\n".to_vec(), modem.dial_result(ResultCode::Busy));
+        assert!(!modem.is_online());
+
+        let mut modem = online_modem();
+        let output = command(&mut modem, "ATH\r");
+        assert_eq!(b"ATH\r".to_vec(), output.remote, "online input goes to the remote side");
+        assert_eq!(None, output.action);
+    }
+
+    #[test]
+    fn test_escape_sequence() {
+        let mut modem = online_modem();
+        let start = Instant::now();
+        modem.input(b"data", start);
+        let escape = start + Duration::from_secs(2);
+        assert_eq!(b"+++".to_vec(), modem.input(b"+++", escape).remote);
+        assert_eq!(Some(escape + Duration::from_secs(1)), modem.next_poll());
+        assert_eq!(ModemOutput::default(), modem.poll(escape + Duration::from_millis(500)));
+        assert_eq!(b"\r\nOK\r\n".to_vec(), modem.poll(escape + Duration::from_secs(1)).terminal);
+        assert!(!modem.is_online());
+        assert_eq!(None, modem.next_poll());
+
+        assert_eq!("\r\nCONNECT\r\n", terminal(&command(&mut modem, "ATO\r")));
+        assert!(modem.is_online());
+
+        modem.input(b"+++", escape + Duration::from_secs(5));
+        modem.poll(escape + Duration::from_secs(7));
+        let output = command(&mut modem, "ATH0\r");
+        assert_eq!(Some(ModemAction::HangUp), output.action);
+        assert_eq!("\r\nOK\r\n", terminal(&output));
+        assert_eq!("\r\nNO CARRIER\r\n", terminal(&command(&mut modem, "ATO\r")));
+    }
+
+    #[test]
+    fn test_escape_needs_guard_time() {
+        let mut modem = online_modem();
+        let start = Instant::now();
+        modem.input(b"a+++", start);
+        assert_eq!(ModemOutput::default(), modem.poll(start + Duration::from_secs(2)));
+
+        // data after the escape characters cancels the escape
+        let later = start + Duration::from_secs(5);
+        modem.input(b"+++", later);
+        modem.input(b"x", later + Duration::from_millis(500));
+        assert_eq!(ModemOutput::default(), modem.poll(later + Duration::from_secs(3)));
+        assert!(modem.is_online());
+    }
+
+    #[test]
+    fn test_carrier_lost() {
+        let mut modem = online_modem();
+        assert_eq!(b"\r\nNO CARRIER\r\n".to_vec(), modem.carrier_lost());
+        assert!(!modem.is_online());
+        assert!(modem.carrier_lost().is_empty());
+    }
+
+    #[test]
+    fn test_dial_protocol() {
+        assert_eq!(Protocol::Telnet, dial_protocol("bbs.example.org:23"));
+        assert_eq!(Protocol::Ssh, dial_protocol("ssh://bbs.example.org"));
+        assert_eq!(Protocol::WebSocket(true), dial_protocol("WSS://bbs.example.org/ws"));
+        assert_eq!("telnet://bbs", strip_dial_modifier(" telnet://bbs"));
+        assert_eq!("bbs.example.org", strip_dial_modifier("T bbs.example.org"));
+        assert_eq!("5551234", strip_dial_modifier("p5551234"));
+    }
+
+    #[test]
+    fn test_dial_result_for_error() {
+        let refused = ConnectionError::ConnectionRefused("refused".to_string());
+        assert_eq!(ResultCode::Busy, dial_result_for_error(&refused));
+        let timeout = ConnectionError::TimedOut("timeout".to_string());
+        assert_eq!(ResultCode::NoAnswer, dial_result_for_error(&timeout));
+        let not_found = ConnectionError::HostNotFound("bbs.example.org".to_string());
+        assert_eq!(ResultCode::NoCarrier, dial_result_for_error(&not_found));
+    }
+}
@@ -1,57 +1,66 @@
-use crate::addresses;
-use std::sync::Arc;
+use crate::{
+    addresses::{self, CharacterSet},
+    KnownCertificates,
+};
 
-use super::{Com, OpenConnectionData, TermComResult};
+use super::{connect_tcp, get_webpki_roots, Com, ConnectionError, HostAddress, OpenConnectionData, TelnetParser, TermComResult, TofuVerifier};
+use icy_engine::Size;
 
-use http::Uri;
-use rustls::RootCertStore;
-use std::io::ErrorKind;
+use http::{
+    header::{HeaderName, HeaderValue, SEC_WEBSOCKET_PROTOCOL},
+    Uri,
+};
 use std::net::TcpStream;
-use tungstenite::{client::IntoClientRequest, stream::MaybeTlsStream, Error, Message, WebSocket};
-/*
-struct NoCertVerifier {}
-
-impl ServerCertVerifier for NoCertVerifier {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &CertificateDer<'_>,
-        _intermediates: &[CertificateDer<'_>],
-        _server_name: &ServerName,
-        _scts: &mut dyn Iterator<Item = &[u8]>,
-        _ocsp_response: &[u8],
-        _now: std::time::SystemTime,
-    ) -> Result<ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
-    }
-}*/
+use tungstenite::{client::IntoClientRequest, stream::MaybeTlsStream, Error, HandshakeError, Message, WebSocket};
+
 pub struct WebSocketComImpl {
     socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    /// Set if the frames carry a telnet stream
+    telnet: Option<TelnetParser>,
+    /// The server sends text frames, data is sent back the same way if it's valid UTF-8
+    text_frames: bool,
 }
 
 impl WebSocketComImpl {
     pub fn connect(connection_data: &OpenConnectionData) -> TermComResult<Self> {
-        let is_secure = connection_data.protocol == addresses::Protocol::WebSocket(true);
+        let is_secure = Self::is_secure(&connection_data.address, connection_data.protocol);
 
         // build an ws:// or wss:// address
-        //  :TODO: default port if not supplied in address
-        let url = format!("{}://{}", Self::schema_prefix(is_secure), connection_data.address);
-
-        let req = Uri::try_from(url)?.into_client_request()?;
-
-        let mut root_store: RootCertStore = RootCertStore::empty();
-
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let addr = HostAddress::parse(&connection_data.address, connection_data.protocol)?;
+        let path = if addr.path.starts_with('?') {
+            format!("/{}", addr.path)
+        } else {
+            addr.path.clone()
+        };
+        let url = format!("{}://{addr}{path}", Self::schema_prefix(is_secure));
 
-        let config = rustls::ClientConfig::builder().with_root_certificates(root_store).with_no_client_auth();
+        let mut req = Uri::try_from(&url)
+            .map_err(|err| ConnectionError::InvalidAddress(format!("'{url}' ({err})")))?
+            .into_client_request()?;
+        if !connection_data.websocket_protocols.is_empty() {
+            let protocols = connection_data.websocket_protocols.join(", ");
+            let value = HeaderValue::from_str(&protocols).map_err(|err| header_error(SEC_WEBSOCKET_PROTOCOL.as_str(), err))?;
+            req.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+        }
+        for (name, value) in &connection_data.websocket_headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|err| header_error(name, err))?;
+            let header_value = HeaderValue::from_str(value).map_err(|err| header_error(name, err))?;
+            req.headers_mut().append(header_name, header_value);
+        }
 
-        // enable this line to test non-secure (ie: invalid certs) wss:// -- we could make this an option in the UI
-        //config.dangerous().set_certificate_verifier(Arc::new(NoCertVerifier{}));
+        let verifier = TofuVerifier::new(&addr.to_string(), get_webpki_roots(), KnownCertificates::load(), connection_data)?;
 
-        let config = Arc::new(config);
+        let stream = connect_tcp(&addr, connection_data)?;
+        let connector: tungstenite::Connector = tungstenite::Connector::Rustls(verifier.create_client_config());
+        let handshake = tungstenite::client_tls_with_config(req, stream, None, Some(connector)).map_err(|err| match err {
+            HandshakeError::Failure(err) => ConnectionError::from(err),
+            HandshakeError::Interrupted(_) => ConnectionError::TimedOut(format!("WebSocket handshake with {addr}")),
+        });
+        let (mut socket, response) = verifier.check_handshake(handshake)?;
 
-        let stream = TcpStream::connect(connection_data.address.clone())?;
-        let connector: tungstenite::Connector = tungstenite::Connector::Rustls(config);
-        let (mut socket, _) = tungstenite::client_tls_with_config(req, stream, None, Some(connector))?;
+        if let Some(protocol) = response.headers().get(SEC_WEBSOCKET_PROTOCOL) {
+            log::info!("WebSocket subprotocol: {protocol:?}");
+        }
 
         let s = socket.get_mut();
         match s {
@@ -64,7 +73,41 @@ impl WebSocketComImpl {
             _ => (),
         }
 
-        Ok(Self { socket })
+        Ok(Self {
+            socket,
+            telnet: connection_data.websocket_telnet.then(|| TelnetParser::new(connection_data)),
+            text_frames: false,
+        })
+    }
+
+    /// A 'ws://' or 'wss://' URL decides over the protocol of the address.
+    fn is_secure(address: &str, protocol: addresses::Protocol) -> bool {
+        match address.trim().split_once("://") {
+            Some((scheme, _)) if scheme.eq_ignore_ascii_case("wss") => true,
+            Some((scheme, _)) if scheme.eq_ignore_ascii_case("ws") => false,
+            _ => protocol == addresses::Protocol::WebSocket(true),
+        }
+    }
+
+    fn received(&mut self, data: Vec<u8>) -> TermComResult<Option<Vec<u8>>> {
+        let Some(telnet) = &mut self.telnet else {
+            return Ok(Some(data));
+        };
+        let data = telnet.parse(&data);
+        self.send_telnet_reply()?;
+        Ok(Some(data))
+    }
+
+    /// Sends the negotiation replies of the telnet parser.
+    fn send_telnet_reply(&mut self) -> TermComResult<()> {
+        let Some(telnet) = &mut self.telnet else {
+            return Ok(());
+        };
+        let reply = telnet.take_reply();
+        if !reply.is_empty() {
+            self.socket.send(Message::binary(reply))?;
+        }
+        Ok(())
     }
 
     fn schema_prefix(is_secure: bool) -> &'static str {
@@ -76,6 +119,10 @@ impl WebSocketComImpl {
     }
 }
 
+fn header_error(name: &str, err: impl std::fmt::Display) -> ConnectionError {
+    ConnectionError::InvalidAddress(format!("HTTP header '{name}' ({err})"))
+}
+
 impl Com for WebSocketComImpl {
     fn get_name(&self) -> &'static str {
         "WebSocket"
@@ -85,17 +132,65 @@ impl Com for WebSocketComImpl {
         443 // generally secure by default
     }
 
-    fn set_terminal_type(&mut self, _terminal: crate::addresses::Terminal) {}
+    fn set_terminal_type(&mut self, terminal: crate::addresses::Terminal) {
+        if let Some(telnet) = &mut self.telnet {
+            telnet.set_terminal_type(terminal);
+        }
+    }
+
+    fn set_raw_mode(&mut self, raw_transfer: bool) {
+        if let Some(telnet) = &mut self.telnet {
+            telnet.set_raw_mode(raw_transfer);
+        }
+    }
+
+    fn take_character_set(&mut self) -> Option<CharacterSet> {
+        self.telnet.as_mut().and_then(TelnetParser::take_character_set)
+    }
+
+    fn remote_option_enabled(&self, opt: u8) -> Option<bool> {
+        self.telnet.as_ref().map(|telnet| telnet.options().is_remote_enabled(opt))
+    }
+
+    fn set_window_size(&mut self, window_size: Size) -> TermComResult<()> {
+        if let Some(telnet) = &mut self.telnet {
+            telnet.set_window_size(window_size);
+        }
+        self.send_telnet_reply()
+    }
 
     fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
-        match self.socket.read() {
-            Ok(msg) => Ok(Some(msg.into_data())),
-            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
-            Err(e) => Err(Box::new(std::io::Error::new(ErrorKind::ConnectionAborted, format!("Connection aborted: {e}")))),
+        loop {
+            match self.socket.read() {
+                Ok(Message::Binary(data)) => return self.received(data),
+                Ok(Message::Text(text)) => {
+                    self.text_frames = true;
+                    return self.received(text.into_bytes());
+                }
+                Ok(Message::Close(frame)) => {
+                    let reason = frame.map(|frame| frame.reason.to_string()).filter(|reason| !reason.is_empty());
+                    return Err(ConnectionError::ClosedByRemote(reason));
+                }
+                // pings are answered by tungstenite on the next read or write, frames after them may be buffered already
+                Ok(_) => {}
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
         }
     }
     fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
-        let msg = Message::binary(buf);
+        let data = match &self.telnet {
+            Some(telnet) => telnet.encode(buf),
+            None => buf.to_vec(),
+        };
+        let msg = if self.text_frames {
+            match String::from_utf8(data) {
+                Ok(text) => Message::text(text),
+                Err(err) => Message::binary(err.into_bytes()),
+            }
+        } else {
+            Message::binary(data)
+        };
         self.socket.send(msg)?; // write + flush
         Ok(buf.len())
     }
@@ -103,4 +198,126 @@ impl Com for WebSocketComImpl {
     fn disconnect(&mut self) -> TermComResult<()> {
         Ok(self.socket.close(None)?)
     }
+
+    #[cfg(unix)]
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        use std::os::fd::AsRawFd;
+        match self.socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => Some(stream.as_raw_fd()),
+            MaybeTlsStream::Rustls(stream) => Some(stream.sock.as_raw_fd()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{addresses::Protocol, Address, Proxy};
+    use std::{net::TcpListener, time::Duration};
+    use tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        protocol::{frame::coding::CloseCode, CloseFrame},
+    };
+
+    fn connect(address: &Address) -> WebSocketComImpl {
+        let connection_data = OpenConnectionData::from(address, Duration::from_secs(1), Size::new(80, 25), None, &Proxy::default());
+        WebSocketComImpl::connect(&connection_data).unwrap()
+    }
+
+    fn read(com: &mut WebSocketComImpl) -> TermComResult<Vec<u8>> {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            assert!(std::time::Instant::now() < deadline, "no data received");
+            if let Some(data) = com.read_data()? {
+                return Ok(data);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // the error type is given by tungstenite
+    #[allow(clippy::result_large_err)]
+    fn check_request(request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+        assert_eq!("/ws", request.uri().path());
+        assert_eq!(Some("node=1"), request.uri().query());
+        assert_eq!("telnet, binary", request.headers()[SEC_WEBSOCKET_PROTOCOL]);
+        assert_eq!("https://bbs.example.com", request.headers()["Origin"]);
+        response.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("binary"));
+        Ok(response)
+    }
+
+    #[test]
+    fn test_url_protocols_and_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept_hdr(stream, check_request).unwrap();
+            socket.send(Message::text("Hello ")).unwrap();
+            socket.send(Message::Ping(vec![1, 2, 3])).unwrap();
+            socket.send(Message::binary(b"World".to_vec())).unwrap();
+            loop {
+                match socket.read().unwrap() {
+                    Message::Pong(data) => assert_eq!(vec![1, 2, 3], data),
+                    msg => {
+                        assert_eq!(Message::text("ok"), msg);
+                        break;
+                    }
+                }
+            }
+            socket
+                .close(Some(CloseFrame {
+                    code: CloseCode::Normal,
+                    reason: "bye".into(),
+                }))
+                .unwrap();
+        });
+
+        let mut address = Address::new("test");
+        // the URL scheme overrides the secure protocol
+        address.address = format!("ws://127.0.0.1:{port}/ws?node=1");
+        address.protocol = Protocol::WebSocket(true);
+        address.websocket_protocols = vec!["telnet".to_string(), " binary".to_string()];
+        address.websocket_headers = vec![("Origin".to_string(), "https://bbs.example.com".to_string())];
+        let mut com = connect(&address);
+
+        let mut received = Vec::new();
+        while received.len() < 11 {
+            received.extend(read(&mut com).unwrap());
+        }
+        assert_eq!(b"Hello World".to_vec(), received);
+        com.send(b"ok").unwrap();
+
+        let err = loop {
+            match read(&mut com) {
+                Ok(_) => {}
+                Err(err) => break err,
+            }
+        };
+        assert!(err.to_string().contains("bye"), "{err}");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_telnet_framing() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            socket.send(Message::binary(b"Hi\xFF\xFF!".to_vec())).unwrap();
+            assert_eq!(vec![b'A', 0xFF, 0xFF], socket.read().unwrap().into_data());
+        });
+
+        let mut address = Address::new("test");
+        address.address = format!("127.0.0.1:{port}");
+        address.protocol = Protocol::WebSocket(false);
+        address.websocket_telnet = true;
+        let mut com = connect(&address);
+
+        assert_eq!(b"Hi\xFF!".to_vec(), read(&mut com).unwrap());
+        com.send(&[b'A', 0xFF]).unwrap();
+        server.join().unwrap();
+    }
 }
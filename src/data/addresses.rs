@@ -1,5 +1,5 @@
 use crate::ui::screen_modes::ScreenMode;
-use crate::TerminalResult;
+use crate::{Proxy, TerminalResult};
 use chrono::{Duration, Utc};
 use icy_engine::ansi::{BaudEmulation, MusicOption};
 use icy_engine::igs::CommandExecutor;
@@ -85,6 +85,22 @@ impl Terminal {
             Terminal::Mode7 => Box::<mode7::CharConverter>::default(),
         }
     }
+
+    /// Terminal type names sent to telnet TTYPE requests if the address doesn't define its own list.
+    #[must_use]
+    pub fn get_default_type_names(&self) -> &'static [&'static str] {
+        match self {
+            Terminal::Ansi => &["ANSI", "VT100"],
+            Terminal::Avatar => &["AVATAR", "ANSI"],
+            Terminal::Ascii => &["RAW", "DUMB"],
+            Terminal::PETscii => &["PETSCII"],
+            Terminal::ATAscii => &["ATASCII"],
+            Terminal::ViewData => &["VIEWDATA"],
+            Terminal::Mode7 => &["MODE7"],
+            Terminal::Rip => &["RIP", "ANSI"],
+            Terminal::IGS => &["IGS"],
+        }
+    }
 }
 
 impl Display for Terminal {
@@ -103,20 +119,90 @@ impl Display for Terminal {
     }
 }
 
+/// Character sets that can be agreed on with the telnet CHARSET option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterSet {
+    Cp437,
+    Petscii,
+    Latin1,
+    Utf8,
+}
+
+impl CharacterSet {
+    /// Returns the character sets that can be used with a terminal type, the preferred one first.
+    #[must_use]
+    pub fn get_supported(terminal: Terminal) -> &'static [CharacterSet] {
+        match terminal {
+            Terminal::Ansi | Terminal::Avatar | Terminal::Ascii | Terminal::Rip | Terminal::IGS => {
+                &[CharacterSet::Cp437, CharacterSet::Latin1, CharacterSet::Utf8]
+            }
+            Terminal::PETscii => &[CharacterSet::Petscii],
+            Terminal::ATAscii | Terminal::ViewData | Terminal::Mode7 => &[],
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<CharacterSet> {
+        match name.to_uppercase().as_str() {
+            "IBM437" | "CP437" | "437" | "IBMPC" => Some(CharacterSet::Cp437),
+            "PETSCII" => Some(CharacterSet::Petscii),
+            "ISO-8859-1" | "ISO_8859-1" | "ISO8859-1" | "LATIN1" => Some(CharacterSet::Latin1),
+            "UTF-8" | "UTF8" => Some(CharacterSet::Utf8),
+            _ => None,
+        }
+    }
+
+    /// IANA name of the character set
+    #[must_use]
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            CharacterSet::Cp437 => "IBM437",
+            CharacterSet::Petscii => "PETSCII",
+            CharacterSet::Latin1 => "ISO-8859-1",
+            CharacterSet::Utf8 => "UTF-8",
+        }
+    }
+
+    /// Encodes a typed character for the server. The terminal's own character sets go through `converter`.
+    #[must_use]
+    pub fn encode(&self, ch: char, converter: &dyn UnicodeConverter) -> Vec<u8> {
+        match self {
+            CharacterSet::Cp437 | CharacterSet::Petscii => vec![converter.convert_from_unicode(ch, 0) as u8],
+            CharacterSet::Latin1 => vec![u8::try_from(ch).unwrap_or(b'?')],
+            CharacterSet::Utf8 => ch.to_string().into_bytes(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Protocol {
     #[default]
     Telnet,
+    TelnetTls,
     Raw,
+    RawTls,
     Modem,
+    /// Direct serial line without dialing, like a null-modem cable
+    Serial,
     Ssh,
+    /// BSD rlogin (RFC 1282)
+    Rlogin,
     WebSocket(bool), // true=secure
+    /// Local Hayes command interpreter, dialing opens a network connection
+    VirtualModem,
+    /// Runs the command line in `address` in a pseudo terminal
+    LocalProcess,
 }
 
 impl Display for Protocol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Ssh => write!(f, "SSH"),
+            Self::TelnetTls => write!(f, "Telnet (TLS)"),
+            Self::RawTls => write!(f, "Raw (TLS)"),
+            Self::Serial => write!(f, "Serial (direct)"),
+            Self::VirtualModem => write!(f, "Virtual modem"),
+            Self::LocalProcess => write!(f, "Local process"),
             Self::WebSocket(is_secure) => match is_secure {
                 true => write!(f, "Secure WebSocket"),
                 false => write!(f, "WebSocket"),
@@ -128,16 +214,130 @@ impl Display for Protocol {
 
 impl Protocol {
     #[cfg(not(target_arch = "wasm32"))]
-    pub const ALL: [Protocol; 6] = [
+    pub const ALL: [Protocol; if cfg!(target_os = "linux") { 12 } else { 11 }] = [
         Protocol::Telnet,
+        Protocol::TelnetTls,
         Protocol::Raw,
+        Protocol::RawTls,
         Protocol::Modem,
+        Protocol::Serial,
         Protocol::Ssh,
+        Protocol::Rlogin,
         Protocol::WebSocket(true),
         Protocol::WebSocket(false),
+        Protocol::VirtualModem,
+        // running a local process needs a pty, that's only implemented for linux
+        #[cfg(target_os = "linux")]
+        Protocol::LocalProcess,
     ];
     #[cfg(target_arch = "wasm32")]
     pub const ALL: [Protocol; 3] = [Protocol::Telnet, Protocol::Raw, Protocol::WebSocket(true)];
+
+    /// Returns true for protocols that use a TLS connection.
+    #[must_use]
+    pub fn is_tls(&self) -> bool {
+        matches!(self, Protocol::TelnetTls | Protocol::RawTls | Protocol::WebSocket(true))
+    }
+
+    /// Returns true for protocols that use a serial port with a modem profile.
+    #[must_use]
+    pub fn is_serial(&self) -> bool {
+        matches!(self, Protocol::Modem | Protocol::Serial)
+    }
+
+    /// The port used if an address doesn't specify one, `None` if a port is required.
+    #[must_use]
+    pub fn default_port(&self) -> Option<u16> {
+        match self {
+            Protocol::Telnet => Some(23),
+            Protocol::TelnetTls | Protocol::RawTls => Some(992),
+            Protocol::Ssh => Some(22),
+            Protocol::Rlogin => Some(513),
+            Protocol::WebSocket(true) => Some(443),
+            Protocol::WebSocket(false) => Some(80),
+            Protocol::Raw | Protocol::Modem | Protocol::Serial | Protocol::VirtualModem | Protocol::LocalProcess => None,
+        }
+    }
+}
+
+/// SSH authentication methods, tried in the order they're configured for an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshAuthMethod {
+    PublicKey,
+    Agent,
+    KeyboardInteractive,
+    Password,
+}
+
+impl SshAuthMethod {
+    pub const ALL: [SshAuthMethod; 4] = [
+        SshAuthMethod::PublicKey,
+        SshAuthMethod::Agent,
+        SshAuthMethod::KeyboardInteractive,
+        SshAuthMethod::Password,
+    ];
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "publickey" => Some(SshAuthMethod::PublicKey),
+            "agent" => Some(SshAuthMethod::Agent),
+            "keyboardinteractive" => Some(SshAuthMethod::KeyboardInteractive),
+            "password" => Some(SshAuthMethod::Password),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SshAuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SshAuthMethod::PublicKey => write!(f, "Public key"),
+            SshAuthMethod::Agent => write!(f, "SSH agent"),
+            SshAuthMethod::KeyboardInteractive => write!(f, "Keyboard-interactive"),
+            SshAuthMethod::Password => write!(f, "Password"),
+        }
+    }
+}
+
+/// Comma separated algorithm lists offered during the SSH key exchange, in order of preference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshAlgorithms {
+    pub key_exchanges: String,
+    pub host_keys: String,
+    pub ciphers: String,
+    pub macs: String,
+}
+
+impl SshAlgorithms {
+    /// Modern algorithms only.
+    #[must_use]
+    pub fn secure() -> Self {
+        Self {
+            key_exchanges: "curve25519-sha256,curve25519-sha256@libssh.org,ecdh-sha2-nistp256,ecdh-sha2-nistp384,ecdh-sha2-nistp521,diffie-hellman-group18-sha512,diffie-hellman-group16-sha512,diffie-hellman-group-exchange-sha256,diffie-hellman-group14-sha256".to_string(),
+            host_keys: "ssh-ed25519,ecdsa-sha2-nistp521,ecdsa-sha2-nistp384,ecdsa-sha2-nistp256,rsa-sha2-512,rsa-sha2-256".to_string(),
+            ciphers: "chacha20-poly1305@openssh.com,aes256-gcm@openssh.com,aes128-gcm@openssh.com,aes256-ctr,aes192-ctr,aes128-ctr".to_string(),
+            macs: "hmac-sha2-256-etm@openssh.com,hmac-sha2-512-etm@openssh.com,hmac-sha2-256,hmac-sha2-512".to_string(),
+        }
+    }
+
+    /// The secure algorithms followed by weak ones old systems may still require.
+    #[must_use]
+    pub fn legacy() -> Self {
+        let secure = Self::secure();
+        Self {
+            key_exchanges: secure.key_exchanges + ",diffie-hellman-group14-sha1,diffie-hellman-group-exchange-sha1,diffie-hellman-group1-sha1",
+            host_keys: secure.host_keys + ",ssh-rsa,ssh-dss",
+            ciphers: secure.ciphers + ",aes256-cbc,aes192-cbc,aes128-cbc,blowfish-cbc,3des-cbc,arcfour256,arcfour128,cast128-cbc,arcfour",
+            macs: secure.macs + ",hmac-sha1-etm@openssh.com,hmac-sha1,hmac-md5",
+        }
+    }
+}
+
+impl Default for SshAlgorithms {
+    fn default() -> Self {
+        Self::secure()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -185,8 +385,29 @@ pub struct Address {
     pub terminal_type: Terminal,
 
     pub address: String,
+    /// Alternative hosts, ports or phone numbers tried in order if `address` can't be reached
+    pub failover_addresses: Vec<String>,
     pub auto_login: String,
     pub protocol: Protocol,
+    /// Id of the modem profile used for `Protocol::Modem` and `Protocol::Serial`, empty for the first one
+    pub modem_profile: String,
+    /// Trust self-signed TLS certificates on first use without asking
+    pub accept_self_signed: bool,
+    /// The WebSocket frames carry a telnet stream with IAC sequences, like behind websockify gateways
+    pub websocket_telnet: bool,
+    /// Requested `Sec-WebSocket-Protocol` names, like "telnet" or "binary"
+    pub websocket_protocols: Vec<String>,
+    /// Additional HTTP headers sent with the WebSocket handshake
+    pub websocket_headers: Vec<(String, String)>,
+    /// Sends the password as rlogin client user name, the way Synchronet logs in - rlogin itself has no password
+    pub rlogin_send_password: bool,
+    pub ssh_auth_methods: Vec<SshAuthMethod>,
+    /// Private key file for SSH public key authentication
+    pub ssh_key_file: String,
+    pub ssh_key_passphrase: String,
+    pub ssh_algorithms: SshAlgorithms,
+    /// Names for the telnet TTYPE option in the order they're sent, empty for the terminal defaults
+    pub terminal_type_names: Vec<String>,
 
     pub ice_mode: bool,
     pub ansi_music: MusicOption,
@@ -208,6 +429,13 @@ pub struct Address {
     pub override_iemsi_settings: bool,
     pub iemsi_user: String,
     pub iemsi_password: String,
+
+    /// Use `proxy` instead of the proxy from the options
+    pub override_proxy_settings: bool,
+    pub proxy: Proxy,
+
+    /// Additional NEW-ENVIRON variables sent to telnet servers
+    pub environment: Vec<(String, String)>,
 }
 
 const TEMPLATE: &str = r#"
@@ -286,6 +514,18 @@ impl Address {
             auto_login: String::new(),
             address: String::new(),
             protocol: Protocol::default(),
+            modem_profile: String::new(),
+            terminal_type_names: Vec::new(),
+            failover_addresses: Vec::new(),
+            accept_self_signed: false,
+            websocket_telnet: false,
+            websocket_protocols: Vec::new(),
+            websocket_headers: Vec::new(),
+            rlogin_send_password: false,
+            ssh_auth_methods: SshAuthMethod::ALL.to_vec(),
+            ssh_key_file: String::new(),
+            ssh_key_passphrase: String::new(),
+            ssh_algorithms: SshAlgorithms::default(),
             ansi_music: MusicOption::default(),
             ice_mode: true,
             id: unsafe { current_id },
@@ -302,9 +542,65 @@ impl Address {
             override_iemsi_settings: false,
             iemsi_user: String::new(),
             iemsi_password: String::new(),
+            override_proxy_settings: false,
+            proxy: Proxy::default(),
+            environment: Vec::new(),
         }
     }
 
+    /// Returns the configured TTYPE names, or the defaults of the terminal type.
+    #[must_use]
+    pub fn get_terminal_type_names(&self) -> Vec<String> {
+        let names: Vec<String> = self
+            .terminal_type_names
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(std::string::ToString::to_string)
+            .collect();
+        if names.is_empty() {
+            self.terminal_type.get_default_type_names().iter().map(|name| (*name).to_string()).collect()
+        } else {
+            names
+        }
+    }
+
+    /// Returns the WebSocket subprotocols without empty entries.
+    #[must_use]
+    pub fn get_websocket_protocols(&self) -> Vec<String> {
+        self.websocket_protocols
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(std::string::ToString::to_string)
+            .collect()
+    }
+
+    /// Returns the extra handshake headers with trimmed names and values, entries without name are skipped.
+    #[must_use]
+    pub fn get_websocket_headers(&self) -> Vec<(String, String)> {
+        self.websocket_headers
+            .iter()
+            .map(|(name, value)| (name.trim().trim_end_matches(':').trim_end(), value.trim()))
+            .filter(|(name, _)| !name.is_empty())
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Returns the address followed by the failover addresses.
+    #[must_use]
+    pub fn get_dial_addresses(&self) -> Vec<String> {
+        let mut result = vec![self.address.clone()];
+        result.extend(
+            self.failover_addresses
+                .iter()
+                .map(|address| address.trim())
+                .filter(|address| !address.is_empty())
+                .map(std::string::ToString::to_string),
+        );
+        result
+    }
+
     #[must_use]
     pub fn get_dialing_directory_file() -> Option<PathBuf> {
         #[cfg(not(target_arch = "wasm32"))]
@@ -535,6 +831,13 @@ fn parse_address(value: &Value) -> Address {
         if let Some(Value::String(value)) = table.get("address") {
             result.address = value.clone();
         }
+        if let Some(Value::Array(values)) = table.get("failover_addresses") {
+            for value in values {
+                if let Value::String(address) = value {
+                    result.failover_addresses.push(address.clone());
+                }
+            }
+        }
         if let Some(Value::String(value)) = table.get("user_name") {
             result.user_name = value.clone();
         }
@@ -568,14 +871,76 @@ fn parse_address(value: &Value) -> Address {
         if let Some(Value::String(value)) = table.get("protocol") {
             match value.to_lowercase().as_str() {
                 "telnet" => result.protocol = Protocol::Telnet,
+                "telnettls" => result.protocol = Protocol::TelnetTls,
                 "ssh" => result.protocol = Protocol::Ssh,
+                "rlogin" => result.protocol = Protocol::Rlogin,
                 "raw" => result.protocol = Protocol::Raw,
+                "rawtls" => result.protocol = Protocol::RawTls,
+                "modem" => result.protocol = Protocol::Modem,
+                "serial" => result.protocol = Protocol::Serial,
                 "websocket(true)" => result.protocol = Protocol::WebSocket(true),
                 "websocket(false)" => result.protocol = Protocol::WebSocket(false),
+                "virtualmodem" => result.protocol = Protocol::VirtualModem,
+                "localprocess" => result.protocol = Protocol::LocalProcess,
                 _ => {}
             }
         }
 
+        if let Some(Value::String(value)) = table.get("modem_profile") {
+            result.modem_profile = value.clone();
+        }
+
+        if let Some(Value::Boolean(value)) = table.get("accept_self_signed") {
+            result.accept_self_signed = *value;
+        }
+
+        if let Some(Value::Boolean(value)) = table.get("websocket_telnet") {
+            result.websocket_telnet = *value;
+        }
+
+        if let Some(Value::Boolean(value)) = table.get("rlogin_send_password") {
+            result.rlogin_send_password = *value;
+        }
+
+        if let Some(Value::Array(values)) = table.get("websocket_protocols") {
+            for value in values {
+                if let Value::String(name) = value {
+                    result.websocket_protocols.push(name.clone());
+                }
+            }
+        }
+
+        if let Some(Value::Array(values)) = table.get("ssh_auth_methods") {
+            result.ssh_auth_methods = values
+                .iter()
+                .filter_map(|value| {
+                    if let Value::String(name) = value {
+                        SshAuthMethod::from_name(name)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+        }
+        if let Some(Value::String(value)) = table.get("ssh_key_file") {
+            result.ssh_key_file = value.clone();
+        }
+        if let Some(Value::String(value)) = table.get("ssh_key_passphrase") {
+            result.ssh_key_passphrase = value.clone();
+        }
+        if let Some(Value::String(value)) = table.get("ssh_key_exchanges") {
+            result.ssh_algorithms.key_exchanges = value.clone();
+        }
+        if let Some(Value::String(value)) = table.get("ssh_host_keys") {
+            result.ssh_algorithms.host_keys = value.clone();
+        }
+        if let Some(Value::String(value)) = table.get("ssh_ciphers") {
+            result.ssh_algorithms.ciphers = value.clone();
+        }
+        if let Some(Value::String(value)) = table.get("ssh_macs") {
+            result.ssh_algorithms.macs = value.clone();
+        }
+
         if let Some(Value::String(value)) = table.get("ansi_music") {
             match value.to_lowercase().as_str() {
                 "banana" => result.ansi_music = MusicOption::Banana,
@@ -600,6 +965,14 @@ fn parse_address(value: &Value) -> Address {
             }
         }
 
+        if let Some(Value::Array(values)) = table.get("terminal_type_names") {
+            for value in values {
+                if let Value::String(name) = value {
+                    result.terminal_type_names.push(name.clone());
+                }
+            }
+        }
+
         if let Some(Value::String(value)) = table.get("baud_emulation") {
             match value.to_lowercase().as_str() {
                 "off" => result.baud_emulation = BaudEmulation::Off,
@@ -650,12 +1023,43 @@ fn parse_address(value: &Value) -> Address {
                 result.iemsi_password = value.clone();
             }
         }
+        if let Some(Value::Table(map)) = table.get("proxy") {
+            if let Some(Value::Boolean(value)) = map.get("override_settings") {
+                result.override_proxy_settings = *value;
+            }
+            result.proxy = Proxy::from_table(map);
+        }
+        if let Some(Value::Table(map)) = table.get("environment") {
+            for (name, value) in map {
+                if let Value::String(value) = value {
+                    result.environment.push((name.clone(), value.clone()));
+                }
+            }
+        }
+        match table.get("websocket_headers") {
+            Some(Value::Array(values)) => {
+                for pair in values {
+                    if let Some([Value::String(name), Value::String(value)]) = pair.as_array().map(Vec::as_slice) {
+                        result.websocket_headers.push((name.clone(), value.clone()));
+                    }
+                }
+            }
+            // written as table before, which can't hold a name twice
+            Some(Value::Table(map)) => {
+                for (name, value) in map {
+                    if let Value::String(value) = value {
+                        result.websocket_headers.push((name.clone(), value.clone()));
+                    }
+                }
+            }
+            _ => {}
+        }
     }
 
     result
 }
 
-fn escape(value: &str) -> String {
+pub(crate) fn escape(value: &str) -> String {
     value
         .replace('\\', "\\\\")
         .replace('"', "\\\"")
@@ -671,9 +1075,67 @@ fn store_address(file: &mut File, addr: &Address) -> TerminalResult<()> {
         file.write_all(format!("is_favored = {}\n", addr.is_favored).as_bytes())?;
     }
     file.write_all(format!("address = \"{}\"\n", escape(&addr.address)).as_bytes())?;
+    let failover_addresses: Vec<String> = addr
+        .failover_addresses
+        .iter()
+        .map(|address| address.trim())
+        .filter(|address| !address.is_empty())
+        .map(|address| format!("\"{}\"", escape(address)))
+        .collect();
+    if !failover_addresses.is_empty() {
+        file.write_all(format!("failover_addresses = [{}]\n", failover_addresses.join(", ")).as_bytes())?;
+    }
     if addr.protocol != Protocol::default() {
         file.write_all(format!("protocol = \"{:?}\"\n", addr.protocol).as_bytes())?;
     }
+    if !addr.modem_profile.is_empty() {
+        file.write_all(format!("modem_profile = \"{}\"\n", escape(&addr.modem_profile)).as_bytes())?;
+    }
+    if addr.accept_self_signed {
+        file.write_all(format!("accept_self_signed = {}\n", addr.accept_self_signed).as_bytes())?;
+    }
+    if addr.websocket_telnet {
+        file.write_all(format!("websocket_telnet = {}\n", addr.websocket_telnet).as_bytes())?;
+    }
+    if addr.rlogin_send_password {
+        file.write_all(format!("rlogin_send_password = {}\n", addr.rlogin_send_password).as_bytes())?;
+    }
+    let websocket_protocols: Vec<String> = addr.get_websocket_protocols().iter().map(|name| format!("\"{}\"", escape(name))).collect();
+    if !websocket_protocols.is_empty() {
+        file.write_all(format!("websocket_protocols = [{}]\n", websocket_protocols.join(", ")).as_bytes())?;
+    }
+    // pairs instead of a table, a header name may repeat and the order is kept
+    let websocket_headers: Vec<String> = addr
+        .get_websocket_headers()
+        .iter()
+        .map(|(name, value)| format!("[\"{}\", \"{}\"]", escape(name), escape(value)))
+        .collect();
+    if !websocket_headers.is_empty() {
+        file.write_all(format!("websocket_headers = [{}]\n", websocket_headers.join(", ")).as_bytes())?;
+    }
+    if addr.ssh_auth_methods != SshAuthMethod::ALL {
+        let methods: Vec<String> = addr.ssh_auth_methods.iter().map(|method| format!("\"{method:?}\"")).collect();
+        file.write_all(format!("ssh_auth_methods = [{}]\n", methods.join(", ")).as_bytes())?;
+    }
+    if !addr.ssh_key_file.is_empty() {
+        file.write_all(format!("ssh_key_file = \"{}\"\n", escape(&addr.ssh_key_file)).as_bytes())?;
+    }
+    if !addr.ssh_key_passphrase.is_empty() {
+        file.write_all(format!("ssh_key_passphrase = \"{}\"\n", escape(&addr.ssh_key_passphrase)).as_bytes())?;
+    }
+    let default_algorithms = SshAlgorithms::default();
+    if addr.ssh_algorithms.key_exchanges != default_algorithms.key_exchanges {
+        file.write_all(format!("ssh_key_exchanges = \"{}\"\n", escape(&addr.ssh_algorithms.key_exchanges)).as_bytes())?;
+    }
+    if addr.ssh_algorithms.host_keys != default_algorithms.host_keys {
+        file.write_all(format!("ssh_host_keys = \"{}\"\n", escape(&addr.ssh_algorithms.host_keys)).as_bytes())?;
+    }
+    if addr.ssh_algorithms.ciphers != default_algorithms.ciphers {
+        file.write_all(format!("ssh_ciphers = \"{}\"\n", escape(&addr.ssh_algorithms.ciphers)).as_bytes())?;
+    }
+    if addr.ssh_algorithms.macs != default_algorithms.macs {
+        file.write_all(format!("ssh_macs = \"{}\"\n", escape(&addr.ssh_algorithms.macs)).as_bytes())?;
+    }
     if !addr.user_name.is_empty() {
         file.write_all(format!("user_name = \"{}\"\n", escape(&addr.user_name)).as_bytes())?;
     }
@@ -688,6 +1150,17 @@ fn store_address(file: &mut File, addr: &Address) -> TerminalResult<()> {
         file.write_all(format!("terminal_type = \"{:?}\"\n", addr.terminal_type).as_bytes())?;
     }
 
+    let terminal_type_names: Vec<String> = addr
+        .terminal_type_names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| format!("\"{}\"", escape(name)))
+        .collect();
+    if !terminal_type_names.is_empty() {
+        file.write_all(format!("terminal_type_names = [{}]\n", terminal_type_names.join(", ")).as_bytes())?;
+    }
+
     if addr.ansi_music != MusicOption::default() {
         file.write_all(format!("ansi_music = \"{:?}\"\n", addr.ansi_music).as_bytes())?;
     }
@@ -723,6 +1196,21 @@ fn store_address(file: &mut File, addr: &Address) -> TerminalResult<()> {
         }
     }
 
+    if addr.override_proxy_settings || addr.proxy != Proxy::default() {
+        file.write_all("[addresses.proxy]\n".to_string().as_bytes())?;
+        if addr.override_proxy_settings {
+            file.write_all(format!("override_settings = {}\n", addr.override_proxy_settings).as_bytes())?;
+        }
+        addr.proxy.write_settings(file)?;
+    }
+
+    if !addr.environment.is_empty() {
+        file.write_all("[addresses.environment]\n".to_string().as_bytes())?;
+        for (name, value) in &addr.environment {
+            file.write_all(format!("\"{}\" = \"{}\"\n", escape(name), escape(value)).as_bytes())?;
+        }
+    }
+
     Ok(())
 }
 
@@ -817,4 +1305,257 @@ mod tests {
         };
         res.load_string(TEMPLATE).unwrap();
     }
+
+    #[test]
+    fn test_parse_environment() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "localhost:23"
+[addresses.environment]
+SYSTEMTYPE = "AMIGA"
+"CLIENT NAME" = "IcyTerm"
+"#,
+        )
+        .unwrap();
+        let mut environment = res.addresses[0].environment.clone();
+        environment.sort();
+        assert_eq!(
+            vec![
+                ("CLIENT NAME".to_string(), "IcyTerm".to_string()),
+                ("SYSTEMTYPE".to_string(), "AMIGA".to_string())
+            ],
+            environment
+        );
+    }
+
+    #[test]
+    fn test_parse_terminal_type_names() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "localhost:23"
+terminal_type_names = ["XTERM", "ANSI"]
+
+[[addresses]]
+system_name = "Test2"
+address = "localhost:23"
+terminal_type = "PETscii"
+"#,
+        )
+        .unwrap();
+        assert_eq!(vec!["XTERM".to_string(), "ANSI".to_string()], res.addresses[0].get_terminal_type_names());
+        assert_eq!(vec!["PETSCII".to_string()], res.addresses[1].get_terminal_type_names());
+    }
+
+    #[test]
+    fn test_parse_failover_addresses() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "bbs.example.com"
+failover_addresses = ["bbs.example.com:2323", " ", "backup.example.com"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                "bbs.example.com".to_string(),
+                "bbs.example.com:2323".to_string(),
+                "backup.example.com".to_string()
+            ],
+            res.addresses[0].get_dial_addresses()
+        );
+    }
+
+    #[test]
+    fn test_parse_modem_profile() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "5551234"
+protocol = "Modem"
+modem_profile = "WiFi Modem"
+"#,
+        )
+        .unwrap();
+        assert_eq!(Protocol::Modem, res.addresses[0].protocol);
+        assert_eq!("WiFi Modem", res.addresses[0].modem_profile);
+    }
+
+    #[test]
+    fn test_parse_websocket_telnet() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "bbs.example.com/telnet"
+protocol = "WebSocket(true)"
+websocket_telnet = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(Protocol::WebSocket(true), res.addresses[0].protocol);
+        assert!(res.addresses[0].websocket_telnet);
+    }
+
+    #[test]
+    fn test_parse_websocket_options() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "wss://bbs.example.com/ws"
+protocol = "WebSocket(true)"
+websocket_protocols = ["telnet", "binary"]
+[addresses.websocket_headers]
+Origin = "https://bbs.example.com"
+"#,
+        )
+        .unwrap();
+        assert_eq!(vec!["telnet".to_string(), "binary".to_string()], res.addresses[0].websocket_protocols);
+        assert_eq!(
+            vec![("Origin".to_string(), "https://bbs.example.com".to_string())],
+            res.addresses[0].websocket_headers
+        );
+
+        let mut address = Address::new("Test");
+        address.websocket_headers = vec![
+            ("Origin:".to_string(), String::new()),
+            (" X-Node".to_string(), " 1 ".to_string()),
+            (String::new(), String::new()),
+        ];
+        assert_eq!(
+            vec![("Origin".to_string(), String::new()), ("X-Node".to_string(), "1".to_string())],
+            address.get_websocket_headers()
+        );
+    }
+
+    #[test]
+    fn test_store_repeated_websocket_headers() {
+        let mut address = Address::new("Test");
+        address.address = "wss://bbs.example.com/ws".to_string();
+        address.protocol = Protocol::WebSocket(true);
+        address.websocket_headers = vec![
+            ("Cookie".to_string(), "b=2".to_string()),
+            ("Origin".to_string(), "https://bbs.example.com".to_string()),
+            ("Cookie".to_string(), "a=\"1\"".to_string()),
+        ];
+
+        let file_name = std::env::temp_dir().join(format!("icy_term_websocket_headers_{}.toml", std::process::id()));
+        let mut file = File::create(&file_name).unwrap();
+        store_address(&mut file, &address).unwrap();
+        drop(file);
+        let text = fs::read_to_string(&file_name).unwrap();
+        let _ = fs::remove_file(&file_name);
+
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(&format!("version = \"{}\"\n{text}", AddressBook::VERSION)).unwrap();
+        assert_eq!(address.websocket_headers, res.addresses[0].websocket_headers);
+    }
+
+    #[test]
+    fn test_parse_ssh_auth_methods() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "localhost:22"
+protocol = "Ssh"
+ssh_auth_methods = ["Agent", "Password", "Unknown"]
+ssh_key_file = "~/.ssh/id_ed25519"
+"#,
+        )
+        .unwrap();
+        assert_eq!(vec![SshAuthMethod::Agent, SshAuthMethod::Password], res.addresses[0].ssh_auth_methods);
+        assert_eq!("~/.ssh/id_ed25519", res.addresses[0].ssh_key_file);
+        assert_eq!(SshAuthMethod::ALL.to_vec(), Address::new("test").ssh_auth_methods);
+    }
+
+    #[test]
+    fn test_parse_ssh_algorithms() {
+        let mut res = AddressBook {
+            write_lock: false,
+            created_backup: false,
+            addresses: Vec::new(),
+        };
+        res.load_string(
+            r#"
+version = "1.1.0"
+
+[[addresses]]
+system_name = "Test"
+address = "localhost:22"
+protocol = "Ssh"
+ssh_ciphers = "aes128-cbc,3des-cbc"
+"#,
+        )
+        .unwrap();
+        assert_eq!("aes128-cbc,3des-cbc", res.addresses[0].ssh_algorithms.ciphers);
+        assert_eq!(SshAlgorithms::secure().key_exchanges, res.addresses[0].ssh_algorithms.key_exchanges);
+
+        let secure = SshAlgorithms::secure();
+        let legacy = SshAlgorithms::legacy();
+        assert!(!secure.key_exchanges.contains("diffie-hellman-group1-sha1"));
+        assert!(!secure.ciphers.contains("arcfour"));
+        assert!(legacy.key_exchanges.starts_with(&secure.key_exchanges));
+        assert!(legacy.key_exchanges.contains("diffie-hellman-group1-sha1"));
+    }
 }
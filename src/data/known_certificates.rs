@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+};
+
+use toml::Value;
+
+use crate::TerminalResult;
+
+/// Fingerprints of TLS certificates the user decided to trust (trust on first use).
+/// Works like ssh's known_hosts: one certificate per 'host:port'.
+#[derive(Debug, Clone, Default)]
+pub struct KnownCertificates {
+    file_name: Option<PathBuf>,
+    certificates: HashMap<String, String>,
+}
+
+impl KnownCertificates {
+    /// Loads the store from the configuration directory, an unreadable file results in an empty store.
+    #[must_use]
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(proj_dirs) = directories::ProjectDirs::from("com", "GitHub", "icy_term") {
+            return Self::load_from(proj_dirs.config_dir().join("known_certificates.toml"));
+        }
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn load_from(file_name: PathBuf) -> Self {
+        let mut result = Self {
+            file_name: Some(file_name.clone()),
+            certificates: HashMap::new(),
+        };
+        if !file_name.exists() {
+            return result;
+        }
+        match fs::read_to_string(&file_name) {
+            Ok(content) => match content.parse::<Value>() {
+                Ok(Value::Table(table)) => {
+                    if let Some(Value::Table(certificates)) = table.get("certificates") {
+                        for (host, fingerprint) in certificates {
+                            if let Value::String(fingerprint) = fingerprint {
+                                result.certificates.insert(host.clone(), fingerprint.clone());
+                            }
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => log::error!("Error parsing {file_name:?}: {err}"),
+            },
+            Err(err) => log::error!("Error reading {file_name:?}: {err}"),
+        }
+        result
+    }
+
+    #[must_use]
+    pub fn get(&self, host: &str) -> Option<&String> {
+        self.certificates.get(host)
+    }
+
+    pub fn insert(&mut self, host: impl Into<String>, fingerprint: impl Into<String>) {
+        self.certificates.insert(host.into(), fingerprint.into());
+    }
+
+    /// Writes the store back to the file it was loaded from.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be written.
+    pub fn store(&self) -> TerminalResult<()> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
+        };
+        if let Some(dir) = file_name.parent() {
+            if !dir.exists() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        let mut write_name = file_name.clone();
+        write_name.set_extension("new");
+
+        let mut file = File::create(&write_name)?;
+        file.write_all(b"version = \"1.0\"\n\n[certificates]\n")?;
+        let mut hosts: Vec<&String> = self.certificates.keys().collect();
+        hosts.sort();
+        for host in hosts {
+            file.write_all(format!("\"{}\" = \"{}\"\n", host.replace('\\', "\\\\").replace('"', "\\\""), self.certificates[host]).as_bytes())?;
+        }
+        file.flush()?;
+
+        // move temp file to the real file
+        fs::rename(&write_name, file_name)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_load() {
+        let file_name = std::env::temp_dir().join(format!("icy_term_known_certificates_{}.toml", std::process::id()));
+        let mut store = KnownCertificates::load_from(file_name.clone());
+        assert!(store.get("bbs.example.com:992").is_none());
+        store.insert("bbs.example.com:992", "AB:CD");
+        store.insert("[::1]:992", "EF:01");
+        store.store().unwrap();
+
+        let store = KnownCertificates::load_from(file_name.clone());
+        fs::remove_file(file_name).unwrap();
+        assert_eq!(Some(&"AB:CD".to_string()), store.get("bbs.example.com:992"));
+        assert_eq!(Some(&"EF:01".to_string()), store.get("[::1]:992"));
+    }
+}
@@ -4,5 +4,11 @@ pub use options::*;
 pub mod addresses;
 pub use addresses::*;
 
+pub mod known_certificates;
+pub use known_certificates::*;
+
 pub mod modem;
 pub use modem::*;
+
+pub mod proxy;
+pub use proxy::*;
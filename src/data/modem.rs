@@ -2,10 +2,13 @@ use std::io::Write;
 
 use serial::{CharSize, FlowControl, StopBits};
 
-use crate::TerminalResult;
+use crate::{addresses::escape, TerminalResult};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Modem {
+    /// Addresses select the modem profile by this id, it stays the same when the profile gets renamed
+    pub id: String,
+    pub name: String,
     pub device: String,
     pub baud_rate: usize,
 
@@ -20,10 +23,9 @@ pub struct Modem {
 }
 impl Modem {
     pub(crate) fn write_modem_settings(&self, file: &mut std::fs::File) -> TerminalResult<()> {
-        // currently unused
-        file.write_all("name = \"Modem 1\"\n".to_string().as_bytes())?;
-
-        file.write_all(format!("device = \"{}\"\n", self.device).as_bytes())?;
+        file.write_all(format!("id = \"{}\"\n", escape(&self.id)).as_bytes())?;
+        file.write_all(format!("name = \"{}\"\n", escape(&self.name)).as_bytes())?;
+        file.write_all(format!("device = \"{}\"\n", escape(&self.device)).as_bytes())?;
         file.write_all(format!("baud_rate = {}\n", self.baud_rate).as_bytes())?;
         let cs = match self.char_size {
             CharSize::Bits5 => 5,
@@ -52,8 +54,8 @@ impl Modem {
             FlowControl::FlowHardware => "Hardware",
         };
         file.write_all(format!("flow_control = \"{cs}\"\n").as_bytes())?;
-        file.write_all(format!("init_string = \"{}\"\n", self.init_string).as_bytes())?;
-        file.write_all(format!("dial_string = \"{}\"\n", self.dial_string).as_bytes())?;
+        file.write_all(format!("init_string = \"{}\"\n", escape(&self.init_string)).as_bytes())?;
+        file.write_all(format!("dial_string = \"{}\"\n", escape(&self.dial_string)).as_bytes())?;
 
         Ok(())
     }
@@ -62,6 +64,16 @@ impl Modem {
         let mut result = Modem::default();
         for (k, v) in table {
             match k.as_str() {
+                "id" => {
+                    if let toml::Value::String(s) = v {
+                        result.id = s.to_string();
+                    }
+                }
+                "name" => {
+                    if let toml::Value::String(s) = v {
+                        result.name = s.to_string();
+                    }
+                }
                 "device" => {
                     if let toml::Value::String(s) = v {
                         result.device = s.to_string();
@@ -125,6 +137,10 @@ impl Modem {
                 _ => {}
             }
         }
+        // profiles written before the ids were selected by their name
+        if !table.contains_key("id") {
+            result.id.clone_from(&result.name);
+        }
         result
     }
 }
@@ -132,6 +148,8 @@ impl Modem {
 impl Default for Modem {
     fn default() -> Self {
         Self {
+            id: "Modem 1".to_string(),
+            name: "Modem 1".to_string(),
             #[cfg(target_os = "windows")]
             device: "COM1".to_string(),
             #[cfg(not(target_os = "windows"))]
@@ -146,3 +164,67 @@ impl Default for Modem {
         }
     }
 }
+
+/// Lists the serial ports of the system, USB adapters included.
+#[cfg(not(target_os = "windows"))]
+pub fn available_serial_ports() -> Vec<String> {
+    // onboard ports, USB serial and CDC ACM adapters on linux/BSD and the callout devices on macOS
+    const PREFIXES: [&str; 6] = ["ttyS", "ttyUSB", "ttyACM", "ttyAMA", "cu.", "ttyU"];
+    let Ok(entries) = std::fs::read_dir("/dev") else {
+        return Vec::new();
+    };
+    let mut ports: Vec<String> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| PREFIXES.iter().any(|prefix| name.starts_with(prefix)))
+        .map(|name| format!("/dev/{name}"))
+        .collect();
+    // linux creates ttyS devices for every possible UART, only the ones with a driver bound exist
+    #[cfg(target_os = "linux")]
+    ports.retain(|port| !port.starts_with("/dev/ttyS") || std::path::Path::new(&format!("/sys/class/tty/{}/device/driver", &port[5..])).exists());
+    ports.sort_by(|a, b| natural_order(a).cmp(&natural_order(b)));
+    ports
+}
+
+/// Lists the serial ports of the system, USB adapters included.
+#[cfg(target_os = "windows")]
+pub fn available_serial_ports() -> Vec<String> {
+    // ports that are in use can't be opened but still exist
+    (1..=32)
+        .map(|i| format!("COM{i}"))
+        .filter(|port| match std::fs::OpenOptions::new().read(true).open(format!("\\\\.\\{port}")) {
+            Ok(_) => true,
+            Err(err) => err.kind() == std::io::ErrorKind::PermissionDenied,
+        })
+        .collect()
+}
+
+/// Sorts 'ttyUSB2' before 'ttyUSB10'.
+#[cfg(not(target_os = "windows"))]
+fn natural_order(port: &str) -> (&str, usize) {
+    let prefix = port.trim_end_matches(|c: char| c.is_ascii_digit());
+    (prefix, port[prefix.len()..].parse().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_natural_order() {
+        let mut ports = vec!["/dev/ttyUSB10", "/dev/ttyACM0", "/dev/ttyUSB2"];
+        ports.sort_by(|a, b| natural_order(a).cmp(&natural_order(b)));
+        assert_eq!(vec!["/dev/ttyACM0", "/dev/ttyUSB2", "/dev/ttyUSB10"], ports);
+    }
+
+    #[test]
+    fn test_parse_modem_name() {
+        let table: toml::map::Map<String, toml::Value> = toml::from_str("name = \"WiFi Modem\"\ndevice = \"/dev/ttyUSB0\"\nbaud_rate = 115200").unwrap();
+        let modem = Modem::from_table(&table);
+        assert_eq!("WiFi Modem", modem.id);
+        assert_eq!("WiFi Modem", modem.name);
+        assert_eq!("/dev/ttyUSB0", modem.device);
+        assert_eq!(115200, modem.baud_rate);
+    }
+}
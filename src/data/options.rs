@@ -11,7 +11,7 @@ use icy_engine::Color;
 use icy_engine_gui::MonitorSettings;
 use toml::Value;
 
-use crate::{Modem, TerminalResult};
+use crate::{Modem, Proxy, TerminalResult};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Scaling {
@@ -270,6 +270,9 @@ impl Default for IEMSISettings {
 pub struct Options {
     pub scaling: Scaling,
     pub connect_timeout: Duration,
+    /// Number of rounds through the dial queue before redialing gives up
+    pub redial_attempts: u32,
+    pub redial_delay: Duration,
     pub console_beep: bool,
     pub is_dark_mode: Option<bool>,
 
@@ -277,20 +280,25 @@ pub struct Options {
     pub bind: KeyBindings,
     pub iemsi: IEMSISettings,
 
-    pub modem: Modem,
+    /// Modem profiles, addresses pick one by name
+    pub modems: Vec<Modem>,
+    pub proxy: Proxy,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             scaling: Scaling::default(),
-            connect_timeout: Duration::default(),
+            connect_timeout: Duration::from_secs(10),
+            redial_attempts: 10,
+            redial_delay: Duration::from_secs(30),
             monitor_settings: MonitorSettings::default(),
             iemsi: IEMSISettings::default(),
             console_beep: true,
             bind: KeyBindings::default(),
             is_dark_mode: None,
-            modem: Modem::default(),
+            modems: vec![Modem::default()],
+            proxy: Proxy::default(),
         }
     }
 }
@@ -349,6 +357,15 @@ impl Options {
             file.write_all(format!("monitor_curvature = {:?}\n", self.monitor_settings.curvature).as_bytes())?;
             file.write_all(format!("monitor_scanlines = {:?}\n", self.monitor_settings.scanlines).as_bytes())?;
 
+            if self.connect_timeout != Options::default().connect_timeout {
+                file.write_all(format!("connect_timeout = {}\n", self.connect_timeout.as_secs()).as_bytes())?;
+            }
+            if self.redial_attempts != Options::default().redial_attempts {
+                file.write_all(format!("redial_attempts = {}\n", self.redial_attempts).as_bytes())?;
+            }
+            if self.redial_delay != Options::default().redial_delay {
+                file.write_all(format!("redial_delay = {}\n", self.redial_delay.as_secs()).as_bytes())?;
+            }
             if self.console_beep != Options::default().console_beep {
                 file.write_all(format!("console_beep = {}\n", self.console_beep).as_bytes())?;
             }
@@ -382,10 +399,17 @@ impl Options {
                 file.write_all(format!("autologin = {}\n", self.iemsi.autologin).as_bytes())?;
             }
 
+            if self.proxy != Proxy::default() {
+                file.write_all("[PROXY]\n".to_string().as_bytes())?;
+                self.proxy.write_settings(&mut file)?;
+            }
+
             write_keybindings(&mut file, &self.bind)?;
 
-            file.write_all("[[modem]]\n".to_string().as_bytes())?;
-            self.modem.write_modem_settings(&mut file)?;
+            for modem in &self.modems {
+                file.write_all("[[modem]]\n".to_string().as_bytes())?;
+                modem.write_modem_settings(&mut file)?;
+            }
 
             file.flush()?;
 
@@ -417,6 +441,11 @@ impl Options {
     pub(crate) fn reset_keybindings(&mut self) {
         self.bind = KeyBindings::default();
     }
+
+    /// Returns the modem profile with the given id, the first profile if there is none.
+    pub fn get_modem(&self, id: &str) -> Option<&Modem> {
+        self.modems.iter().find(|modem| modem.id == id).or(self.modems.first())
+    }
 }
 
 fn parse_value(options: &mut Options, value: &Value) {
@@ -503,12 +532,32 @@ fn parse_value(options: &mut Options, value: &Value) {
                             parse_iemsi_settings(options, iemsi_settings);
                         }
                     }
+                    "PROXY" => {
+                        if let Value::Table(proxy_settings) = v {
+                            options.proxy = Proxy::from_table(proxy_settings);
+                        }
+                    }
                     "KEYBINDINGS" => {
                         if let Value::Table(keybind_settings) = v {
                             parse_keybinding_settings(options, keybind_settings);
                         }
                     }
 
+                    "connect_timeout" => {
+                        if let Value::Integer(secs) = v {
+                            options.connect_timeout = Duration::from_secs((*secs).max(1) as u64);
+                        }
+                    }
+                    "redial_attempts" => {
+                        if let Value::Integer(attempts) = v {
+                            options.redial_attempts = (*attempts).clamp(1, u32::MAX as i64) as u32;
+                        }
+                    }
+                    "redial_delay" => {
+                        if let Value::Integer(secs) = v {
+                            options.redial_delay = Duration::from_secs((*secs).max(0) as u64);
+                        }
+                    }
                     "console_beep" => {
                         if let Value::Boolean(b) = v {
                             options.console_beep = *b;
@@ -521,11 +570,12 @@ fn parse_value(options: &mut Options, value: &Value) {
                     }*/
                     "modem" => {
                         if let Value::Array(array) = v {
-                            for v in array {
-                                if let Value::Table(b) = v {
-                                    options.modem = Modem::from_table(b);
-                                    break;
-                                }
+                            let modems: Vec<Modem> = array
+                                .iter()
+                                .filter_map(|v| if let Value::Table(b) = v { Some(Modem::from_table(b)) } else { None })
+                                .collect();
+                            if !modems.is_empty() {
+                                options.modems = modems;
                             }
                         }
                     }
@@ -604,4 +654,26 @@ mod tests {
         opt.reset_keybindings();
         assert_eq!(Options::default().bind, opt.bind);
     }
+
+    #[test]
+    fn test_parse_modem_profiles() {
+        let opt = Options::from_str(
+            r#"
+[[modem]]
+name = "Real Modem"
+device = "/dev/ttyS0"
+
+[[modem]]
+id = "Modem 2"
+name = "WiFi Modem"
+device = "/dev/ttyUSB0"
+baud_rate = 115200
+"#,
+        );
+        assert_eq!(2, opt.modems.len());
+        assert_eq!("/dev/ttyUSB0", opt.get_modem("Modem 2").unwrap().device);
+        assert_eq!("/dev/ttyS0", opt.get_modem("Real Modem").unwrap().device);
+        assert_eq!("Real Modem", opt.get_modem("").unwrap().name);
+        assert_eq!("Real Modem", opt.get_modem("Unknown").unwrap().name);
+    }
 }
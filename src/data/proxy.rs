@@ -0,0 +1,99 @@
+use std::{fmt::Display, io::Write};
+
+use crate::TerminalResult;
+
+use super::addresses::escape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProxyKind {
+    /// Connect directly
+    #[default]
+    None,
+    Socks5,
+    HttpConnect,
+}
+
+impl ProxyKind {
+    pub const ALL: [ProxyKind; 3] = [ProxyKind::None, ProxyKind::Socks5, ProxyKind::HttpConnect];
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "socks5" => ProxyKind::Socks5,
+            "httpconnect" => ProxyKind::HttpConnect,
+            _ => ProxyKind::None,
+        }
+    }
+}
+
+impl Display for ProxyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyKind::None => write!(f, "None"),
+            ProxyKind::Socks5 => write!(f, "SOCKS5"),
+            ProxyKind::HttpConnect => write!(f, "HTTP CONNECT"),
+        }
+    }
+}
+
+/// Proxy all TCP based connections get tunneled through.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    /// 'host:port' of the proxy server
+    pub address: String,
+    /// Optional credentials, SOCKS5 username/password or HTTP basic authentication
+    pub user_name: String,
+    pub password: String,
+}
+
+impl Proxy {
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.kind != ProxyKind::None
+    }
+
+    pub(crate) fn write_settings(&self, file: &mut std::fs::File) -> TerminalResult<()> {
+        file.write_all(format!("kind = \"{:?}\"\n", self.kind).as_bytes())?;
+        if !self.address.is_empty() {
+            file.write_all(format!("address = \"{}\"\n", escape(&self.address)).as_bytes())?;
+        }
+        if !self.user_name.is_empty() {
+            file.write_all(format!("user_name = \"{}\"\n", escape(&self.user_name)).as_bytes())?;
+        }
+        if !self.password.is_empty() {
+            file.write_all(format!("password = \"{}\"\n", escape(&self.password)).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn from_table(table: &toml::map::Map<String, toml::Value>) -> Proxy {
+        let mut result = Proxy::default();
+        for (k, v) in table {
+            match k.as_str() {
+                "kind" => {
+                    if let toml::Value::String(s) = v {
+                        result.kind = ProxyKind::from_name(s);
+                    }
+                }
+                "address" => {
+                    if let toml::Value::String(s) = v {
+                        result.address = s.to_string();
+                    }
+                }
+                "user_name" => {
+                    if let toml::Value::String(s) = v {
+                        result.user_name = s.to_string();
+                    }
+                }
+                "password" => {
+                    if let toml::Value::String(s) = v {
+                        result.password = s.to_string();
+                    }
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+}
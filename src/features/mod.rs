@@ -5,3 +5,6 @@ mod auto_login;
 pub use auto_login::*;
 
 mod iemsi_com;
+
+mod redial;
+pub use redial::*;
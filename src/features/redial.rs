@@ -0,0 +1,187 @@
+use web_time::{Duration, Instant};
+
+/// A phonebook entry in the dial queue with all addresses that reach it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialTarget {
+    pub id: usize,
+    pub name: String,
+    /// The address of the entry followed by its failover addresses.
+    pub addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedialState {
+    Dialing,
+    /// Waiting for the next attempt
+    Waiting(Instant),
+    Finished,
+}
+
+/// Dials the queued entries one after another until one connects.
+///
+/// Failover addresses of an entry and the next entries get dialed right away,
+/// after a failed round through the whole queue the next attempt starts after a delay.
+pub struct DialQueue {
+    targets: Vec<DialTarget>,
+    max_attempts: u32,
+    delay: Duration,
+    attempt: u32,
+    target: usize,
+    address: usize,
+    state: RedialState,
+}
+
+impl DialQueue {
+    pub fn new(targets: Vec<DialTarget>, max_attempts: u32, delay: Duration) -> Self {
+        let targets: Vec<DialTarget> = targets.into_iter().filter(|t| !t.addresses.is_empty()).collect();
+        let state = if targets.is_empty() { RedialState::Finished } else { RedialState::Dialing };
+        Self {
+            targets,
+            max_attempts: max_attempts.max(1),
+            delay,
+            attempt: 1,
+            target: 0,
+            address: 0,
+            state,
+        }
+    }
+
+    /// The entry and the address that is dialed now or with the next attempt.
+    pub fn current(&self) -> Option<(&DialTarget, &str)> {
+        if self.state == RedialState::Finished {
+            return None;
+        }
+        let target = &self.targets[self.target];
+        Some((target, &target.addresses[self.address]))
+    }
+
+    pub fn state(&self) -> RedialState {
+        self.state
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// A single connect with just one address doesn't need any redial feedback.
+    pub fn is_redial(&self) -> bool {
+        self.max_attempts > 1 || self.targets.len() > 1
+    }
+
+    /// Moves on after a failed dial, returns `true` if the next address should be dialed right away.
+    pub fn dial_failed(&mut self, now: Instant) -> bool {
+        if self.state != RedialState::Dialing {
+            return false;
+        }
+        self.address += 1;
+        if self.address < self.targets[self.target].addresses.len() {
+            return true;
+        }
+        self.address = 0;
+        self.target += 1;
+        if self.target < self.targets.len() {
+            return true;
+        }
+        self.target = 0;
+        if self.attempt >= self.max_attempts {
+            self.state = RedialState::Finished;
+            return false;
+        }
+        self.attempt += 1;
+        if self.delay.is_zero() {
+            return true;
+        }
+        self.state = RedialState::Waiting(now + self.delay);
+        false
+    }
+
+    /// Returns `true` if the delay is over and the next attempt should be dialed.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if let RedialState::Waiting(until) = self.state {
+            if now >= until {
+                self.state = RedialState::Dialing;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(id: usize, addresses: &[&str]) -> DialTarget {
+        DialTarget {
+            id,
+            name: format!("BBS {id}"),
+            addresses: addresses.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn current(queue: &DialQueue) -> Option<(usize, String)> {
+        queue.current().map(|(t, a)| (t.id, a.to_string()))
+    }
+
+    #[test]
+    fn test_single_address() {
+        let mut queue = DialQueue::new(vec![target(1, &["bbs.example.com"])], 1, Duration::from_secs(30));
+        assert!(!queue.is_redial());
+        assert_eq!(Some((1, "bbs.example.com".to_string())), current(&queue));
+        assert!(!queue.dial_failed(Instant::now()));
+        assert_eq!(RedialState::Finished, queue.state());
+        assert_eq!(None, current(&queue));
+    }
+
+    #[test]
+    fn test_failover_addresses() {
+        let mut queue = DialQueue::new(
+            vec![target(1, &["bbs.example.com", "bbs.example.com:2323", "backup.example.com"])],
+            1,
+            Duration::ZERO,
+        );
+        assert!(queue.dial_failed(Instant::now()));
+        assert_eq!(Some((1, "bbs.example.com:2323".to_string())), current(&queue));
+        assert!(queue.dial_failed(Instant::now()));
+        assert_eq!(Some((1, "backup.example.com".to_string())), current(&queue));
+        assert!(!queue.dial_failed(Instant::now()));
+        assert_eq!(RedialState::Finished, queue.state());
+    }
+
+    #[test]
+    fn test_redial_queue() {
+        let now = Instant::now();
+        let delay = Duration::from_secs(30);
+        let mut queue = DialQueue::new(vec![target(1, &["one"]), target(2, &["two", "two-backup"])], 2, delay);
+        assert!(queue.is_redial());
+        assert!(queue.dial_failed(now));
+        assert_eq!(Some((2, "two".to_string())), current(&queue));
+        assert!(queue.dial_failed(now));
+        assert_eq!(Some((2, "two-backup".to_string())), current(&queue));
+
+        // second attempt starts with the first entry after the delay
+        assert!(!queue.dial_failed(now));
+        assert_eq!(RedialState::Waiting(now + delay), queue.state());
+        assert_eq!(2, queue.attempt());
+        assert_eq!(Some((1, "one".to_string())), current(&queue));
+        assert!(!queue.poll(now));
+        assert!(queue.poll(now + delay));
+        assert_eq!(RedialState::Dialing, queue.state());
+
+        assert!(queue.dial_failed(now));
+        assert!(queue.dial_failed(now));
+        assert!(!queue.dial_failed(now));
+        assert_eq!(RedialState::Finished, queue.state());
+    }
+
+    #[test]
+    fn test_empty_queue() {
+        let queue = DialQueue::new(vec![target(1, &[])], 3, Duration::ZERO);
+        assert_eq!(RedialState::Finished, queue.state());
+        assert_eq!(None, current(&queue));
+    }
+}
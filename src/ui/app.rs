@@ -87,6 +87,7 @@ impl MainWindow {
             terminal_type: None,
             mouse_field: Vec::new(),
             cache_directory: PathBuf::new(),
+            character_set_decoder: None,
         }));
 
         let update_thread_handle = crate::ui::buffer_update_thread::run_update_thread(&cc.egui_ctx, buffer_update_thread.clone());
@@ -103,6 +104,8 @@ impl MainWindow {
             poll_thread,
             is_fullscreen_mode,
             export_dialog: dialogs::export_dialog::DialogState::default(),
+            auth_prompt_dialog: dialogs::auth_prompt_dialog::DialogState::default(),
+            dial_queue: None,
             upload_dialog: dialogs::upload_dialog::DialogState::default(),
             dialing_directory_dialog: dialogs::dialing_directory_dialog::DialogState::new(addresses),
             drag_start: None,
@@ -173,6 +176,7 @@ impl eframe::App for MainWindow {
                 self.handle_terminal_key_binds(ctx);
                 self.update_terminal_window(ctx, frame, false);
                 check_error!(self, res, false);
+                dialogs::redial_dialog::show_dialog(self, ctx);
                 ctx.request_repaint_after(Duration::from_millis(150));
             }
             MainWindowMode::ShowDialingDirectory => {
@@ -257,11 +261,25 @@ impl eframe::App for MainWindow {
                 ctx.request_repaint_after(Duration::from_millis(150));
             } // MainWindowMode::AskDeleteEntry => todo!(),
 
-            MainWindowMode::ShowDisconnectedMessage(time, system) => {
+            MainWindowMode::ShowUnknownHost(unknown_host) => {
                 let res = self.update_state(ctx);
                 self.update_terminal_window(ctx, frame, false);
                 check_error!(self, res, false);
-                dialogs::show_disconnected_message::show_disconnected(self, ctx, time, system);
+                dialogs::show_unknown_host::show_dialog(self, ctx, &unknown_host);
+            }
+
+            MainWindowMode::ShowAuthPrompt(auth_prompt) => {
+                let res = self.update_state(ctx);
+                self.update_terminal_window(ctx, frame, false);
+                check_error!(self, res, false);
+                dialogs::auth_prompt_dialog::show_dialog(self, ctx, &auth_prompt);
+            }
+
+            MainWindowMode::ShowDisconnectedMessage(time, system, reason) => {
+                let res = self.update_state(ctx);
+                self.update_terminal_window(ctx, frame, false);
+                check_error!(self, res, false);
+                dialogs::show_disconnected_message::show_disconnected(self, ctx, time, system, reason.as_ref());
                 ctx.request_repaint_after(Duration::from_millis(150));
             }
         }
@@ -1,12 +1,12 @@
 use crate::{
     features::{AutoFileTransfer, AutoLogin},
     protocol::TransferType,
-    util::SoundThread,
+    util::{CharacterSetDecoder, SoundThread},
     Terminal, TerminalResult,
 };
 use egui::mutex::Mutex;
 use icy_engine::{
-    ansi::{self, MusicOption},
+    ansi::{self, BaudEmulation, MusicOption},
     rip::bgi::MouseField,
     BufferParser, Caret,
 };
@@ -19,6 +19,9 @@ use super::{
     dialogs,
 };
 
+/// Interval the state gets updated with while nothing is received
+const IDLE_INTERVAL: Duration = Duration::from_millis(10);
+
 pub struct BufferUpdateThread {
     pub capture_dialog: dialogs::capture_dialog::DialogState,
 
@@ -37,12 +40,20 @@ pub struct BufferUpdateThread {
     pub mouse_field: Vec<MouseField>,
 
     pub cache_directory: PathBuf,
+
+    /// Decodes received data while a character set is agreed on with the server
+    pub character_set_decoder: Option<CharacterSetDecoder>,
 }
 
 impl BufferUpdateThread {
     pub fn get_data(&self) -> TerminalResult<Vec<u8>> {
+        let mut connect_speed = None;
         let data = if let Some(con) = self.connection.lock().as_mut() {
             con.update_state()?;
+            connect_speed = con.take_connect_speed();
+            if let Some(speed) = connect_speed {
+                con.set_baud_rate(speed)?;
+            }
             if con.is_disconnected() {
                 return Ok(Vec::new());
             }
@@ -55,6 +66,13 @@ impl BufferUpdateThread {
             Vec::new()
         };
 
+        if let Some(speed) = connect_speed {
+            self.buffer_view
+                .lock()
+                .get_buffer_mut()
+                .terminal_state
+                .set_baud_rate(BaudEmulation::Rate(speed));
+        }
         Ok(data)
     }
     pub fn update_state(&mut self, ctx: &egui::Context, buffer_parser: &mut dyn BufferParser, data: &[u8]) -> TerminalResult<(u64, usize)> {
@@ -88,6 +106,11 @@ impl BufferUpdateThread {
             mem::swap(&mut caret, self.buffer_view.lock().get_caret_mut());
         }
 
+        let character_set = self.connection.lock().as_ref().and_then(|con| con.get_character_set());
+        if self.character_set_decoder.as_ref().map(CharacterSetDecoder::get_character_set) != character_set {
+            self.character_set_decoder = character_set.map(CharacterSetDecoder::new);
+        }
+
         let mut idx = 0;
         for ch in data {
             let ch = *ch;
@@ -119,7 +142,14 @@ impl BufferUpdateThread {
                 }
             }*/
             self.capture_dialog.append_data(ch);
-            let (p, ms) = self.print_char(&mut self.buffer_view.lock(), buffer_parser, ch);
+            let printed = match &mut self.character_set_decoder {
+                Some(decoder) => decoder.push_byte(ch),
+                None => Some(ch as char),
+            };
+            let (p, ms) = match printed {
+                Some(printed) => self.print_char(&mut self.buffer_view.lock(), buffer_parser, printed),
+                None => (false, 0),
+            };
             idx += 1;
 
             if p {
@@ -140,11 +170,11 @@ impl BufferUpdateThread {
         }
     }
 
-    pub fn print_char(&self, buffer_view: &mut BufferView, buffer_parser: &mut dyn BufferParser, c: u8) -> (bool, u32) {
+    pub fn print_char(&self, buffer_view: &mut BufferView, buffer_parser: &mut dyn BufferParser, c: char) -> (bool, u32) {
         let mut caret: Caret = Caret::default();
         mem::swap(&mut caret, buffer_view.get_caret_mut());
         let buffer = buffer_view.get_buffer_mut();
-        let result = buffer_parser.print_char(buffer, 0, &mut caret, c as char);
+        let result = buffer_parser.print_char(buffer, 0, &mut caret, c);
         mem::swap(&mut caret, buffer_view.get_caret_mut());
 
         match result {
@@ -191,8 +221,16 @@ impl BufferUpdateThread {
                     }
                 }
             }
-            icy_engine::CallbackAction::ResizeTerminal(_, _) => {
+            icy_engine::CallbackAction::ResizeTerminal(width, height) => {
                 buffer_view.redraw_view();
+                if let Some(con) = self.connection.lock().as_mut() {
+                    if con.is_connected() {
+                        let r = con.set_window_size(icy_engine::Size::new(width, height));
+                        if let Err(r) = r {
+                            log::error!("callbackaction::ResizeTerminal: {r}");
+                        }
+                    }
+                }
             }
 
             icy_engine::CallbackAction::NoUpdate => {
@@ -261,7 +299,14 @@ pub fn run_update_thread(ctx: &egui::Context, update_thread: Arc<Mutex<BufferUpd
                     }
                 }
             } else {
-                thread::sleep(Duration::from_millis(10));
+                // wakes up as soon as the com thread sent something
+                let data_signal = update_thread.lock().connection.lock().as_ref().map(|con| con.get_data_signal());
+                match data_signal {
+                    Some(data_signal) => {
+                        data_signal.wait(IDLE_INTERVAL);
+                    }
+                    None => thread::sleep(IDLE_INTERVAL),
+                }
             }
         }
     })
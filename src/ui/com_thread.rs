@@ -5,151 +5,464 @@ use std::sync::mpsc::{self};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_thread as thread;
-use web_time::Instant;
+use web_time::{Duration, Instant};
 
-use crate::com::{Com, TermComResult};
+use crate::com::{
+    dial_protocol, dial_result_for_error, telnet_option, AuthPrompt, Com, ConnectionError, LineStatus, ModemAction, ModemOutput, ResultCode, TermComResult,
+    UnknownHost, VirtualModem,
+};
 
-use super::connect::{Connection, OpenConnectionData, SendData};
+use super::connect::{ChannelSender, Connection, DataSignal, OpenConnectionData, SendData};
 use super::MainWindow;
 
 const BITS_PER_BYTE: u32 = 8;
+const LINE_STATUS_INTERVAL: Duration = Duration::from_millis(200);
+/// Interval the received data is passed on with while the baud rate gets emulated
+const BAUD_EMULATION_INTERVAL: Duration = Duration::from_millis(10);
+/// Connections that can't be waited on (serial ports, other platforms than unix) get read with this interval
+const READ_INTERVAL: Duration = Duration::from_millis(10);
+/// Reads before the ui messages get handled again, keeps keystrokes flowing while a lot of data is received
+const MAX_READS_PER_WAKEUP: usize = 64;
 
 pub struct ConnectionThreadData {
-    tx: mpsc::Sender<SendData>,
+    tx: ChannelSender,
     rx: mpsc::Receiver<SendData>,
+    /// Receiving end of the socket the ui writes to after each message, see [`ConnectionThreadData::wait`]
+    #[cfg(unix)]
+    wakeup: Option<std::os::unix::net::UnixStream>,
     com: Box<dyn Com>,
     thread_is_running: bool,
     is_connected: bool,
+    /// The read limit got hit, the connection has more data
+    read_pending: bool,
+    /// Messages of the ui that arrived while a dialog was waited for, handled before the next ones from the channel
+    pending_messages: VecDeque<SendData>,
 
     // used for baud rate emulation
     data_buffer: VecDeque<u8>,
     baud_rate: u32,
     last_send_time: Instant,
+
+    line_status: Option<LineStatus>,
+    last_line_status_poll: Instant,
+
+    /// What the ui got told about the server echoing, see [`ConnectionThreadData::update_remote_echo`]
+    remote_echo: bool,
+    /// The server enabled the telnet ECHO option at some point
+    echo_negotiated: bool,
+
+    virtual_modem: Option<VirtualModem>,
+    /// Settings of the virtual modem session, dialed addresses are connected with them
+    virtual_modem_data: Option<OpenConnectionData>,
 }
 
 impl ConnectionThreadData {
     fn disconnect(&mut self) {
+        if let Err(err) = self.com.disconnect() {
+            log::warn!("Error while disconnecting: {err}");
+        }
         self.is_connected = false;
+        self.line_status = None;
+        self.remote_echo = true;
+        self.echo_negotiated = false;
+        self.virtual_modem = None;
+        self.virtual_modem_data = None;
         self.com = Box::new(crate::com::NullConnection {});
         self.baud_rate = 0;
         self.data_buffer.clear();
         self.thread_is_running &= self.tx.send(SendData::Disconnect).is_ok();
     }
 
+    /// Tells the ui why connecting failed or the connection got lost, then disconnects.
+    fn disconnect_with_error(&mut self, err: ConnectionError) {
+        self.thread_is_running &= self.tx.send(SendData::ConnectionError(err)).is_ok();
+        self.disconnect();
+    }
+
+    /// Reads until the connection has no more data, returns whether anything was read.
     fn read_data(&mut self) -> bool {
-        if self.data_buffer.is_empty() {
-            match self.com.read_data() {
+        self.read_pending = false;
+        self.update_remote_echo();
+        if !self.data_buffer.is_empty() || !self.has_remote_side() {
+            return false;
+        }
+        for _ in 0..MAX_READS_PER_WAKEUP {
+            let result = self.com.read_data();
+            if let Some(character_set) = self.com.take_character_set() {
+                self.thread_is_running &= self.tx.send(SendData::SetCharacterSet(character_set)).is_ok();
+            }
+            self.update_remote_echo();
+            match result {
                 Ok(Some(data)) => {
+                    if data.is_empty() {
+                        continue;
+                    }
                     if self.baud_rate == 0 {
                         if let Err(err) = self.tx.send(SendData::Data(data)) {
                             log::error!("connection_thread::read_data1: {err}");
                             self.thread_is_running &= self.tx.send(SendData::Disconnect).is_ok();
+                            return false;
                         }
                     } else {
+                        // the rest is read once the emulation passed the buffer on
                         self.data_buffer.extend(data);
+                        return true;
                     }
                 }
-                Ok(None) => return false,
+                Ok(None) => return true,
 
                 Err(err) => {
                     log::error!("connection_thread::read_data2: {err}");
-                    self.disconnect();
+                    if self.virtual_modem.is_some() {
+                        self.virtual_modem_carrier_lost();
+                    } else {
+                        self.disconnect_with_error(err);
+                    }
                     return false;
                 }
             }
-        } else if self.baud_rate == 0 {
+        }
+        self.read_pending = true;
+        true
+    }
+
+    /// Tells the ui whether the server echoes. A server that never enabled the telnet ECHO option is expected to echo
+    /// by itself, only turning the option off again (like after a password prompt) switches the ui to local echo.
+    fn update_remote_echo(&mut self) {
+        let enabled = if self.has_remote_side() {
+            self.com.remote_option_enabled(telnet_option::Echo)
+        } else {
+            None
+        };
+        match enabled {
+            Some(true) => self.echo_negotiated = true,
+            Some(false) => {}
+            None => self.echo_negotiated = false,
+        }
+        let remote_echo = !self.echo_negotiated || enabled == Some(true);
+        if remote_echo != self.remote_echo {
+            self.remote_echo = remote_echo;
+            self.thread_is_running &= self.tx.send(SendData::RemoteEcho(remote_echo)).is_ok();
+        }
+    }
+
+    /// The virtual modem in command mode has no remote side to read from.
+    fn has_remote_side(&self) -> bool {
+        self.is_connected && self.virtual_modem.as_ref().is_none_or(VirtualModem::is_online)
+    }
+
+    /// Passes the received data on at the speed of the emulated baud rate.
+    fn send_buffered_data(&mut self) {
+        if self.data_buffer.is_empty() {
+            return;
+        }
+        if self.baud_rate == 0 {
             if let Err(err) = self.tx.send(SendData::Data(self.data_buffer.drain(..).collect())) {
                 log::error!("connection_thread::read_data3: {err}");
                 self.thread_is_running &= self.tx.send(SendData::Disconnect).is_ok();
                 self.disconnect();
             }
-        } else {
-            let cur_time = Instant::now();
-            let bytes_per_sec = self.baud_rate / BITS_PER_BYTE;
-            let elapsed_ms = cur_time.duration_since(self.last_send_time).as_millis() as u32;
-            let bytes_to_send: usize = ((bytes_per_sec.saturating_mul(elapsed_ms)) / 1000).min(self.data_buffer.len() as u32) as usize;
-
-            if bytes_to_send > 0 {
-                if let Err(err) = self.tx.send(SendData::Data(self.data_buffer.drain(..bytes_to_send).collect())) {
-                    log::error!("Error while sending: {err}");
-                    self.thread_is_running &= self.tx.send(SendData::Disconnect).is_ok();
-                }
-                self.last_send_time = cur_time;
+            return;
+        }
+        let cur_time = Instant::now();
+        let bytes_per_sec = self.baud_rate / BITS_PER_BYTE;
+        let elapsed_ms = cur_time.duration_since(self.last_send_time).as_millis() as u32;
+        let bytes_to_send: usize = ((bytes_per_sec.saturating_mul(elapsed_ms)) / 1000).min(self.data_buffer.len() as u32) as usize;
+
+        if bytes_to_send > 0 {
+            if let Err(err) = self.tx.send(SendData::Data(self.data_buffer.drain(..bytes_to_send).collect())) {
+                log::error!("Error while sending: {err}");
+                self.thread_is_running &= self.tx.send(SendData::Disconnect).is_ok();
             }
+            self.last_send_time = cur_time;
+        }
+    }
+
+    fn poll_line_status(&mut self) {
+        if self.last_line_status_poll.elapsed() >= LINE_STATUS_INTERVAL {
+            self.last_line_status_poll = Instant::now();
+            self.update_line_status();
+        }
+    }
+
+    /// Reports changed control lines to the ui and disconnects if the carrier got lost.
+    fn update_line_status(&mut self) {
+        let Some(line_status) = self.com.line_status() else {
+            return;
+        };
+        if self.line_status == Some(line_status) {
+            return;
+        }
+        let carrier_lost = LineStatus::carrier_lost(self.line_status, line_status);
+        self.line_status = Some(line_status);
+        self.thread_is_running &= self.tx.send(SendData::LineStatus(line_status)).is_ok();
+        if carrier_lost {
+            log::info!("Carrier lost");
+            self.disconnect_with_error(ConnectionError::CarrierLost);
+        }
+    }
+
+    fn poll_virtual_modem(&mut self) {
+        if let Some(output) = self.virtual_modem.as_mut().map(|modem| modem.poll(Instant::now())) {
+            self.handle_modem_output(output);
+        }
+    }
+
+    fn handle_modem_output(&mut self, output: ModemOutput) {
+        if !output.remote.is_empty() {
+            if let Err(err) = self.com.send(&output.remote) {
+                log::error!("connection_thread::handle_modem_output: {err}");
+                self.virtual_modem_carrier_lost();
+            }
+        }
+        self.send_to_terminal(output.terminal);
+        match output.action {
+            Some(ModemAction::Dial(address)) => self.dial_virtual_modem(address),
+            Some(ModemAction::HangUp) => self.hang_up_virtual_modem(),
+            None => {}
+        }
+    }
+
+    fn send_to_terminal(&mut self, data: Vec<u8>) {
+        if !data.is_empty() {
+            self.thread_is_running &= self.tx.send(SendData::Data(data)).is_ok();
+        }
+    }
+
+    fn dial_virtual_modem(&mut self, address: String) {
+        let Some(mut connection_data) = self.virtual_modem_data.clone() else {
+            return;
+        };
+        connection_data.protocol = dial_protocol(&address);
+        connection_data.address = address;
+        let result = match self.open_connection(&connection_data) {
+            Ok(()) => ResultCode::Connect(self.com.take_connect_speed()),
+            Err(err) => {
+                log::warn!("Virtual modem dial of {} failed: {err}", connection_data.address);
+                dial_result_for_error(&err)
+            }
+        };
+        if let Some(terminal) = self.virtual_modem.as_mut().map(|modem| modem.dial_result(result)) {
+            self.send_to_terminal(terminal);
+        }
+    }
+
+    /// Closes the dialed connection, the virtual modem stays in command mode.
+    fn hang_up_virtual_modem(&mut self) {
+        if let Err(err) = self.com.disconnect() {
+            log::warn!("Error while hanging up: {err}");
+        }
+        self.com = Box::new(crate::com::NullConnection {});
+        self.data_buffer.clear();
+    }
+
+    fn virtual_modem_carrier_lost(&mut self) {
+        self.hang_up_virtual_modem();
+        if let Some(terminal) = self.virtual_modem.as_mut().map(VirtualModem::carrier_lost) {
+            self.send_to_terminal(terminal);
         }
-        true
     }
 
     fn try_connect(&mut self, connection_data: &OpenConnectionData) -> TermComResult<()> {
         self.com = match connection_data.protocol {
-            crate::addresses::Protocol::Telnet => Box::new(crate::com::ComTelnetImpl::connect(connection_data)?),
-            crate::addresses::Protocol::Raw => Box::new(crate::com::ComRawImpl::connect(connection_data)?),
-            crate::addresses::Protocol::Modem => Box::new(crate::com::ComModemImpl::connect(connection_data)?),
+            crate::addresses::Protocol::Telnet | crate::addresses::Protocol::TelnetTls => Box::new(crate::com::ComTelnetImpl::connect(connection_data)?),
+            crate::addresses::Protocol::Raw | crate::addresses::Protocol::RawTls => Box::new(crate::com::ComRawImpl::connect(connection_data)?),
+            crate::addresses::Protocol::Modem => Box::new(crate::com::ComModemImpl::connect(connection_data, &mut || {
+                Self::is_canceled(&self.rx, &mut self.pending_messages)
+            })?),
+            crate::addresses::Protocol::Serial => Box::new(crate::com::ComSerialImpl::connect(connection_data)?),
+            crate::addresses::Protocol::VirtualModem => {
+                self.virtual_modem = Some(VirtualModem::default());
+                self.virtual_modem_data = Some(connection_data.clone());
+                Box::new(crate::com::NullConnection {})
+            }
             #[cfg(not(target_arch = "wasm32"))]
-            crate::addresses::Protocol::Ssh => Box::new(crate::com::ssh::SSHComImpl::connect(connection_data)?),
+            crate::addresses::Protocol::Ssh => Box::new(crate::com::ssh::SSHComImpl::connect(connection_data, &mut |prompt| {
+                Self::ask_auth_prompt(&self.tx, &self.rx, &mut self.pending_messages, prompt)
+            })?),
             crate::addresses::Protocol::WebSocket(_) => {
                 #[cfg(target_arch = "wasm32")] //TODO
                 panic!("WebSocket is not supported on web");
 
                 Box::new(crate::com::websocket::WebSocketComImpl::connect(connection_data)?)
             }
+            #[cfg(not(target_arch = "wasm32"))]
+            crate::addresses::Protocol::Rlogin => Box::new(crate::com::rlogin::ComRloginImpl::connect(connection_data)?),
             #[cfg(target_arch = "wasm32")]
-            crate::addresses::Protocol::Ssh => Box::new(crate::com::NullConnection {}),
+            crate::addresses::Protocol::Ssh | crate::addresses::Protocol::Rlogin => Box::new(crate::com::NullConnection {}),
+            #[cfg(target_os = "linux")]
+            crate::addresses::Protocol::LocalProcess => Box::new(crate::com::local_process::ComLocalProcessImpl::connect(connection_data)?),
+            #[cfg(not(target_os = "linux"))]
+            crate::addresses::Protocol::LocalProcess => return Err(ConnectionError::Unsupported("local processes are only supported on linux".to_string())),
         };
         Ok(())
     }
 
+    /// Connects and asks the user about unknown hosts, a trusted host gets connected again.
+    fn open_connection(&mut self, connection_data: &OpenConnectionData) -> TermComResult<()> {
+        let mut connection_data = connection_data.clone();
+        loop {
+            match self.try_connect(&connection_data) {
+                Err(ConnectionError::UnknownHost(unknown_host)) => {
+                    if connection_data.trusted_fingerprint.as_ref() == Some(&unknown_host.fingerprint) || !self.verify_host((*unknown_host).clone()) {
+                        return Err(ConnectionError::UnknownHost(unknown_host));
+                    }
+                    connection_data.trusted_fingerprint = Some(unknown_host.fingerprint.clone());
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Blocks until the user trusted or rejected the host, a disconnect rejects it.
+    fn verify_host(&mut self, unknown_host: UnknownHost) -> bool {
+        if self.tx.send(SendData::VerifyHost(unknown_host)).is_err() {
+            self.thread_is_running = false;
+            return false;
+        }
+        loop {
+            match self.rx.recv() {
+                Ok(SendData::HostVerified(trust)) => return trust,
+                Ok(SendData::Disconnect) => return false,
+                Ok(data) => self.pending_messages.push_back(data),
+                Err(err) => {
+                    log::error!("Error while waiting for host verification: {err}");
+                    self.thread_is_running = false;
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Blocks until the user answered the prompt, `None` if the login got canceled or a disconnect arrived.
+    /// Other messages are kept in `pending_messages`.
+    fn ask_auth_prompt(
+        tx: &ChannelSender,
+        rx: &mpsc::Receiver<SendData>,
+        pending_messages: &mut VecDeque<SendData>,
+        prompt: AuthPrompt,
+    ) -> Option<Vec<String>> {
+        tx.send(SendData::AuthPrompt(prompt)).ok()?;
+        loop {
+            match rx.recv() {
+                Ok(SendData::AuthPromptAnswer(answers)) => return answers,
+                Ok(SendData::Disconnect) => return None,
+                Ok(data) => pending_messages.push_back(data),
+                Err(err) => {
+                    log::error!("Error while waiting for login prompt answers: {err}");
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Checks for a disconnect while connecting, other messages are kept in `pending_messages`.
+    fn is_canceled(rx: &mpsc::Receiver<SendData>, pending_messages: &mut VecDeque<SendData>) -> bool {
+        loop {
+            match rx.try_recv() {
+                Ok(SendData::Disconnect) => return true,
+                Ok(data) => pending_messages.push_back(data),
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => return true,
+            }
+        }
+    }
+
     pub fn handle_receive(&mut self) {
         loop {
-            match self.rx.try_recv() {
-                Ok(SendData::OpenConnection(connection_data)) => match self.try_connect(&connection_data) {
+            let data = match self.pending_messages.pop_front() {
+                Some(data) => Ok(data),
+                None => self.rx.try_recv(),
+            };
+            match data {
+                Ok(data) => self.handle_data(data),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(err) => {
+                    log::error!("Error while receiving: {err}");
+                    self.thread_is_running = false;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_data(&mut self, data: SendData) {
+        match data {
+            SendData::OpenConnection(connection_data) => {
+                self.virtual_modem = None;
+                self.virtual_modem_data = None;
+                match self.open_connection(&connection_data) {
                     Ok(()) => {
                         self.thread_is_running &= self.tx.send(SendData::Connected).is_ok();
+                        if let Some(speed) = self.com.take_connect_speed() {
+                            self.thread_is_running &= self.tx.send(SendData::ConnectSpeed(speed)).is_ok();
+                        }
                         self.is_connected = true;
                     }
                     Err(err) => {
-                        self.thread_is_running &= self.tx.send(SendData::ConnectionError(err.to_string())).is_ok();
-                        self.disconnect();
-                    }
-                },
-                Ok(SendData::Data(buf)) => {
-                    if let Err(err) = self.com.send(&buf) {
-                        log::error!("connection_thread::handle_receive: {err}");
-                        let _ = self.tx.send(SendData::Disconnect);
-                        self.disconnect();
+                        self.disconnect_with_error(err);
                     }
                 }
-
-                Ok(SendData::SetBaudRate(baud)) => {
-                    self.baud_rate = baud;
+            }
+            SendData::Data(buf) => {
+                if let Some(modem) = &mut self.virtual_modem {
+                    let output = modem.input(&buf, Instant::now());
+                    self.handle_modem_output(output);
+                } else if let Err(err) = self.com.send(&buf) {
+                    log::error!("connection_thread::handle_receive: {err}");
+                    self.disconnect_with_error(err);
                 }
+            }
 
-                Ok(SendData::SetRawMode(raw_transfer)) => {
-                    self.com.set_raw_mode(raw_transfer);
+            SendData::SetBaudRate(baud) => {
+                self.baud_rate = baud;
+            }
+
+            SendData::SetRawMode(raw_transfer) => {
+                self.com.set_raw_mode(raw_transfer);
+            }
+            SendData::SetWindowSize(window_size) => {
+                if let Err(err) = self.com.set_window_size(window_size) {
+                    log::error!("connection_thread::set_window_size: {err}");
                 }
-                Ok(SendData::Disconnect) => {
-                    self.disconnect();
+            }
+            SendData::SendBreak => {
+                if let Err(err) = self.com.send_break() {
+                    log::error!("connection_thread::send_break: {err}");
                 }
-                Ok(_) => {}
-                Err(mpsc::TryRecvError::Empty) => break,
-                Err(err) => {
-                    log::error!("Error while receiving: {err}");
-                    break;
+            }
+            SendData::SetControlLines(dtr, rts) => {
+                if let Err(err) = self.com.set_control_lines(dtr, rts) {
+                    log::error!("connection_thread::set_control_lines: {err}");
                 }
             }
+            SendData::Disconnect => {
+                self.disconnect();
+            }
+            _ => {}
         }
     }
 
-    fn new(tx: mpsc::Sender<SendData>, rx: mpsc::Receiver<SendData>) -> Self {
+    fn new(tx: ChannelSender, rx: mpsc::Receiver<SendData>) -> Self {
         Self {
             tx,
             rx,
+            #[cfg(unix)]
+            wakeup: None,
             baud_rate: 0,
             com: Box::new(crate::com::NullConnection {}),
             data_buffer: VecDeque::<u8>::new(),
             last_send_time: Instant::now(),
+            line_status: None,
+            last_line_status_poll: Instant::now(),
+            remote_echo: true,
+            echo_negotiated: false,
+            virtual_modem: None,
+            virtual_modem_data: None,
             thread_is_running: true,
             is_connected: false,
+            read_pending: false,
+            pending_messages: VecDeque::new(),
         }
     }
 
@@ -157,41 +470,649 @@ impl ConnectionThreadData {
     pub fn poll(&mut self) {
         if self.is_connected {
             self.read_data();
+            self.send_buffered_data();
+            self.poll_line_status();
+            self.poll_virtual_modem();
         }
         self.handle_receive();
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run(&mut self) {
+        while self.thread_is_running {
+            if self.is_connected {
+                self.read_data();
+                self.send_buffered_data();
+                self.poll_line_status();
+                self.poll_virtual_modem();
+            }
+            self.handle_receive();
+            if self.thread_is_running {
+                let timeout = self.next_timeout();
+                self.wait(timeout);
+            }
+        }
+    }
+
+    /// How long the thread may sleep until something needs to be done without new data, `None` if nothing is scheduled.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn next_timeout(&self) -> Option<Duration> {
+        if !self.is_connected {
+            return None;
+        }
+        if self.read_pending {
+            return Some(Duration::ZERO);
+        }
+        let now = Instant::now();
+        let mut timeout = if self.data_buffer.is_empty() {
+            (self.has_remote_side() && self.get_poll_fd().is_none()).then_some(READ_INTERVAL)
+        } else {
+            Some(BAUD_EMULATION_INTERVAL)
+        };
+        let mut schedule = |deadline: Instant| {
+            let remaining = deadline.saturating_duration_since(now);
+            timeout = Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)));
+        };
+        if self.line_status.is_some() {
+            schedule(self.last_line_status_poll + LINE_STATUS_INTERVAL);
+        }
+        if let Some(deadline) = self.virtual_modem.as_ref().and_then(VirtualModem::next_poll) {
+            schedule(deadline);
+        }
+        timeout
+    }
+
+    /// The descriptor to wait on for incoming data, `None` if nothing should be read right now or the connection can't be waited on.
+    #[cfg(unix)]
+    fn get_poll_fd(&self) -> Option<std::os::fd::RawFd> {
+        if !self.data_buffer.is_empty() || !self.has_remote_side() {
+            return None;
+        }
+        self.com.get_poll_fd()
+    }
+
+    #[cfg(all(not(unix), not(target_arch = "wasm32")))]
+    fn get_poll_fd(&self) -> Option<()> {
+        None
+    }
+
+    /// Sleeps until the connection has data, the ui sent something or the timeout elapsed.
+    ///
+    /// On unix the thread waits with poll(2) on the connection and a socket the ui writes to after each message.
+    /// Otherwise it waits on the channel of the ui and reads the connection in intervals.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn wait(&mut self, timeout: Option<Duration>) {
+        #[cfg(unix)]
+        if let (Some(fd), Some(wakeup_fd)) = (self.get_poll_fd(), self.wakeup.as_ref().map(std::os::fd::AsRawFd::as_raw_fd)) {
+            if let Err(err) = wait_readable([fd, wakeup_fd], timeout) {
+                log::error!("connection_thread::wait: {err}");
+                self.wait_on_channel(Some(READ_INTERVAL));
+            }
+            if let Some(wakeup) = &self.wakeup {
+                let mut buf = [0; 64];
+                while matches!(std::io::Read::read(&mut &*wakeup, &mut buf), Ok(size) if size > 0) {}
+            }
+            return;
+        }
+        self.wait_on_channel(timeout);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn wait_on_channel(&mut self, timeout: Option<Duration>) {
+        let result = match timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout),
+            None => self.rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+        };
+        match result {
+            Ok(data) => self.handle_data(data),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(err) => {
+                log::error!("Error while receiving: {err}");
+                self.thread_is_running = false;
+            }
+        }
+    }
+}
+
+/// Blocks until one of the descriptors is readable or the timeout elapsed.
+#[cfg(unix)]
+fn wait_readable(fds: [std::os::fd::RawFd; 2], timeout: Option<Duration>) -> std::io::Result<()> {
+    let mut poll_fds = fds.map(|fd| libc::pollfd {
+        fd,
+        // rlogin control messages arrive as urgent data
+        events: libc::POLLIN | libc::POLLPRI,
+        revents: 0,
+    });
+    // rounded up, a timeout of 0 would spin until the deadline
+    let timeout = timeout.map_or(-1, |timeout| timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int);
+    if unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, timeout) } == -1 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(())
 }
 
 impl MainWindow {
     #[cfg(not(target_arch = "wasm32"))]
     pub fn start_com_thread() -> Connection {
-        use web_time::Duration;
-
         let (tx, rx) = mpsc::channel::<SendData>();
         let (tx2, rx2) = mpsc::channel::<SendData>();
+        let data_signal = DataSignal::default();
+        let tx = ChannelSender::with_signal(tx, data_signal.clone());
+        #[cfg(unix)]
+        let (tx2, wakeup) = match create_wakeup_socket() {
+            Ok((sender, receiver)) => (ChannelSender::with_socket(tx2, sender), Some(receiver)),
+            Err(err) => {
+                log::error!("Can't create the wakeup socket, the connection gets polled: {err}");
+                (tx2.into(), None)
+            }
+        };
+        #[cfg(not(unix))]
+        let tx2 = tx2.into();
         if let Err(err) = std::thread::Builder::new().name("com_thread".to_string()).spawn(move || {
-            let mut data: ConnectionThreadData = ConnectionThreadData::new(tx, rx2);
-            while data.thread_is_running {
-                if data.is_connected {
-                    if !data.read_data() {
-                        std::thread::sleep(Duration::from_millis(25));
-                    }
-                } else {
-                    std::thread::sleep(Duration::from_millis(100));
-                }
-                data.handle_receive();
+            let mut data = ConnectionThreadData::new(tx, rx2);
+            #[cfg(unix)]
+            {
+                data.wakeup = wakeup;
             }
+            data.run();
             log::error!("communication thread closed because it lost connection with the ui thread.");
         }) {
             log::error!("error in communication thread: {}", err);
         }
-        Connection::new(rx, tx2)
+        Connection::new(rx, tx2, data_signal)
     }
 
     #[cfg(target_arch = "wasm32")]
     pub fn start_poll_thead() -> (Connection, ConnectionThreadData) {
         let (tx, rx) = mpsc::channel::<SendData>();
         let (tx2, rx2) = mpsc::channel::<SendData>();
-        (Connection::new(rx, tx2), ConnectionThreadData::new(tx, rx2))
+        let data_signal = DataSignal::default();
+        (
+            Connection::new(rx, tx2.into(), data_signal.clone()),
+            ConnectionThreadData::new(ChannelSender::with_signal(tx, data_signal), rx2),
+        )
+    }
+}
+
+/// The ui writes to the first socket after each message, the com thread waits on the second one.
+#[cfg(unix)]
+fn create_wakeup_socket() -> std::io::Result<(std::os::unix::net::UnixStream, std::os::unix::net::UnixStream)> {
+    let (sender, receiver) = std::os::unix::net::UnixStream::pair()?;
+    sender.set_nonblocking(true)?;
+    receiver.set_nonblocking(true)?;
+    Ok((sender, receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addresses::Terminal;
+
+    /// Reports the given line states one after another.
+    struct LineStatusCom {
+        states: VecDeque<LineStatus>,
+        disconnected: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl Com for LineStatusCom {
+        fn get_name(&self) -> &'static str {
+            "test"
+        }
+
+        fn default_port(&self) -> u16 {
+            0
+        }
+
+        fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
+            Ok(buf.len())
+        }
+
+        fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        fn set_terminal_type(&mut self, _terminal: Terminal) {}
+
+        fn disconnect(&mut self) -> TermComResult<()> {
+            self.disconnected.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn line_status(&mut self) -> Option<LineStatus> {
+            self.states.pop_front()
+        }
+    }
+
+    #[test]
+    fn test_carrier_loss_disconnects() {
+        let (tx, rx) = mpsc::channel();
+        let (_tx2, rx2) = mpsc::channel();
+        let disconnected = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let up = LineStatus {
+            dcd: true,
+            dsr: true,
+            cts: true,
+            ri: false,
+        };
+        let mut data = ConnectionThreadData::new(tx.into(), rx2);
+        data.com = Box::new(LineStatusCom {
+            states: VecDeque::from([up, up, LineStatus { cts: false, ..up }, LineStatus { dcd: false, ..up }]),
+            disconnected: disconnected.clone(),
+        });
+        data.is_connected = true;
+
+        data.update_line_status();
+        assert!(matches!(rx.try_recv(), Ok(SendData::LineStatus(status)) if status == up));
+        data.update_line_status();
+        assert!(rx.try_recv().is_err(), "unchanged lines aren't reported");
+        data.update_line_status();
+        assert!(matches!(rx.try_recv(), Ok(SendData::LineStatus(status)) if !status.cts));
+        assert!(data.is_connected);
+
+        data.update_line_status();
+        assert!(matches!(rx.try_recv(), Ok(SendData::LineStatus(status)) if !status.dcd));
+        assert!(matches!(rx.try_recv(), Ok(SendData::ConnectionError(ConnectionError::CarrierLost))));
+        assert!(matches!(rx.try_recv(), Ok(SendData::Disconnect)));
+        assert!(!data.is_connected);
+        assert!(disconnected.load(std::sync::atomic::Ordering::SeqCst), "the port got hung up");
+    }
+
+    /// Reports the telnet ECHO state the test sets.
+    struct EchoCom {
+        echo: std::sync::Arc<std::sync::Mutex<Option<bool>>>,
+    }
+
+    impl Com for EchoCom {
+        fn get_name(&self) -> &'static str {
+            "test"
+        }
+
+        fn default_port(&self) -> u16 {
+            0
+        }
+
+        fn send(&mut self, buf: &[u8]) -> TermComResult<usize> {
+            Ok(buf.len())
+        }
+
+        fn read_data(&mut self) -> TermComResult<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        fn set_terminal_type(&mut self, _terminal: Terminal) {}
+
+        fn disconnect(&mut self) -> TermComResult<()> {
+            Ok(())
+        }
+
+        fn remote_option_enabled(&self, opt: u8) -> Option<bool> {
+            assert_eq!(telnet_option::Echo, opt);
+            *self.echo.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_remote_echo() {
+        let (tx, rx) = mpsc::channel();
+        let (_tx2, rx2) = mpsc::channel();
+        let echo = std::sync::Arc::new(std::sync::Mutex::new(Some(false)));
+        let mut data = ConnectionThreadData::new(tx.into(), rx2);
+        data.com = Box::new(EchoCom { echo: echo.clone() });
+        data.is_connected = true;
+        let mut set_echo = |state| {
+            *echo.lock().unwrap() = state;
+            data.read_data();
+            rx.try_iter()
+                .filter_map(|msg| match msg {
+                    SendData::RemoteEcho(remote_echo) => Some(remote_echo),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // the server never negotiated ECHO, it's expected to echo by itself
+        assert!(set_echo(Some(false)).is_empty());
+        assert!(set_echo(Some(true)).is_empty());
+        assert_eq!(vec![false], set_echo(Some(false)));
+        assert_eq!(vec![true], set_echo(Some(true)));
+        assert_eq!(vec![false], set_echo(Some(false)));
+        assert_eq!(vec![true], set_echo(None));
+    }
+
+    #[test]
+    fn test_messages_during_host_verification() {
+        let (tx, rx) = mpsc::channel();
+        let (tx2, rx2) = mpsc::channel();
+        let mut data = ConnectionThreadData::new(tx.into(), rx2);
+        let unknown_host = UnknownHost {
+            kind: crate::com::HostIdentity::SshHostKey,
+            host: "localhost:22".to_string(),
+            reason: String::new(),
+            fingerprint: "fingerprint".to_string(),
+            known_fingerprint: None,
+        };
+
+        tx2.send(SendData::SetBaudRate(2400)).unwrap();
+        tx2.send(SendData::HostVerified(true)).unwrap();
+        assert!(data.verify_host(unknown_host.clone()));
+        assert!(matches!(rx.try_recv(), Ok(SendData::VerifyHost(_))));
+        assert_eq!(0, data.baud_rate, "handled after the answer");
+        data.handle_receive();
+        assert_eq!(2400, data.baud_rate);
+
+        tx2.send(SendData::Disconnect).unwrap();
+        assert!(!data.verify_host(unknown_host));
+    }
+
+    #[test]
+    fn test_messages_during_auth_prompt() {
+        let (tx, rx) = mpsc::channel();
+        let (tx2, rx2) = mpsc::channel();
+        let tx: ChannelSender = tx.into();
+        let mut pending_messages = VecDeque::new();
+        let prompt = AuthPrompt {
+            host: "localhost:22".to_string(),
+            name: String::new(),
+            instruction: String::new(),
+            prompts: vec![("Password: ".to_string(), false)],
+        };
+
+        tx2.send(SendData::Data(b"dir".to_vec())).unwrap();
+        tx2.send(SendData::AuthPromptAnswer(Some(vec!["secret".to_string()]))).unwrap();
+        let answers = ConnectionThreadData::ask_auth_prompt(&tx, &rx2, &mut pending_messages, prompt.clone());
+        assert_eq!(Some(vec!["secret".to_string()]), answers);
+        assert!(matches!(rx.try_recv(), Ok(SendData::AuthPrompt(_))));
+        assert!(matches!(pending_messages.pop_front(), Some(SendData::Data(data)) if data == b"dir"));
+
+        tx2.send(SendData::Disconnect).unwrap();
+        assert_eq!(None, ConnectionThreadData::ask_auth_prompt(&tx, &rx2, &mut pending_messages, prompt));
+        assert!(pending_messages.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_while_connecting() {
+        let (tx, rx) = mpsc::channel();
+        let mut pending_messages = VecDeque::new();
+        assert!(!ConnectionThreadData::is_canceled(&rx, &mut pending_messages));
+
+        tx.send(SendData::SetBaudRate(2400)).unwrap();
+        assert!(!ConnectionThreadData::is_canceled(&rx, &mut pending_messages));
+        assert!(matches!(pending_messages.pop_front(), Some(SendData::SetBaudRate(2400))));
+
+        tx.send(SendData::Data(b"ATZ".to_vec())).unwrap();
+        tx.send(SendData::Disconnect).unwrap();
+        assert!(ConnectionThreadData::is_canceled(&rx, &mut pending_messages));
+        assert!(matches!(pending_messages.pop_front(), Some(SendData::Data(data)) if data == b"ATZ"));
+
+        drop(tx);
+        assert!(ConnectionThreadData::is_canceled(&rx, &mut pending_messages));
+    }
+
+    /// Collects the terminal output until it contains `expected`.
+    fn wait_for_output(data: &mut ConnectionThreadData, rx: &mpsc::Receiver<SendData>, expected: &str) -> String {
+        let mut output = String::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !output.contains(expected) {
+            assert!(Instant::now() < deadline, "'{expected}' not received, got '{output}'");
+            data.read_data();
+            data.poll_virtual_modem();
+            while let Ok(msg) = rx.try_recv() {
+                if let SendData::Data(buf) = msg {
+                    output.push_str(&String::from_utf8_lossy(&buf));
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        output
+    }
+
+    #[test]
+    fn test_virtual_modem_dial() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"Welcome").unwrap();
+            let mut buf = [0; 2];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(b"hi", &buf);
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let (_tx2, rx2) = mpsc::channel();
+        let mut data = ConnectionThreadData::new(tx.into(), rx2);
+        let mut address = crate::Address::new("test");
+        address.protocol = crate::addresses::Protocol::VirtualModem;
+        let connection_data = OpenConnectionData::from(&address, Duration::from_secs(1), icy_engine::Size::new(80, 25), None, &crate::Proxy::default());
+        data.open_connection(&connection_data).unwrap();
+        data.is_connected = true;
+
+        assert!(!data.read_data(), "nothing is read in command mode");
+        let output = data
+            .virtual_modem
+            .as_mut()
+            .unwrap()
+            .input(format!("ATDT raw://127.0.0.1:{port}\r").as_bytes(), Instant::now());
+        data.handle_modem_output(output);
+        let output = wait_for_output(&mut data, &rx, "Welcome");
+        assert!(output.contains("\r\nCONNECT\r\n"), "{output}");
+
+        let output = data.virtual_modem.as_mut().unwrap().input(b"hi", Instant::now());
+        data.handle_modem_output(output);
+        server.join().unwrap();
+        wait_for_output(&mut data, &rx, "NO CARRIER");
+        assert!(data.is_connected, "the virtual modem stays connected after the remote side hung up");
+
+        let output = data
+            .virtual_modem
+            .as_mut()
+            .unwrap()
+            .input(format!("ATDT 127.0.0.1:{port}\r").as_bytes(), Instant::now());
+        data.handle_modem_output(output);
+        let output = wait_for_output(&mut data, &rx, "\r\n");
+        assert!(output.contains("BUSY") || output.contains("NO CARRIER"), "{output}");
+    }
+
+    /// Moves data through the com thread over loopback in blocks like a file transfer does, then echoes single bytes.
+    #[test]
+    fn test_loopback_transfer() {
+        use crate::ui::connect::DataConnection;
+        use std::io::{Read, Write};
+
+        const SIZE: usize = 8 * 1024 * 1024;
+        const BLOCK_SIZE: usize = 1024;
+        const ROUND_TRIPS: usize = 200;
+        // a prime length doesn't line up with the blocks, lost or reordered data shows up
+        let pattern = |offset: usize| (offset % 251) as u8;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (upload_done_tx, upload_done) = mpsc::channel();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for block in 0..SIZE / BLOCK_SIZE {
+                let data: Vec<u8> = (0..BLOCK_SIZE).map(|i| pattern(block * BLOCK_SIZE + i)).collect();
+                stream.write_all(&data).unwrap();
+            }
+
+            let mut buf = vec![0; 64 * 1024];
+            let mut received = 0;
+            while received < SIZE {
+                let size = stream.read(&mut buf).unwrap();
+                assert!(size > 0, "upload incomplete, got {received} bytes");
+                for (i, b) in buf[..size].iter().enumerate() {
+                    assert_eq!(pattern(received + i), *b, "upload differs at {}", received + i);
+                }
+                received += size;
+            }
+            upload_done_tx.send(()).unwrap();
+
+            let mut byte = [0];
+            for _ in 0..ROUND_TRIPS {
+                stream.read_exact(&mut byte).unwrap();
+                stream.write_all(&byte).unwrap();
+            }
+        });
+
+        let mut connection = MainWindow::start_com_thread();
+        let mut address = crate::Address::new("test");
+        address.address = format!("127.0.0.1:{port}");
+        address.protocol = crate::addresses::Protocol::Raw;
+        connection
+            .connect(&address, Duration::from_secs(5), icy_engine::Size::new(80, 25), None, &crate::Proxy::default())
+            .unwrap();
+        wait_for_connection(&mut connection);
+
+        for block in 0..SIZE / BLOCK_SIZE {
+            let expected: Vec<u8> = (0..BLOCK_SIZE).map(|i| pattern(block * BLOCK_SIZE + i)).collect();
+            assert_eq!(expected, connection.read_exact(BLOCK_SIZE).unwrap(), "download differs in block {block}");
+        }
+
+        for block in 0..SIZE / BLOCK_SIZE {
+            connection.send((0..BLOCK_SIZE).map(|i| pattern(block * BLOCK_SIZE + i)).collect()).unwrap();
+        }
+        upload_done.recv_timeout(Duration::from_secs(30)).unwrap();
+
+        for i in 0..ROUND_TRIPS {
+            connection.send(vec![i as u8]).unwrap();
+            assert_eq!(i as u8, connection.read_u8().unwrap());
+        }
+
+        connection.disconnect().unwrap();
+        server.join().unwrap();
+    }
+
+    /// Measures the throughput of the com thread over loopback, run with `cargo test -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_loopback_throughput() {
+        use crate::ui::connect::DataConnection;
+        use std::io::{Read, Write};
+
+        const SIZE: usize = 64 * 1024 * 1024;
+        const BLOCK_SIZE: usize = 8 * 1024;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let block = vec![0x55; BLOCK_SIZE];
+            for _ in 0..SIZE / BLOCK_SIZE {
+                stream.write_all(&block).unwrap();
+            }
+
+            let mut buf = vec![0; 64 * 1024];
+            let mut received = 0;
+            while received < SIZE {
+                let size = stream.read(&mut buf).unwrap();
+                assert!(size > 0, "upload incomplete, got {received} bytes");
+                received += size;
+            }
+            stream.write_all(&[0]).unwrap();
+        });
+
+        let mut connection = MainWindow::start_com_thread();
+        let mut address = crate::Address::new("test");
+        address.address = format!("127.0.0.1:{port}");
+        address.protocol = crate::addresses::Protocol::Raw;
+        connection
+            .connect(&address, Duration::from_secs(5), icy_engine::Size::new(80, 25), None, &crate::Proxy::default())
+            .unwrap();
+        wait_for_connection(&mut connection);
+
+        let mb = SIZE as f64 / (1024.0 * 1024.0);
+        let start = Instant::now();
+        for _ in 0..SIZE / BLOCK_SIZE {
+            connection.read_exact(BLOCK_SIZE).unwrap();
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("download: {mb:.0} MiB in {elapsed:.2}s, {:.1} MiB/s", mb / elapsed);
+
+        let start = Instant::now();
+        for _ in 0..SIZE / BLOCK_SIZE {
+            connection.send(vec![0x55; BLOCK_SIZE]).unwrap();
+        }
+        // the server acknowledges once everything arrived
+        connection.read_u8().unwrap();
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("upload: {mb:.0} MiB in {elapsed:.2}s, {:.1} MiB/s", mb / elapsed);
+
+        connection.disconnect().unwrap();
+        server.join().unwrap();
+    }
+
+    fn wait_for_connection(connection: &mut crate::ui::connect::Connection) {
+        while !connection.is_connected() {
+            connection.update_state().unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// The remote side goes away in the middle of a block, the transfer has to fail instead of waiting for the rest.
+    #[test]
+    fn test_link_drop_during_transfer() {
+        use crate::protocol::{Protocol, TestStorageHandler, TransferState, XYModemVariant, XYmodem};
+        use std::io::{Read, Write};
+        use std::sync::{Arc, Mutex};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut start = [0];
+            stream.read_exact(&mut start).unwrap();
+            // SOH, block 1 and its complement, then only a part of the data
+            let mut block = vec![0x01, 1, 0xFE];
+            block.extend([b'x'; 40]);
+            stream.write_all(&block).unwrap();
+        });
+
+        let mut connection = MainWindow::start_com_thread();
+        let mut address = crate::Address::new("test");
+        address.address = format!("127.0.0.1:{port}");
+        address.protocol = crate::addresses::Protocol::Raw;
+        connection
+            .connect(&address, Duration::from_secs(5), icy_engine::Size::new(80, 25), None, &crate::Proxy::default())
+            .unwrap();
+        wait_for_connection(&mut connection);
+        connection.start_transfer();
+
+        let (result_tx, result_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut protocol = XYmodem::new(XYModemVariant::XModem1k);
+            let transfer_state = Arc::new(Mutex::new(TransferState::default()));
+            let mut storage_handler = TestStorageHandler::new();
+            let mut receive = || -> crate::TerminalResult<()> {
+                protocol.initiate_recv(&mut connection, &mut transfer_state.lock().unwrap())?;
+                while !transfer_state.lock().unwrap().is_finished {
+                    protocol.update(&mut connection, &transfer_state, &mut storage_handler)?;
+                }
+                Ok(())
+            };
+            result_tx.send(receive()).unwrap();
+        });
+
+        let result = result_rx
+            .recv_timeout(Duration::from_secs(30))
+            .expect("transfer didn't end after the link dropped");
+        assert!(result.is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_carrier_lost() {
+        let up = LineStatus {
+            dcd: true,
+            ..Default::default()
+        };
+        assert!(LineStatus::carrier_lost(Some(up), LineStatus::default()));
+        assert!(!LineStatus::carrier_lost(None, LineStatus::default()));
+        assert!(!LineStatus::carrier_lost(Some(LineStatus::default()), LineStatus::default()));
+        assert!(!LineStatus::carrier_lost(Some(up), up));
     }
 }
@@ -1,7 +1,16 @@
-use crate::{Address, Modem, Terminal, TerminalResult};
-use std::{collections::VecDeque, sync::mpsc};
+use crate::{
+    com::{AuthPrompt, ConnectionError, LineStatus, UnknownHost},
+    Address, CharacterSet, Modem, Protocol, Proxy, SshAlgorithms, SshAuthMethod, Terminal, TerminalResult,
+};
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Condvar, Mutex, PoisonError},
+};
 use web_time::{Duration, Instant};
 
+/// How long a blocking read waits for the com thread before it checks the connection state again.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+
 pub trait DataConnection {
     fn is_data_available(&mut self) -> TerminalResult<bool>;
     fn read_buffer(&mut self) -> Vec<u8>;
@@ -16,8 +25,25 @@ pub struct Connection {
     time: Instant,
     is_connected: bool,
     pub rx: mpsc::Receiver<SendData>,
-    pub tx: mpsc::Sender<SendData>,
+    pub tx: ChannelSender,
+    /// Notified by the com thread whenever it sent something
+    data_signal: DataSignal,
     end_transfer: bool,
+    character_set: Option<CharacterSet>,
+    connect_speed: Option<u32>,
+    /// Why the last connect attempt failed
+    connect_error: Option<ConnectionError>,
+    /// Why an established connection got lost, `None` if it was closed locally
+    disconnect_reason: Option<ConnectionError>,
+    line_status: Option<LineStatus>,
+    /// Whether the server echoes the typed characters
+    remote_echo: bool,
+    /// DTR and RTS of a serial connection, raised after connecting
+    control_lines: (bool, bool),
+    /// Whether the connection goes through a serial port (serial or modem)
+    is_serial: bool,
+    unknown_host: Option<UnknownHost>,
+    auth_prompt: Option<AuthPrompt>,
     buf: std::collections::VecDeque<u8>,
 }
 
@@ -37,15 +63,15 @@ impl DataConnection for Connection {
 
     fn read_u8(&mut self) -> TerminalResult<u8> {
         while !self.is_data_available()? {
-            std::thread::sleep(Duration::from_millis(10));
+            self.wait_for_data()?;
         }
         Ok(self.buf.pop_front().unwrap())
     }
 
     fn read_exact(&mut self, size: usize) -> TerminalResult<Vec<u8>> {
+        self.fill_buffer()?;
         while self.buf.len() < size {
-            self.fill_buffer()?;
-            std::thread::sleep(Duration::from_millis(10));
+            self.wait_for_data()?;
         }
 
         Ok(self.buf.drain(0..size).collect())
@@ -62,17 +88,33 @@ impl DataConnection for Connection {
 }
 
 impl Connection {
-    pub fn new(rx: mpsc::Receiver<SendData>, tx: mpsc::Sender<SendData>) -> Self {
+    pub fn new(rx: mpsc::Receiver<SendData>, tx: ChannelSender, data_signal: DataSignal) -> Self {
         Self {
             time: Instant::now(),
             is_connected: false,
             end_transfer: false,
+            character_set: None,
+            connect_speed: None,
+            connect_error: None,
+            disconnect_reason: None,
+            line_status: None,
+            remote_echo: true,
+            control_lines: (true, true),
+            is_serial: false,
+            unknown_host: None,
+            auth_prompt: None,
             rx,
             tx,
+            data_signal,
             buf: VecDeque::new(),
         }
     }
 
+    /// The signal the com thread notifies, lets other threads wait for data without locking the connection.
+    pub fn get_data_signal(&self) -> DataSignal {
+        self.data_signal.clone()
+    }
+
     pub fn should_end_transfer(&mut self) -> bool {
         self.fill_buffer().unwrap_or_default();
         self.end_transfer
@@ -86,6 +128,71 @@ impl Connection {
         self.fill_buffer()
     }
 
+    /// Returns the character set agreed on with the server, `None` means the terminal's own one.
+    pub fn get_character_set(&self) -> Option<CharacterSet> {
+        self.character_set
+    }
+
+    pub fn take_connect_speed(&mut self) -> Option<u32> {
+        self.connect_speed.take()
+    }
+
+    /// The control lines of a serial connection, `None` for network connections.
+    pub fn get_line_status(&self) -> Option<LineStatus> {
+        self.line_status
+    }
+
+    /// Serial and modem connections can toggle DTR and RTS and send a break, even if the line status can't be read.
+    pub fn has_control_lines(&self) -> bool {
+        self.is_connected && self.is_serial
+    }
+
+    pub fn get_control_lines(&self) -> (bool, bool) {
+        self.control_lines
+    }
+
+    pub fn set_control_lines(&mut self, dtr: bool, rts: bool) -> TerminalResult<()> {
+        self.control_lines = (dtr, rts);
+        self.tx.send(SendData::SetControlLines(dtr, rts))?;
+        Ok(())
+    }
+
+    pub fn send_break(&self) -> TerminalResult<()> {
+        self.tx.send(SendData::SendBreak)?;
+        Ok(())
+    }
+
+    /// Returns the error once if the last connect attempt failed.
+    pub fn take_connect_error(&mut self) -> Option<ConnectionError> {
+        self.connect_error.take()
+    }
+
+    /// Returns the error the connection got lost with since the last call.
+    pub fn take_disconnect_reason(&mut self) -> Option<ConnectionError> {
+        self.disconnect_reason.take()
+    }
+
+    /// Returns a host the com thread needs a trust decision for, see [`Connection::verify_host`].
+    pub fn take_unknown_host(&mut self) -> Option<UnknownHost> {
+        self.unknown_host.take()
+    }
+
+    pub fn verify_host(&self, trust: bool) -> TerminalResult<()> {
+        self.tx.send(SendData::HostVerified(trust))?;
+        Ok(())
+    }
+
+    /// Returns questions of a keyboard-interactive login, see [`Connection::answer_auth_prompt`].
+    pub fn take_auth_prompt(&mut self) -> Option<AuthPrompt> {
+        self.auth_prompt.take()
+    }
+
+    /// Sends the answers for an [`AuthPrompt`], `None` cancels the login.
+    pub fn answer_auth_prompt(&self, answers: Option<Vec<String>>) -> TerminalResult<()> {
+        self.tx.send(SendData::AuthPromptAnswer(answers))?;
+        Ok(())
+    }
+
     pub fn start_transfer(&mut self) {
         self.end_transfer = false;
     }
@@ -98,48 +205,115 @@ impl Connection {
     fn fill_buffer(&mut self) -> TerminalResult<()> {
         loop {
             match self.rx.try_recv() {
-                Ok(data) => match data {
-                    SendData::Data(v) => {
-                        self.buf.extend(v);
-                    }
-                    SendData::Disconnect => {
-                        self.is_connected = false;
-                        break;
-                    }
-                    SendData::EndTransfer => {
-                        self.end_transfer = true;
+                Ok(data) => {
+                    if !self.receive(data)? {
                         break;
                     }
-                    SendData::Connected => {
-                        self.is_connected = true;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(err @ mpsc::TryRecvError::Disconnected) => {
+                    if !self.is_connected {
                         break;
                     }
-                    SendData::ConnectionError(err) => {
-                        log::error!("Connection aborted while fill_buffer: {err}");
-                        self.is_connected = false;
-                        self.end_transfer = true;
-                        return Err(anyhow::anyhow!("{err}"));
-                    }
-                    _ => {
-                        return Err(anyhow::anyhow!("Unsupported send data: {data:?}"));
-                    }
-                },
-
-                Err(err) => match err {
-                    mpsc::TryRecvError::Empty => break,
-                    mpsc::TryRecvError::Disconnected => {
-                        if !self.is_connected {
-                            break;
-                        }
-                        self.is_connected = false;
-                        return Err(anyhow::anyhow!("disconnected: {err}"));
-                    }
-                },
+                    self.is_connected = false;
+                    return Err(anyhow::anyhow!("disconnected: {err}"));
+                }
             }
         }
         Ok(())
     }
 
+    /// Blocks until the com thread sent something or the receive timeout elapsed.
+    /// Fails once the connection is gone or the transfer got ended, otherwise readers would wait forever.
+    /// Data that arrived before is still in the buffer, callers check it before waiting again.
+    fn wait_for_data(&mut self) -> TerminalResult<()> {
+        self.check_readable()?;
+        match self.rx.recv_timeout(RECEIVE_TIMEOUT) {
+            Ok(data) => {
+                if self.receive(data)? {
+                    self.fill_buffer()?;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(err @ mpsc::RecvTimeoutError::Disconnected) => {
+                self.is_connected = false;
+                return Err(anyhow::anyhow!("disconnected: {err}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_readable(&self) -> TerminalResult<()> {
+        if let Some(err) = &self.disconnect_reason {
+            return Err(anyhow::anyhow!("{}", err.get_localized_message()));
+        }
+        if !self.is_connected {
+            return Err(anyhow::anyhow!("disconnected"));
+        }
+        if self.end_transfer {
+            return Err(anyhow::anyhow!("transfer ended"));
+        }
+        Ok(())
+    }
+
+    /// Handles a message of the com thread, returns `false` if the caller should stop reading for now.
+    fn receive(&mut self, data: SendData) -> TerminalResult<bool> {
+        match data {
+            SendData::Data(v) => {
+                self.buf.extend(v);
+            }
+            SendData::Disconnect => {
+                self.is_connected = false;
+                self.line_status = None;
+                self.remote_echo = true;
+                return Ok(false);
+            }
+            SendData::EndTransfer => {
+                self.end_transfer = true;
+                return Ok(false);
+            }
+            SendData::Connected => {
+                self.is_connected = true;
+                return Ok(false);
+            }
+            SendData::SetCharacterSet(character_set) => {
+                self.character_set = Some(character_set);
+            }
+            SendData::ConnectSpeed(speed) => {
+                self.connect_speed = Some(speed);
+            }
+            SendData::LineStatus(line_status) => {
+                self.line_status = Some(line_status);
+            }
+            SendData::RemoteEcho(remote_echo) => {
+                self.remote_echo = remote_echo;
+            }
+            SendData::VerifyHost(unknown_host) => {
+                self.unknown_host = Some(unknown_host);
+            }
+            SendData::AuthPrompt(auth_prompt) => {
+                self.auth_prompt = Some(auth_prompt);
+            }
+            SendData::ConnectionError(err) => {
+                self.end_transfer = true;
+                if self.is_connected {
+                    // the disconnect follows, the reason is shown in the disconnected dialog
+                    log::error!("Connection lost: {err}");
+                    self.disconnect_reason = Some(err);
+                    return Ok(true);
+                }
+                log::error!("Connect failed: {err}");
+                let message = err.get_localized_message();
+                self.connect_error = Some(err);
+                return Err(anyhow::anyhow!("{message}"));
+            }
+            _ => {
+                return Err(anyhow::anyhow!("Unsupported send data: {data:?}"));
+            }
+        }
+        Ok(true)
+    }
+
     pub fn disconnect(&self) -> TerminalResult<()> {
         self.tx.send(SendData::Disconnect)?;
         Ok(())
@@ -158,14 +332,32 @@ impl Connection {
         self.is_connected
     }
 
+    /// The server doesn't echo what gets typed, the terminal has to show it.
+    pub fn needs_local_echo(&self) -> bool {
+        self.is_connected && !self.remote_echo
+    }
+
+    pub fn set_window_size(&self, window_size: icy_engine::Size) -> TerminalResult<()> {
+        self.tx.send(SendData::SetWindowSize(window_size))?;
+        Ok(())
+    }
+
     pub fn set_baud_rate(&self, baud_rate: u32) -> TerminalResult<()> {
         self.tx.send(SendData::SetBaudRate(baud_rate))?;
         Ok(())
     }
 
-    pub fn connect(&self, call_adr: &Address, timeout: Duration, window_size: icy_engine::Size, modem: Option<Modem>) -> TerminalResult<()> {
+    pub fn connect(&mut self, call_adr: &Address, timeout: Duration, window_size: icy_engine::Size, modem: Option<Modem>, proxy: &Proxy) -> TerminalResult<()> {
+        self.is_connected = false;
+        self.remote_echo = true;
+        self.character_set = None;
+        self.connect_error = None;
+        self.disconnect_reason = None;
+        self.line_status = None;
+        self.control_lines = (true, true);
+        self.is_serial = matches!(call_adr.protocol, Protocol::Serial | Protocol::Modem);
         self.tx
-            .send(SendData::OpenConnection(OpenConnectionData::from(call_adr, timeout, window_size, modem)))?;
+            .send(SendData::OpenConnection(OpenConnectionData::from(call_adr, timeout, window_size, modem, proxy)))?;
         Ok(())
     }
 }
@@ -176,34 +368,148 @@ impl Connection {
 pub struct OpenConnectionData {
     pub address: String,
     pub terminal: Terminal,
+    pub terminal_type_names: Vec<String>,
     pub user_name: String,
     pub password: String,
+    pub environment: Vec<(String, String)>,
     pub protocol: crate::Protocol,
+    pub accept_self_signed: bool,
+    pub websocket_telnet: bool,
+    pub websocket_protocols: Vec<String>,
+    pub websocket_headers: Vec<(String, String)>,
+    pub rlogin_send_password: bool,
+    /// Emulated baud rate of the address, 0 if it's off
+    pub baud_rate: u32,
+    /// Fingerprint of a host identity the user decided to trust while connecting
+    pub trusted_fingerprint: Option<String>,
+    pub ssh_auth_methods: Vec<SshAuthMethod>,
+    pub ssh_key_file: String,
+    pub ssh_key_passphrase: String,
+    pub ssh_algorithms: SshAlgorithms,
     pub timeout: Duration,
     pub window_size: icy_engine::Size,
     pub modem: Option<Modem>,
+    pub proxy: Proxy,
 }
 
 impl OpenConnectionData {
-    pub fn from(call_adr: &Address, timeout: Duration, window_size: icy_engine::Size, modem: Option<Modem>) -> Self {
+    /// `proxy` is the global proxy setting, addresses may override it.
+    pub fn from(call_adr: &Address, timeout: Duration, window_size: icy_engine::Size, modem: Option<Modem>, proxy: &Proxy) -> Self {
         Self {
             address: call_adr.address.clone(),
+            terminal_type_names: call_adr.get_terminal_type_names(),
             user_name: call_adr.user_name.clone(),
             password: call_adr.password.clone(),
+            environment: call_adr.environment.clone(),
             terminal: call_adr.terminal_type,
             protocol: call_adr.protocol,
+            accept_self_signed: call_adr.accept_self_signed,
+            websocket_telnet: call_adr.websocket_telnet,
+            websocket_protocols: call_adr.get_websocket_protocols(),
+            websocket_headers: call_adr.get_websocket_headers(),
+            rlogin_send_password: call_adr.rlogin_send_password,
+            baud_rate: call_adr.baud_emulation.get_baud_rate(),
+            trusted_fingerprint: None,
+            ssh_auth_methods: call_adr.ssh_auth_methods.clone(),
+            ssh_key_file: call_adr.ssh_key_file.clone(),
+            ssh_key_passphrase: call_adr.ssh_key_passphrase.clone(),
+            ssh_algorithms: call_adr.ssh_algorithms.clone(),
             timeout,
             window_size,
             modem,
+            proxy: if call_adr.override_proxy_settings {
+                call_adr.proxy.clone()
+            } else {
+                proxy.clone()
+            },
         }
     }
 }
 
+/// Sending end of the channel between the ui and the com thread, wakes up the receiving side after each message.
+#[derive(Debug, Clone)]
+pub struct ChannelSender {
+    tx: mpsc::Sender<SendData>,
+    wakeup: Wakeup,
+}
+
+#[derive(Debug, Clone)]
+enum Wakeup {
+    /// The receiver polls the channel itself
+    None,
+    Signal(DataSignal),
+    /// One end of a socket pair, the com thread waits on the other end together with the connection
+    #[cfg(unix)]
+    Socket(Arc<std::os::unix::net::UnixStream>),
+}
+
+impl ChannelSender {
+    pub fn with_signal(tx: mpsc::Sender<SendData>, signal: DataSignal) -> Self {
+        Self {
+            tx,
+            wakeup: Wakeup::Signal(signal),
+        }
+    }
+
+    /// `socket` needs to be non-blocking.
+    #[cfg(unix)]
+    pub fn with_socket(tx: mpsc::Sender<SendData>, socket: std::os::unix::net::UnixStream) -> Self {
+        Self {
+            tx,
+            wakeup: Wakeup::Socket(Arc::new(socket)),
+        }
+    }
+
+    /// Fails like [`mpsc::Sender::send`] if the receiver is gone.
+    #[allow(clippy::result_large_err)]
+    pub fn send(&self, data: SendData) -> Result<(), mpsc::SendError<SendData>> {
+        self.tx.send(data)?;
+        match &self.wakeup {
+            Wakeup::None => {}
+            Wakeup::Signal(signal) => signal.notify(),
+            #[cfg(unix)]
+            Wakeup::Socket(socket) => {
+                // a full socket buffer means the com thread has a wakeup pending anyways
+                let _ = std::io::Write::write(&mut &**socket, &[0]);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<mpsc::Sender<SendData>> for ChannelSender {
+    fn from(tx: mpsc::Sender<SendData>) -> Self {
+        Self { tx, wakeup: Wakeup::None }
+    }
+}
+
+/// Lets a thread sleep until the com thread sent something instead of polling the connection.
+#[derive(Debug, Clone, Default)]
+pub struct DataSignal(Arc<(Mutex<bool>, Condvar)>);
+
+impl DataSignal {
+    pub fn notify(&self) {
+        let (notified, condvar) = &*self.0;
+        *notified.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        condvar.notify_all();
+    }
+
+    /// Waits until the signal got notified or the timeout elapsed, returns whether it got notified.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let (notified, condvar) = &*self.0;
+        let guard = notified.lock().unwrap_or_else(PoisonError::into_inner);
+        let (mut guard, _) = condvar
+            .wait_timeout_while(guard, timeout, |notified| !*notified)
+            .unwrap_or_else(PoisonError::into_inner);
+        std::mem::take(&mut *guard)
+    }
+}
+
 /// Data that is sent to the connection thread
 #[derive(Debug)]
 pub enum SendData {
     OpenConnection(OpenConnectionData),
-    ConnectionError(String),
+    ConnectionError(ConnectionError),
     Connected,
 
     Data(Vec<u8>),
@@ -212,6 +518,23 @@ pub enum SendData {
     CancelTransfer,
     SetBaudRate(u32),
     SetRawMode(bool),
+    SetWindowSize(icy_engine::Size),
+    SetCharacterSet(CharacterSet),
+    /// The line speed reported by the connection, used for the baud emulation
+    ConnectSpeed(u32),
+    /// The control lines of a serial connection changed
+    LineStatus(LineStatus),
+    /// Whether the server echoes the typed characters, the ui echoes them itself otherwise
+    RemoteEcho(bool),
+    SendBreak,
+    /// DTR and RTS of a serial connection
+    SetControlLines(bool, bool),
+    /// Asks the user whether to trust a host, answered with `HostVerified`
+    VerifyHost(UnknownHost),
+    HostVerified(bool),
+    /// Asks the user for keyboard-interactive answers, answered with `AuthPromptAnswer`
+    AuthPrompt(AuthPrompt),
+    AuthPromptAnswer(Option<Vec<String>>),
 }
 
 #[cfg(test)]
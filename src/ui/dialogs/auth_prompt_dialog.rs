@@ -0,0 +1,69 @@
+use eframe::egui::{self, RichText, TextEdit};
+use egui_modal::Modal;
+use i18n_embed_fl::fl;
+
+use crate::{
+    com::AuthPrompt,
+    ui::{MainWindow, MainWindowMode},
+};
+
+#[derive(Default)]
+pub struct DialogState {
+    answers: Vec<String>,
+}
+
+pub fn show_dialog(window: &mut MainWindow, ctx: &egui::Context, auth_prompt: &AuthPrompt) {
+    let mut result = None;
+    if ctx.input(|i| i.key_down(egui::Key::Escape)) {
+        result = Some(None);
+    }
+    window.auth_prompt_dialog.answers.resize(auth_prompt.prompts.len(), String::new());
+
+    let modal = Modal::new(ctx, "auth_prompt_modal");
+    modal.show(|ui| {
+        if auth_prompt.name.is_empty() {
+            modal.title(ui, fl!(crate::LANGUAGE_LOADER, "auth-prompt-title"));
+        } else {
+            modal.title(ui, &auth_prompt.name);
+        }
+        modal.frame(ui, |ui: &mut egui::Ui| {
+            modal.body(ui, fl!(crate::LANGUAGE_LOADER, "auth-prompt-message", host = auth_prompt.host.clone()));
+            if !auth_prompt.instruction.is_empty() {
+                ui.label(RichText::new(&auth_prompt.instruction).small());
+            }
+            ui.add_space(8.);
+            egui::Grid::new("auth_prompt_grid").num_columns(2).spacing([4.0, 8.0]).show(ui, |ui| {
+                for (i, (prompt, echo)) in auth_prompt.prompts.iter().enumerate() {
+                    ui.label(prompt);
+                    let r = ui.add(TextEdit::singleline(&mut window.auth_prompt_dialog.answers[i]).password(!*echo));
+                    if i == 0 && !r.has_focus() && window.auth_prompt_dialog.answers[0].is_empty() {
+                        r.request_focus();
+                    }
+                    if r.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) && i + 1 == auth_prompt.prompts.len() {
+                        result = Some(Some(window.auth_prompt_dialog.answers.clone()));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        modal.buttons(ui, |ui| {
+            if modal.button(ui, fl!(crate::LANGUAGE_LOADER, "auth-prompt-login-button")).clicked() {
+                result = Some(Some(window.auth_prompt_dialog.answers.clone()));
+            }
+            if modal.button(ui, fl!(crate::LANGUAGE_LOADER, "auth-prompt-cancel-button")).clicked() {
+                result = Some(None);
+            }
+        });
+    });
+    modal.open();
+
+    if let Some(answers) = result {
+        window.auth_prompt_dialog.answers.clear();
+        if let Some(con) = window.connection.lock().as_mut() {
+            if let Err(err) = con.answer_auth_prompt(answers) {
+                log::error!("Error sending login answers: {err}");
+            }
+        }
+        window.set_mode(MainWindowMode::ShowTerminal);
+    }
+}
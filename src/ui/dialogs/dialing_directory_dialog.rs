@@ -13,7 +13,7 @@ use crate::{
     icons::{ADD, CLOSE, DELETE, STAR, UNSTAR, VISIBILITY, VISIBILITY_OFF},
     ui::{MainWindow, MainWindowMode, ScreenMode, DEFAULT_MODES},
     util::Rng,
-    AddressBook,
+    AddressBook, Modem,
 };
 
 #[derive(Default)]
@@ -47,6 +47,8 @@ pub struct DialogState {
     pub dialing_directory_filter_string: String,
     rng: Rng,
     show_passwords: bool,
+    /// Ids of the entries the redial cycles through
+    pub dial_queue: Vec<usize>,
 
     // UI
     pub address_category: AddressCategory,
@@ -74,6 +76,7 @@ impl DialogState {
                 break;
             }
         }
+        self.dial_queue.retain(|queued| *queued != uuid);
         let _ = self.addresses.store_phone_book();
         //check_error!(self, r, false);
     }
@@ -82,11 +85,11 @@ impl DialogState {
         self.selected_bbs = uuid;
     }
 
-    fn show_content(&mut self, ui: &mut egui::Ui) {
+    fn show_content(&mut self, ui: &mut egui::Ui, modems: &[Modem]) {
         if self.selected_bbs.is_some() {
             let bbs = self.selected_bbs;
             let sav: Address = self.get_address_mut(bbs).clone();
-            self.view_edit_bbs(ui);
+            self.view_edit_bbs(ui, modems);
             if sav != *self.get_address_mut(bbs) {
                 self.store_dialing_directory();
             }
@@ -234,7 +237,9 @@ impl DialogState {
                             addr.centered = true;
                             addr
                         } else {
-                            AddressRow::new(selected, addr.clone())
+                            let mut row = AddressRow::new(selected, addr.clone());
+                            row.queue_position = self.dial_queue.iter().position(|queued| *queued == addr.id);
+                            row
                         });
                         if let Some((scroll_to, align)) = self.scroll_to {
                             if scroll_to == i {
@@ -294,7 +299,7 @@ impl DialogState {
     }
 
     #[allow(clippy::range_plus_one)]
-    fn view_edit_bbs(&mut self, ui: &mut egui::Ui) {
+    fn view_edit_bbs(&mut self, ui: &mut egui::Ui, modems: &[Modem]) {
         // Name row
 
         ui.horizontal(|ui| {
@@ -319,6 +324,21 @@ impl DialogState {
             {
                 adr.is_favored = !adr.is_favored;
             }
+
+            let id = adr.id;
+            let queue_position = self.dial_queue.iter().position(|queued| *queued == id);
+            if ui
+                .selectable_label(queue_position.is_some(), "⟳")
+                .on_hover_text(fl!(crate::LANGUAGE_LOADER, "dialing_directory-dial-queue"))
+                .clicked()
+            {
+                match queue_position {
+                    Some(i) => {
+                        self.dial_queue.remove(i);
+                    }
+                    None => self.dial_queue.push(id),
+                }
+            }
         });
 
         ui.add_space(8.);
@@ -477,6 +497,25 @@ impl DialogState {
                 }
                 ui.end_row();
 
+                if matches!(
+                    adr.protocol,
+                    addresses::Protocol::Telnet | addresses::Protocol::TelnetTls | addresses::Protocol::Rlogin | addresses::Protocol::LocalProcess
+                ) {
+                    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-terminal_type_names")));
+                    });
+                    let mut txt = adr.terminal_type_names.join(",");
+                    let hint = adr.terminal_type.get_default_type_names().join(",");
+                    if ui.add(TextEdit::singleline(&mut txt).desired_width(250.).hint_text(hint)).changed() {
+                        adr.terminal_type_names = if txt.is_empty() {
+                            Vec::new()
+                        } else {
+                            txt.split(',').map(|name| name.trim_start().to_string()).collect()
+                        };
+                    }
+                    ui.end_row();
+                }
+
                 if adr.terminal_type == Terminal::Ansi {
                     ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
                         ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-music-option")));
@@ -562,6 +601,11 @@ impl DialogState {
                 });
                 ui.add(TextEdit::singleline(&mut self.get_address_mut(self.selected_bbs).auto_login).desired_width(f32::INFINITY));
                 ui.end_row();
+
+                if self.get_address_mut(self.selected_bbs).protocol == addresses::Protocol::Ssh {
+                    self.render_ssh_login_rows(ui);
+                }
+
                 ui.label("");
 
                 ui.checkbox(
@@ -602,6 +646,71 @@ impl DialogState {
             });
     }
 
+    fn render_ssh_login_rows(&mut self, ui: &mut egui::Ui) {
+        // Authentication methods row - tried in order
+        ui.with_layout(Layout::right_to_left(egui::Align::TOP), |ui| {
+            ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-auth-methods")));
+        });
+        ui.vertical(|ui| {
+            let adr = self.get_address_mut(self.selected_bbs);
+            let mut move_up = None;
+            let mut remove = None;
+            for (i, method) in adr.ssh_auth_methods.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}. {method}", i + 1));
+                    if ui.add_enabled(i > 0, egui::Button::new("⏶").small()).clicked() {
+                        move_up = Some(i);
+                    }
+                    if ui.add(ImageButton::new(DELETE.clone().tint(crate::ui::button_tint(ui))).frame(false)).clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = move_up {
+                adr.ssh_auth_methods.swap(i - 1, i);
+            }
+            if let Some(i) = remove {
+                adr.ssh_auth_methods.remove(i);
+            }
+
+            let missing: Vec<addresses::SshAuthMethod> = addresses::SshAuthMethod::ALL
+                .iter()
+                .filter(|m| !adr.ssh_auth_methods.contains(m))
+                .copied()
+                .collect();
+            if !missing.is_empty() {
+                ui.menu_button(fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-add-auth-method"), |ui| {
+                    for method in missing {
+                        if ui.button(method.to_string()).clicked() {
+                            adr.ssh_auth_methods.push(method);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+        });
+        ui.end_row();
+
+        // Key file row
+        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-key-file")));
+        });
+        ui.add(
+            TextEdit::singleline(&mut self.get_address_mut(self.selected_bbs).ssh_key_file)
+                .hint_text("~/.ssh/id_ed25519")
+                .desired_width(f32::INFINITY),
+        );
+        ui.end_row();
+
+        // Key passphrase row
+        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-key-passphrase")));
+        });
+        let pw = self.show_passwords;
+        ui.add(TextEdit::singleline(&mut self.get_address_mut(self.selected_bbs).ssh_key_passphrase).password(!pw));
+        ui.end_row();
+    }
+
     fn render_server_catogery(&mut self, ui: &mut egui::Ui) {
         let adr = self.get_address_mut(self.selected_bbs);
         egui::Grid::new("some_unique_id")
@@ -611,11 +720,31 @@ impl DialogState {
             .show(ui, |ui| {
                 // Addreess row
                 ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
-                    ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-address")));
+                    if adr.protocol == addresses::Protocol::LocalProcess {
+                        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-command")));
+                    } else {
+                        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-address")));
+                    }
                 });
                 ui.add(TextEdit::singleline(&mut adr.address));
                 ui.end_row();
 
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-failover-addresses")));
+                });
+                let mut txt = adr.failover_addresses.join(",");
+                if ui
+                    .add(TextEdit::singleline(&mut txt).hint_text(fl!(crate::LANGUAGE_LOADER, "dialing_directory-failover-addresses-hint")))
+                    .changed()
+                {
+                    adr.failover_addresses = if txt.is_empty() {
+                        Vec::new()
+                    } else {
+                        txt.split(',').map(|address| address.trim_start().to_string()).collect()
+                    };
+                }
+                ui.end_row();
+
                 // Protocol row
                 ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
                     ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-protocol")));
@@ -631,6 +760,109 @@ impl DialogState {
                         }
                     });
                 ui.end_row();
+
+                if adr.protocol.is_tls() {
+                    ui.label("");
+                    ui.checkbox(&mut adr.accept_self_signed, fl!(crate::LANGUAGE_LOADER, "dialing_directory-accept-self-signed"));
+                    ui.end_row();
+                }
+
+                if adr.protocol == addresses::Protocol::Rlogin {
+                    ui.label("");
+                    ui.checkbox(
+                        &mut adr.rlogin_send_password,
+                        fl!(crate::LANGUAGE_LOADER, "dialing_directory-rlogin-send-password"),
+                    )
+                    .on_hover_text(fl!(crate::LANGUAGE_LOADER, "dialing_directory-rlogin-send-password-tooltip"));
+                    ui.end_row();
+                }
+
+                if matches!(adr.protocol, addresses::Protocol::WebSocket(_)) {
+                    ui.label("");
+                    ui.checkbox(&mut adr.websocket_telnet, fl!(crate::LANGUAGE_LOADER, "dialing_directory-websocket-telnet"))
+                        .on_hover_text(fl!(crate::LANGUAGE_LOADER, "dialing_directory-websocket-telnet-tooltip"));
+                    ui.end_row();
+
+                    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-websocket-protocols")));
+                    });
+                    let mut txt = adr.websocket_protocols.join(",");
+                    if ui.add(TextEdit::singleline(&mut txt).desired_width(250.).hint_text("telnet,binary")).changed() {
+                        adr.websocket_protocols = if txt.is_empty() {
+                            Vec::new()
+                        } else {
+                            txt.split(',').map(|name| name.trim_start().to_string()).collect()
+                        };
+                    }
+                    ui.end_row();
+
+                    ui.with_layout(Layout::right_to_left(egui::Align::Min), |ui| {
+                        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-websocket-headers")));
+                    });
+                    // one 'Name: value' per line, incomplete lines are kept as name so editing doesn't lose text
+                    let mut txt = adr
+                        .websocket_headers
+                        .iter()
+                        .map(|(name, value)| if value.is_empty() { name.clone() } else { format!("{name}:{value}") })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    if ui
+                        .add(
+                            TextEdit::multiline(&mut txt)
+                                .desired_width(250.)
+                                .desired_rows(2)
+                                .hint_text("Origin: https://bbs.example.com"),
+                        )
+                        .changed()
+                    {
+                        adr.websocket_headers = txt
+                            .split('\n')
+                            .map(|line| match line.split_once(':') {
+                                Some((name, value)) if !value.is_empty() => (name.to_string(), value.to_string()),
+                                _ => (line.to_string(), String::new()),
+                            })
+                            .collect();
+                        if adr.websocket_headers.iter().all(|(name, _)| name.is_empty()) {
+                            adr.websocket_headers.clear();
+                        }
+                    }
+                    ui.end_row();
+                }
+
+                if adr.protocol == addresses::Protocol::Ssh {
+                    render_ssh_algorithm_rows(ui, &mut adr.ssh_algorithms);
+                }
+
+                if adr.protocol.is_serial() {
+                    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-modem-profile")));
+                    });
+                    let selected = modems
+                        .iter()
+                        .find(|modem| modem.id == adr.modem_profile)
+                        .or(modems.first())
+                        .map(|modem| modem.name.clone())
+                        .unwrap_or_default();
+                    egui::ComboBox::from_id_source("modem_profile_combobox")
+                        .selected_text(RichText::new(selected))
+                        .width(PROTOCOL_COMBOBOX_WIDTH)
+                        .show_ui(ui, |ui| {
+                            for modem in modems {
+                                let label = RichText::new(&modem.name);
+                                ui.selectable_value(&mut adr.modem_profile, modem.id.clone(), label);
+                            }
+                        });
+                    ui.end_row();
+                }
+
+                if !adr.protocol.is_serial() && adr.protocol != addresses::Protocol::LocalProcess {
+                    ui.label("");
+                    ui.checkbox(&mut adr.override_proxy_settings, fl!(crate::LANGUAGE_LOADER, "dialing_directory-custom-proxy"));
+                    ui.end_row();
+                    if adr.override_proxy_settings {
+                        crate::ui::dialogs::settings_dialog::show_proxy_rows(ui, &mut adr.proxy);
+                    }
+                }
             });
     }
 
@@ -641,9 +873,51 @@ impl DialogState {
         }
     }
 }
+fn render_ssh_algorithm_rows(ui: &mut egui::Ui, algorithms: &mut addresses::SshAlgorithms) {
+    let secure = addresses::SshAlgorithms::secure();
+    let legacy = addresses::SshAlgorithms::legacy();
+    let preset = if *algorithms == secure {
+        fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-algorithms-secure")
+    } else if *algorithms == legacy {
+        fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-algorithms-legacy")
+    } else {
+        fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-algorithms-custom")
+    };
+
+    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-algorithms")));
+    });
+    egui::ComboBox::from_id_source("ssh_algorithms_combobox")
+        .selected_text(preset)
+        .width(PROTOCOL_COMBOBOX_WIDTH)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(algorithms, secure, fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-algorithms-secure"));
+            ui.selectable_value(algorithms, legacy, fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-algorithms-legacy"));
+        });
+    ui.end_row();
+
+    for (label, list) in [
+        (
+            fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-key-exchanges"),
+            &mut algorithms.key_exchanges,
+        ),
+        (fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-host-keys"), &mut algorithms.host_keys),
+        (fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-ciphers"), &mut algorithms.ciphers),
+        (fl!(crate::LANGUAGE_LOADER, "dialing_directory-ssh-macs"), &mut algorithms.macs),
+    ] {
+        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(RichText::new(label));
+        });
+        ui.add(TextEdit::singleline(list).desired_width(f32::INFINITY));
+        ui.end_row();
+    }
+}
+
 pub struct AddressRow {
     selected: bool,
     pub centered: bool,
+    /// Position in the dial queue
+    pub queue_position: Option<usize>,
     addr: Address,
 }
 
@@ -652,6 +926,7 @@ impl AddressRow {
         Self {
             selected,
             centered: false,
+            queue_position: None,
             addr,
         }
     }
@@ -659,7 +934,12 @@ impl AddressRow {
 
 impl egui::Widget for AddressRow {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
-        let Self { selected, centered, addr } = self;
+        let Self {
+            selected,
+            centered,
+            queue_position,
+            addr,
+        } = self;
 
         let button_padding = ui.spacing().button_padding;
         let total_extra = button_padding + button_padding + Vec2::new(0.0, 8.0);
@@ -711,6 +991,13 @@ impl egui::Widget for AddressRow {
                     let text_pos = rect.right_top() - button_padding - Vec2::new(star_text_size.x, -2.);
                     ui.painter().galley(text_pos, star_text, visuals.text_color());
                 }
+
+                if let Some(position) = queue_position {
+                    let queue_text = WidgetText::from(RichText::new(format!("⟳{}", position + 1)).font(FontId::new(12.0, FontFamily::Proportional)));
+                    let queue_text = queue_text.into_galley(ui, Some(false), wrap_width, egui::TextStyle::Button);
+                    let text_pos = rect.right_bottom() - button_padding - queue_text.size();
+                    ui.painter().galley(text_pos, queue_text, visuals.text_color());
+                }
             }
         }
 
@@ -870,13 +1157,31 @@ pub fn view_dialing_directory(window: &mut MainWindow, ctx: &egui::Context) {
                     let cancel_text = WidgetText::from(fl!(crate::LANGUAGE_LOADER, "dialing_directory-cancel-button"));
                     let cancel_text_size = cancel_text.into_galley(ui, Some(false), 1000., egui::TextStyle::Button).size();
 
-                    ui.add_space(ui.available_size_before_wrap().x - connect_text_size.x - cancel_text_size.x - 8.);
+                    let redial_text = WidgetText::from(fl!(crate::LANGUAGE_LOADER, "dialing_directory-redial-button"));
+                    let redial_text_size = redial_text.into_galley(ui, Some(false), 1000., egui::TextStyle::Button).size();
+
+                    ui.add_space(ui.available_size_before_wrap().x - connect_text_size.x - cancel_text_size.x - redial_text_size.x - 16.);
 
                     let r: egui::Response = ui.add(egui::Button::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-cancel-button")));
                     if r.clicked() {
                         window.show_terminal();
                     }
 
+                    let dial_queue = if window.dialing_directory_dialog.dial_queue.is_empty() {
+                        window.dialing_directory_dialog.selected_bbs.into_iter().collect()
+                    } else {
+                        window.dialing_directory_dialog.dial_queue.clone()
+                    };
+                    let r: egui::Response = ui
+                        .add_enabled(
+                            !dial_queue.is_empty(),
+                            egui::Button::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-redial-button")),
+                        )
+                        .on_hover_text(fl!(crate::LANGUAGE_LOADER, "dialing_directory-redial-tooltip"));
+                    if r.clicked() {
+                        window.redial(&dial_queue);
+                    }
+
                     let r: egui::Response = ui.add(egui::Button::new(fl!(crate::LANGUAGE_LOADER, "dialing_directory-connect-button")));
                     if r.clicked() {
                         window.call_bbs_uuid(window.dialing_directory_dialog.selected_bbs);
@@ -885,7 +1190,7 @@ pub fn view_dialing_directory(window: &mut MainWindow, ctx: &egui::Context) {
             });
 
         egui::CentralPanel::default().show_inside(ui, |ui| {
-            window.dialing_directory_dialog.show_content(ui);
+            window.dialing_directory_dialog.show_content(ui, &window.state.options.modems);
         });
     });
 
@@ -1,7 +1,9 @@
+pub mod auth_prompt_dialog;
 pub mod capture_dialog;
 pub mod dialing_directory_dialog;
 pub mod export_dialog;
 pub mod protocol_selector;
+pub mod redial_dialog;
 pub mod settings_dialog;
 pub mod show_iemsi;
 pub mod up_download_dialog;
@@ -10,3 +12,4 @@ pub mod upload_dialog;
 pub mod find_dialog;
 pub mod show_delete_address_confirmation;
 pub mod show_disconnected_message;
+pub mod show_unknown_host;
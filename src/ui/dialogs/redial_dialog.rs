@@ -0,0 +1,54 @@
+use eframe::egui::{self, RichText};
+use egui::{Align2, Frame, Layout, Vec2};
+use i18n_embed_fl::fl;
+
+use crate::{features::RedialState, ui::MainWindow};
+
+/// Shows the progress of a running redial on top of the terminal.
+pub fn show_dialog(window: &mut MainWindow, ctx: &egui::Context) {
+    let Some(queue) = &window.dial_queue else {
+        return;
+    };
+    if !queue.is_redial() {
+        return;
+    }
+    let Some((target, address)) = queue.current() else {
+        return;
+    };
+
+    let status = match queue.state() {
+        RedialState::Waiting(until) => {
+            let seconds = until.saturating_duration_since(web_time::Instant::now()).as_secs() + 1;
+            fl!(crate::LANGUAGE_LOADER, "redial-waiting", seconds = seconds)
+        }
+        _ => fl!(crate::LANGUAGE_LOADER, "redial-dialing", address = address.to_string()),
+    };
+
+    let mut cancel = false;
+    egui::Window::new("")
+        .title_bar(false)
+        .frame(Frame::window(&ctx.style()))
+        .resizable(false)
+        .anchor(Align2::CENTER_TOP, Vec2::new(0., 48.))
+        .show(ctx, |ui| {
+            ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "redial-heading", name = target.name.clone())).strong());
+            ui.separator();
+            ui.label(fl!(
+                crate::LANGUAGE_LOADER,
+                "redial-attempt",
+                attempt = queue.attempt(),
+                attempts = queue.max_attempts()
+            ));
+            ui.label(status);
+            ui.add_space(4.0);
+            ui.with_layout(Layout::right_to_left(egui::Align::TOP), |ui| {
+                if ui.button(fl!(crate::LANGUAGE_LOADER, "redial-cancel-button")).clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if cancel || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        window.cancel_redial();
+    }
+}
@@ -5,12 +5,14 @@ use icy_engine_gui::show_monitor_settings;
 
 use crate::{
     ui::{MainWindowMode, MainWindowState},
-    KeyBindings, Modem,
+    KeyBindings, Modem, Proxy, ProxyKind,
 };
 
 #[derive(Default)]
 pub struct DialogState {
     pub settings_category: usize,
+    /// The modem profile shown in the modem category
+    pub selected_modem: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -21,18 +23,25 @@ pub(crate) enum Message {
     ResetMonitorSettings,
     ResetKeybindSettings,
     UpdateIEMSI(crate::IEMSISettings),
-    UpdateModem(Modem),
+    UpdateModem(usize, Modem),
+    SelectModem(usize),
+    AddModem,
+    RemoveModem(usize),
+    UpdateProxy(Proxy),
     UpdateMonitorSettings(icy_engine_gui::MonitorSettings),
     // ChangeOpenglScaling(Scaling),
     UpdateKeybinds(KeyBindings),
     ChangeConsoleBeep(bool),
+    ChangeConnectTimeout(std::time::Duration),
+    ChangeRedialAttempts(u32),
+    ChangeRedialDelay(std::time::Duration),
 }
 
 type ShowSettingsCallback = fn(&MainWindowState, ui: &mut egui::Ui) -> Option<Message>;
 type ResetMessage = Option<Message>;
 
 lazy_static::lazy_static! {
-    static ref SETTING_CATEGORIES: [(String, ShowSettingsCallback, ResetMessage); 5] = [
+    static ref SETTING_CATEGORIES: [(String, ShowSettingsCallback, ResetMessage); 6] = [
         (
             fl!(crate::LANGUAGE_LOADER, "settings-monitor-category"),
             show_monitor_settings2,
@@ -58,6 +67,11 @@ lazy_static::lazy_static! {
             show_modem_settings,
             None
         ),
+        (
+            fl!(crate::LANGUAGE_LOADER, "settings-proxy-category"),
+            show_proxy_settings,
+            None
+        ),
     ];
 }
 
@@ -186,6 +200,30 @@ fn show_terminal_settings(state: &MainWindowState, ui: &mut egui::Ui) -> Option<
         result = Some(Message::ChangeConsoleBeep(beep));
     }
 
+    let mut timeout = state.options.connect_timeout.as_secs();
+    ui.horizontal(|ui| {
+        ui.label(fl!(crate::LANGUAGE_LOADER, "settings-terminal-connect-timeout"));
+        if ui.add(egui::DragValue::new(&mut timeout).clamp_range(1..=120).suffix(" s")).changed() {
+            result = Some(Message::ChangeConnectTimeout(std::time::Duration::from_secs(timeout)));
+        }
+    });
+
+    let mut attempts = state.options.redial_attempts;
+    ui.horizontal(|ui| {
+        ui.label(fl!(crate::LANGUAGE_LOADER, "settings-terminal-redial-attempts"));
+        if ui.add(egui::DragValue::new(&mut attempts).clamp_range(1..=100)).changed() {
+            result = Some(Message::ChangeRedialAttempts(attempts));
+        }
+    });
+
+    let mut delay = state.options.redial_delay.as_secs();
+    ui.horizontal(|ui| {
+        ui.label(fl!(crate::LANGUAGE_LOADER, "settings-terminal-redial-delay"));
+        if ui.add(egui::DragValue::new(&mut delay).clamp_range(0..=600).suffix(" s")).changed() {
+            result = Some(Message::ChangeRedialDelay(std::time::Duration::from_secs(delay)));
+        }
+    });
+
     ui.add_space(16.0);
     if ui.button(fl!(crate::LANGUAGE_LOADER, "settings-terminal-open-settings-dir-button")).clicked() {
         result = Some(Message::OpenSettingsFolder);
@@ -231,8 +269,38 @@ fn update_state(state: &mut MainWindowState, message_opt: Option<Message>) {
             state.options.iemsi = iemsi;
             state.store_options();
         }
-        Some(Message::UpdateModem(modem)) => {
-            state.options.modem = modem;
+        Some(Message::UpdateModem(i, modem)) => {
+            if let Some(m) = state.options.modems.get_mut(i) {
+                *m = modem;
+                state.store_options();
+            }
+        }
+        Some(Message::SelectModem(i)) => {
+            state.settings_dialog.selected_modem = i;
+        }
+        Some(Message::AddModem) => {
+            let name = (1..)
+                .map(|i| format!("Modem {i}"))
+                .find(|name| state.options.modems.iter().all(|modem| modem.id != *name && modem.name != *name))
+                .unwrap();
+            state.options.modems.push(Modem {
+                id: name.clone(),
+                name,
+                ..Default::default()
+            });
+            state.settings_dialog.selected_modem = state.options.modems.len() - 1;
+            state.store_options();
+        }
+        Some(Message::RemoveModem(i)) => {
+            // addresses fall back to the first profile, there is always one left
+            if state.options.modems.len() > 1 && i < state.options.modems.len() {
+                state.options.modems.remove(i);
+                state.settings_dialog.selected_modem = state.settings_dialog.selected_modem.min(state.options.modems.len() - 1);
+                state.store_options();
+            }
+        }
+        Some(Message::UpdateProxy(proxy)) => {
+            state.options.proxy = proxy;
             state.store_options();
         }
         Some(Message::UpdateMonitorSettings(monitor_settings)) => {
@@ -251,22 +319,88 @@ fn update_state(state: &mut MainWindowState, message_opt: Option<Message>) {
             state.options.console_beep = beep;
             state.store_options();
         }
+        Some(Message::ChangeConnectTimeout(timeout)) => {
+            state.options.connect_timeout = timeout;
+            state.store_options();
+        }
+        Some(Message::ChangeRedialAttempts(attempts)) => {
+            state.options.redial_attempts = attempts;
+            state.store_options();
+        }
+        Some(Message::ChangeRedialDelay(delay)) => {
+            state.options.redial_delay = delay;
+            state.store_options();
+        }
         _ => {}
     }
 }
 
 fn show_modem_settings(state: &MainWindowState, ui: &mut egui::Ui) -> Option<Message> {
-    let mut modem = state.options.modem.clone();
+    let mut result = None;
+    let selected = state.settings_dialog.selected_modem.min(state.options.modems.len().saturating_sub(1));
+    ui.horizontal(|ui| {
+        ui.label(fl!(crate::LANGUAGE_LOADER, "settings-modem-profile"));
+        let selected_name = state.options.modems.get(selected).map(|modem| modem.name.clone()).unwrap_or_default();
+        egui::ComboBox::from_id_source("modem_profile_combobox")
+            .selected_text(RichText::new(selected_name))
+            .show_ui(ui, |ui| {
+                for (i, modem) in state.options.modems.iter().enumerate() {
+                    if ui.selectable_label(i == selected, &modem.name).clicked() {
+                        result = Some(Message::SelectModem(i));
+                    }
+                }
+            });
+        if ui.button(fl!(crate::LANGUAGE_LOADER, "settings-modem-add-button")).clicked() {
+            result = Some(Message::AddModem);
+        }
+        if ui
+            .add_enabled(
+                state.options.modems.len() > 1,
+                egui::Button::new(fl!(crate::LANGUAGE_LOADER, "settings-modem-remove-button")),
+            )
+            .clicked()
+        {
+            result = Some(Message::RemoveModem(selected));
+        }
+    });
+    ui.add_space(8.0);
+
+    let Some(old_modem) = state.options.modems.get(selected) else {
+        return result;
+    };
+    let mut modem = old_modem.clone();
 
     egui::Grid::new("some_unique_id")
         .num_columns(2)
         .spacing([4.0, 8.0])
         .min_row_height(24.)
         .show(ui, |ui| {
+            ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "settings-modem-name")));
+            });
+            ui.add(TextEdit::singleline(&mut modem.name));
+            ui.end_row();
+
             ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
                 ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "settings-modem-device")));
             });
-            ui.add(TextEdit::singleline(&mut modem.device));
+            ui.horizontal(|ui| {
+                ui.add(TextEdit::singleline(&mut modem.device).desired_width(180.0));
+                // the ports are only enumerated while the list is open
+                egui::ComboBox::from_id_source("modem_device_combobox")
+                    .selected_text("")
+                    .width(24.0)
+                    .show_ui(ui, |ui| {
+                        let ports = crate::available_serial_ports();
+                        if ports.is_empty() {
+                            ui.label(fl!(crate::LANGUAGE_LOADER, "settings-modem-no-ports"));
+                        }
+                        for port in ports {
+                            let label = port.clone();
+                            ui.selectable_value(&mut modem.device, port, label);
+                        }
+                    });
+            });
             ui.end_row();
 
             ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
@@ -331,11 +465,65 @@ fn show_modem_settings(state: &MainWindowState, ui: &mut egui::Ui) -> Option<Mes
             ui.end_row();
         });
 
-    if modem == state.options.modem {
+    if modem != *old_modem {
+        result = Some(Message::UpdateModem(selected, modem));
+    }
+    result
+}
+
+fn show_proxy_settings(state: &MainWindowState, ui: &mut egui::Ui) -> Option<Message> {
+    let mut proxy = state.options.proxy.clone();
+
+    egui::Grid::new("some_unique_id")
+        .num_columns(2)
+        .spacing([4.0, 8.0])
+        .min_row_height(24.)
+        .show(ui, |ui| {
+            show_proxy_rows(ui, &mut proxy);
+        });
+
+    if proxy == state.options.proxy {
         None
     } else {
-        Some(Message::UpdateModem(modem))
+        Some(Message::UpdateProxy(proxy))
+    }
+}
+
+/// Grid rows for editing a proxy, shared with the per address settings of the dialing directory.
+pub(crate) fn show_proxy_rows(ui: &mut egui::Ui, proxy: &mut Proxy) {
+    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "settings-proxy-kind")));
+    });
+    egui::ComboBox::from_id_source("proxy_kind_combobox")
+        .selected_text(RichText::new(proxy.kind.to_string()))
+        .show_ui(ui, |ui| {
+            for kind in ProxyKind::ALL {
+                ui.selectable_value(&mut proxy.kind, kind, kind.to_string());
+            }
+        });
+    ui.end_row();
+
+    if !proxy.is_enabled() {
+        return;
     }
+
+    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "settings-proxy-address")));
+    });
+    ui.add(TextEdit::singleline(&mut proxy.address).hint_text("proxy.example.com:1080"));
+    ui.end_row();
+
+    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "settings-proxy-user")));
+    });
+    ui.add(TextEdit::singleline(&mut proxy.user_name));
+    ui.end_row();
+
+    ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+        ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "settings-proxy-password")));
+    });
+    ui.add(TextEdit::singleline(&mut proxy.password).password(true));
+    ui.end_row();
 }
 
 #[cfg(test)]
@@ -348,7 +536,7 @@ mod tests {
             dialogs::settings_dialog::{update_state, SETTING_CATEGORIES},
             MainWindowState,
         },
-        IEMSISettings, KeyBindings, Options, Scaling,
+        IEMSISettings, KeyBindings, Modem, Options, Proxy, ProxyKind, Scaling,
     };
 
     #[test]
@@ -409,6 +597,24 @@ mod tests {
         assert!(state.options_written);
     }
 
+    #[test]
+    fn test_change_connect_timeout() {
+        let mut state: MainWindowState = MainWindowState::default();
+        update_state(&mut state, Some(super::Message::ChangeConnectTimeout(std::time::Duration::from_secs(30))));
+        assert_eq!(std::time::Duration::from_secs(30), state.options.connect_timeout);
+        assert!(state.options_written);
+    }
+
+    #[test]
+    fn test_change_redial_settings() {
+        let mut state: MainWindowState = MainWindowState::default();
+        update_state(&mut state, Some(super::Message::ChangeRedialAttempts(3)));
+        update_state(&mut state, Some(super::Message::ChangeRedialDelay(std::time::Duration::from_secs(5))));
+        assert_eq!(3, state.options.redial_attempts);
+        assert_eq!(std::time::Duration::from_secs(5), state.options.redial_delay);
+        assert!(state.options_written);
+    }
+
     #[test]
     fn test_set_keybindings() {
         let mut state: MainWindowState = MainWindowState::default();
@@ -441,4 +647,50 @@ mod tests {
         assert_ne!(IEMSISettings::default(), state.options.iemsi);
         assert!(state.options_written);
     }
+
+    #[test]
+    fn test_modem_profiles() {
+        let mut state: MainWindowState = MainWindowState::default();
+        update_state(&mut state, Some(super::Message::AddModem));
+        assert_eq!(2, state.options.modems.len());
+        assert_eq!("Modem 2", state.options.modems[1].name);
+        assert_eq!(1, state.settings_dialog.selected_modem);
+
+        let modem = Modem {
+            name: "WiFi Modem".to_string(),
+            device: "/dev/ttyUSB0".to_string(),
+            ..state.options.modems[1].clone()
+        };
+        update_state(&mut state, Some(super::Message::UpdateModem(1, modem.clone())));
+        assert_eq!(modem, state.options.modems[1]);
+        // addresses still find the renamed profile
+        assert_eq!(Some(&modem), state.options.get_modem("Modem 2"));
+
+        // the id of the renamed profile isn't given out again
+        update_state(&mut state, Some(super::Message::AddModem));
+        assert_eq!("Modem 3", state.options.modems[2].id);
+        update_state(&mut state, Some(super::Message::RemoveModem(2)));
+
+        update_state(&mut state, Some(super::Message::RemoveModem(0)));
+        assert_eq!(vec![modem], state.options.modems);
+        assert_eq!(0, state.settings_dialog.selected_modem);
+
+        // the last profile can't be removed
+        update_state(&mut state, Some(super::Message::RemoveModem(0)));
+        assert_eq!(1, state.options.modems.len());
+        assert!(state.options_written);
+    }
+
+    #[test]
+    fn test_set_proxy_settings() {
+        let mut state: MainWindowState = MainWindowState::default();
+        let proxy = Proxy {
+            kind: ProxyKind::Socks5,
+            address: "localhost:1080".to_string(),
+            ..Default::default()
+        };
+        update_state(&mut state, Some(super::Message::UpdateProxy(proxy.clone())));
+        assert_eq!(proxy, state.options.proxy);
+        assert!(state.options_written);
+    }
 }
@@ -2,9 +2,12 @@ use eframe::egui::{self, RichText};
 use egui::{Align2, Vec2};
 use i18n_embed_fl::fl;
 
-use crate::ui::{MainWindow, MainWindowMode};
+use crate::{
+    com::ConnectionError,
+    ui::{MainWindow, MainWindowMode},
+};
 
-pub fn show_disconnected(window: &mut MainWindow, ctx: &egui::Context, system: String, time: String) {
+pub fn show_disconnected(window: &mut MainWindow, ctx: &egui::Context, system: String, time: String, reason: Option<&ConnectionError>) {
     use egui::{Frame, Layout};
 
     let mut open = true;
@@ -30,6 +33,10 @@ pub fn show_disconnected(window: &mut MainWindow, ctx: &egui::Context, system: S
                 system = system,
                 time = time
             )));
+            if let Some(reason) = reason {
+                ui.add_space(4.0);
+                ui.label(RichText::new(reason.get_localized_message()));
+            }
 
             ui.add_space(8.);
             ui.separator();
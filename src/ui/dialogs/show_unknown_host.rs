@@ -0,0 +1,58 @@
+use eframe::egui::{self, RichText};
+use egui_modal::Modal;
+use i18n_embed_fl::fl;
+
+use crate::{
+    com::{HostIdentity, UnknownHost},
+    ui::{MainWindow, MainWindowMode},
+};
+
+pub fn show_dialog(window: &mut MainWindow, ctx: &egui::Context, unknown_host: &UnknownHost) {
+    let mut result = None;
+    if ctx.input(|i| i.key_down(egui::Key::Escape)) {
+        result = Some(false);
+    }
+    let modal = Modal::new(ctx, "unknown_host_modal");
+    modal.show(|ui| {
+        if unknown_host.known_fingerprint.is_some() {
+            modal.title(ui, fl!(crate::LANGUAGE_LOADER, "unknown-host-changed-title"));
+        } else {
+            modal.title(ui, fl!(crate::LANGUAGE_LOADER, "unknown-host-title"));
+        }
+        modal.frame(ui, |ui: &mut egui::Ui| {
+            modal.body(ui, fl!(crate::LANGUAGE_LOADER, "unknown-host-message", host = unknown_host.host.clone()));
+            ui.label(RichText::new(&unknown_host.reason).small());
+            ui.add_space(8.);
+            match unknown_host.kind {
+                HostIdentity::Certificate => ui.label(fl!(crate::LANGUAGE_LOADER, "unknown-host-certificate-fingerprint")),
+                HostIdentity::SshHostKey => ui.label(fl!(crate::LANGUAGE_LOADER, "unknown-host-ssh-fingerprint")),
+            };
+            ui.label(RichText::new(&unknown_host.fingerprint).monospace());
+            if let Some(known_fingerprint) = &unknown_host.known_fingerprint {
+                ui.add_space(8.);
+                ui.label(fl!(crate::LANGUAGE_LOADER, "unknown-host-known-fingerprint"));
+                ui.label(RichText::new(known_fingerprint).monospace());
+                ui.add_space(8.);
+                ui.label(RichText::new(fl!(crate::LANGUAGE_LOADER, "unknown-host-changed-warning")).strong());
+            }
+        });
+        modal.buttons(ui, |ui| {
+            if modal.button(ui, fl!(crate::LANGUAGE_LOADER, "unknown-host-trust-button")).clicked() {
+                result = Some(true);
+            }
+            if modal.button(ui, fl!(crate::LANGUAGE_LOADER, "unknown-host-reject-button")).clicked() {
+                result = Some(false);
+            }
+        });
+    });
+    modal.open();
+
+    if let Some(trust) = result {
+        if let Some(con) = window.connection.lock().as_mut() {
+            if let Err(err) = con.verify_host(trust) {
+                log::error!("Error sending host verification: {err}");
+            }
+        }
+        window.set_mode(MainWindowMode::ShowTerminal);
+    }
+}
@@ -14,10 +14,11 @@ use std::time::Instant;
 
 use eframe::egui::Key;
 
-use crate::features::AutoLogin;
+use crate::com::{AuthPrompt, ConnectionError, UnknownHost};
+use crate::features::{AutoLogin, DialQueue, DialTarget, RedialState};
 use crate::ui::connect::DataConnection;
-use crate::Options;
 use crate::{protocol::FileDescriptor, TerminalResult};
+use crate::{CharacterSet, Options};
 
 pub mod app;
 pub mod connect;
@@ -67,7 +68,9 @@ pub enum MainWindowMode {
     ShowExportDialog,
     ShowUploadDialog,
     ShowIEMSI,
-    ShowDisconnectedMessage(String, String),
+    ShowDisconnectedMessage(String, String, Option<ConnectionError>),
+    ShowUnknownHost(UnknownHost),
+    ShowAuthPrompt(AuthPrompt),
 }
 
 #[derive(Default)]
@@ -120,6 +123,9 @@ pub struct MainWindow {
     pub dialing_directory_dialog: dialogs::dialing_directory_dialog::DialogState,
     pub export_dialog: dialogs::export_dialog::DialogState,
     pub upload_dialog: dialogs::upload_dialog::DialogState,
+    pub auth_prompt_dialog: dialogs::auth_prompt_dialog::DialogState,
+    /// Entries that get dialed until one connects
+    pub dial_queue: Option<DialQueue>,
 
     pub show_find_dialog: bool,
     pub find_dialog: dialogs::find_dialog::DialogState,
@@ -153,12 +159,14 @@ impl MainWindow {
 
     pub fn output_char(&mut self, ch: char) {
         let translated_char = self.buffer_view.lock().get_unicode_converter().convert_from_unicode(ch, 0);
+        let character_set = self.connection.lock().as_ref().and_then(|con| con.get_character_set());
+        let encoded = self.encode_char(character_set, ch);
         let mut print = true;
         if let Some(con) = self.connection.lock().as_mut() {
             if con.is_connected() {
-                let r = con.send(vec![translated_char as u8]);
+                let r = con.send(encoded);
                 check_error!(self, r, false);
-                print = false;
+                print = con.needs_local_echo();
             }
         }
 
@@ -172,14 +180,11 @@ impl MainWindow {
 
         if let Some(con) = self.connection.lock().as_mut() {
             if con.is_connected() {
-                let mut v = Vec::new();
-                for ch in str.chars() {
-                    let translated_char = self.buffer_view.lock().get_unicode_converter().convert_from_unicode(ch, 0);
-                    v.push(translated_char as u8);
-                }
+                let character_set = con.get_character_set();
+                let v = str.chars().flat_map(|ch| self.encode_char(character_set, ch)).collect();
                 let r = con.send(v);
                 check_error!(self, r, false);
-                print = false;
+                print = con.needs_local_echo();
             }
         }
         if print {
@@ -190,6 +195,15 @@ impl MainWindow {
         }
     }
 
+    /// Encodes a typed character in the character set agreed on with the server or the terminal's own one.
+    fn encode_char(&self, character_set: Option<CharacterSet>, ch: char) -> Vec<u8> {
+        let buffer_view = self.buffer_view.lock();
+        match character_set {
+            Some(character_set) => character_set.encode(ch, buffer_view.get_unicode_converter()),
+            None => vec![buffer_view.get_unicode_converter().convert_from_unicode(ch, 0) as u8],
+        }
+    }
+
     pub fn print_char(&self, c: u8) {
         let buffer_view = &mut self.buffer_view.lock();
         buffer_view.get_edit_state_mut().set_is_buffer_dirty();
@@ -239,6 +253,13 @@ impl MainWindow {
     pub fn set_screen_mode(&mut self, mode: ScreenMode) {
         self.screen_mode = mode;
         mode.set_mode(self);
+        if let Some(con) = self.connection.lock().as_mut() {
+            if con.is_connected() {
+                if let Err(err) = con.set_window_size(mode.get_window_size()) {
+                    log::error!("set_screen_mode: {err}");
+                }
+            }
+        }
     }
 
     pub fn show_terminal(&mut self) {
@@ -265,15 +286,88 @@ impl MainWindow {
     }
 
     pub fn call_bbs(&mut self, i: usize) {
+        let id = self.dialing_directory_dialog.addresses.addresses[i].id;
+        self.start_dial_queue(&[id], 1);
+    }
+
+    /// Dials the entries one after another until one connects, with the redial settings from the options.
+    pub fn redial(&mut self, ids: &[usize]) {
+        self.start_dial_queue(ids, self.get_options().redial_attempts);
+    }
+
+    fn start_dial_queue(&mut self, ids: &[usize], attempts: u32) {
+        let targets = ids
+            .iter()
+            .filter_map(|id| self.dialing_directory_dialog.addresses.addresses.iter().find(|adr| adr.id == *id))
+            .map(|adr| DialTarget {
+                id: adr.id,
+                name: if adr.system_name.is_empty() {
+                    adr.address.clone()
+                } else {
+                    adr.system_name.clone()
+                },
+                addresses: adr.get_dial_addresses(),
+            })
+            .collect();
+        self.dial_queue = Some(DialQueue::new(targets, attempts, self.get_options().redial_delay));
+        self.dial_next();
+    }
+
+    fn dial_next(&mut self) {
+        let Some((id, address)) = self
+            .dial_queue
+            .as_ref()
+            .and_then(DialQueue::current)
+            .map(|(target, address)| (target.id, address.to_string()))
+        else {
+            return;
+        };
+        if let Some(i) = self.dialing_directory_dialog.addresses.addresses.iter().position(|adr| adr.id == id) {
+            self.dial(i, address);
+        }
+    }
+
+    pub fn cancel_redial(&mut self) {
+        if self.dial_queue.take().is_some() {
+            if let Some(con) = self.connection.lock().as_mut() {
+                check_error!(self, con.disconnect(), false);
+            }
+        }
+    }
+
+    fn update_dial_queue(&mut self, connected: bool, connect_error: Option<&ConnectionError>) {
+        let Some(queue) = &mut self.dial_queue else {
+            return;
+        };
+        if connected {
+            self.dial_queue = None;
+            return;
+        }
+        if connect_error.is_some_and(|err| !err.can_redial()) {
+            // dialing again fails the same way, the error got already printed
+            self.dial_queue = None;
+            return;
+        }
+        let now = web_time::Instant::now();
+        let dial = if connect_error.is_some() { queue.dial_failed(now) } else { queue.poll(now) };
+        if queue.state() == RedialState::Finished {
+            let (is_redial, attempts) = (queue.is_redial(), queue.attempt());
+            self.dial_queue = None;
+            if is_redial {
+                self.println(&fl!(crate::LANGUAGE_LOADER, "redial-failed", attempts = attempts));
+            }
+        } else if dial {
+            self.dial_next();
+        }
+    }
+
+    fn dial(&mut self, i: usize, address: String) {
         self.set_mode(MainWindowMode::ShowTerminal);
-        let cloned_addr = self.dialing_directory_dialog.addresses.addresses[i].clone();
+        let mut cloned_addr = self.dialing_directory_dialog.addresses.addresses[i].clone();
+        cloned_addr.address = address;
 
         {
             let address = &mut self.dialing_directory_dialog.addresses.addresses[i];
-            let mut adr = address.address.clone();
-            if !adr.contains(':') {
-                adr.push_str(":23");
-            }
             address.number_of_calls += 1;
             address.last_call = Some(Utc::now());
 
@@ -318,7 +412,13 @@ impl MainWindow {
         let timeout = self.get_options().connect_timeout;
         let window_size = self.screen_mode.get_window_size();
         if let Some(con) = self.connection.lock().as_mut() {
-            let r = con.connect(&cloned_addr, timeout, window_size, Some(self.get_options().modem.clone()));
+            let r = con.connect(
+                &cloned_addr,
+                timeout,
+                window_size,
+                self.get_options().get_modem(&cloned_addr.modem_profile).cloned(),
+                &self.state.options.proxy,
+            );
             check_error!(self, r, false);
             let r = con.set_baud_rate(cloned_addr.baud_emulation.get_baud_rate());
             check_error!(self, r, false);
@@ -328,8 +428,24 @@ impl MainWindow {
     pub fn update_state(&mut self, ctx: &egui::Context) -> TerminalResult<()> {
         #[cfg(target_arch = "wasm32")]
         self.poll_thread.poll();
+        let mut result = Ok(());
+        let mut unknown_host = None;
+        let mut auth_prompt = None;
+        let mut connected = false;
+        let mut connect_error = None;
         if let Some(con) = self.connection.lock().as_mut() {
-            con.update_state()?;
+            result = con.update_state();
+            unknown_host = con.take_unknown_host();
+            auth_prompt = con.take_auth_prompt();
+            connected = con.is_connected();
+            connect_error = con.take_connect_error();
+        }
+        self.update_dial_queue(connected, connect_error.as_ref());
+        if let Some(unknown_host) = unknown_host {
+            self.set_mode(MainWindowMode::ShowUnknownHost(unknown_host));
+        }
+        if let Some(auth_prompt) = auth_prompt {
+            self.set_mode(MainWindowMode::ShowAuthPrompt(auth_prompt));
         }
 
         if self.update_thread_handle.as_ref().unwrap().is_finished() {
@@ -350,7 +466,7 @@ impl MainWindow {
         if let Some((protocol_type, download)) = take {
             self.initiate_file_transfer(protocol_type, download);
         }
-        Ok(())
+        result
     }
 
     pub fn hangup(&mut self) {
@@ -414,6 +530,7 @@ impl MainWindow {
             }
 
             let mut show_disconnect = false;
+            let mut disconnect_reason = None;
             let mut connection_time = String::new();
             let mut system_name = String::new();
             if let Some(con) = self.connection.lock().as_mut() {
@@ -444,12 +561,17 @@ impl MainWindow {
                     }
                     self.is_disconnected = true;
                     show_disconnect = true;
+                    disconnect_reason = con.take_disconnect_reason();
                     fl!(crate::LANGUAGE_LOADER, "title-offline", version = crate::VERSION.to_string())
                 };
                 ctx.send_viewport_cmd(egui::ViewportCommand::Title(title));
             }
             if show_disconnect {
-                self.set_mode(MainWindowMode::ShowDisconnectedMessage(system_name.clone(), connection_time.clone()));
+                self.set_mode(MainWindowMode::ShowDisconnectedMessage(
+                    system_name.clone(),
+                    connection_time.clone(),
+                    disconnect_reason,
+                ));
                 self.output_string("\nNO CARRIER\n");
             }
         }
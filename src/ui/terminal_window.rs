@@ -23,6 +23,20 @@ fn encode_mouse_position(pos: i32) -> char {
     unsafe { char::from_u32_unchecked(b'!'.saturating_add(pos as u8) as u32) }
 }
 
+/// Shows the state of a serial control line like the LEDs on the front of a modem.
+fn line_status_led(ui: &mut egui::Ui, name: &str, on: bool, tooltip: String) {
+    let size = ui.spacing().interact_size.y * 0.5;
+    let (rect, _) = ui.allocate_exact_size(Vec2::splat(size), egui::Sense::hover());
+    let color = if on {
+        egui::Color32::from_rgb(0x20, 0xE0, 0x40)
+    } else {
+        ui.visuals().widgets.inactive.bg_fill
+    };
+    ui.painter().circle(rect.center(), size / 2.0, color, ui.visuals().widgets.inactive.fg_stroke);
+    ui.label(RichText::new(name).small().monospace()).on_hover_text(tooltip);
+    ui.add_space(8.0);
+}
+
 impl MainWindow {
     pub fn update_terminal_window(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame, show_dialing_directory: bool) {
         let toolbar_bg_color = ctx.style().visuals.extreme_bg_color;
@@ -175,6 +189,46 @@ impl MainWindow {
                 });
             });
         }
+        if !self.is_fullscreen_mode {
+            let status = self
+                .connection
+                .lock()
+                .as_ref()
+                .filter(|con| con.has_control_lines())
+                .map(|con| (con.get_line_status(), con.get_control_lines()));
+            if let Some((line_status, (mut dtr, mut rts))) = status {
+                let mut send_break = false;
+                egui::TopBottomPanel::bottom("status_bar").frame(button_frame).show(ctx, |ui| {
+                    if !enable_ui {
+                        ui.set_enabled(false);
+                    }
+                    ui.horizontal(|ui| {
+                        // the leds need a port that reports its status lines
+                        if let Some(line_status) = line_status {
+                            line_status_led(ui, "DCD", line_status.dcd, fl!(crate::LANGUAGE_LOADER, "terminal-line-dcd"));
+                            line_status_led(ui, "DSR", line_status.dsr, fl!(crate::LANGUAGE_LOADER, "terminal-line-dsr"));
+                            line_status_led(ui, "CTS", line_status.cts, fl!(crate::LANGUAGE_LOADER, "terminal-line-cts"));
+                            line_status_led(ui, "RI", line_status.ri, fl!(crate::LANGUAGE_LOADER, "terminal-line-ri"));
+                            ui.separator();
+                        }
+                        ui.toggle_value(&mut dtr, RichText::new("DTR").small().monospace())
+                            .on_hover_text(fl!(crate::LANGUAGE_LOADER, "terminal-line-dtr"));
+                        ui.toggle_value(&mut rts, RichText::new("RTS").small().monospace())
+                            .on_hover_text(fl!(crate::LANGUAGE_LOADER, "terminal-line-rts"));
+                        send_break = ui.button(RichText::new(fl!(crate::LANGUAGE_LOADER, "terminal-send-break")).small()).clicked();
+                    });
+                });
+                if let Some(con) = self.connection.lock().as_mut() {
+                    if (dtr, rts) != con.get_control_lines() {
+                        check_error!(self, con.set_control_lines(dtr, rts), false);
+                    }
+                    if send_break {
+                        check_error!(self, con.send_break(), false);
+                    }
+                }
+            }
+        }
+
         let frame_no_margins = egui::containers::Frame::none().outer_margin(Margin::same(0.0)).inner_margin(Margin::same(0.0));
 
         egui::CentralPanel::default().frame(frame_no_margins).show(ctx, |ui| {
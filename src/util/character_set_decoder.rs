@@ -0,0 +1,113 @@
+use icy_engine::{AttributedChar, TextAttribute, UnicodeConverter};
+
+use crate::{CharacterSet, Terminal};
+
+/// Turns the bytes of an agreed telnet character set into characters of the CP437 font.
+/// ISO-8859-1 and UTF-8 are only offered for terminals using that font.
+pub struct CharacterSetDecoder {
+    character_set: CharacterSet,
+    converter: Box<dyn UnicodeConverter>,
+    pending: Vec<u8>,
+}
+
+impl CharacterSetDecoder {
+    pub fn new(character_set: CharacterSet) -> Self {
+        Self {
+            character_set,
+            converter: Terminal::Ansi.get_unicode_converter(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn get_character_set(&self) -> CharacterSet {
+        self.character_set
+    }
+
+    /// Returns the character to print for the next received byte, `None` while a UTF-8 sequence is incomplete.
+    pub fn push_byte(&mut self, b: u8) -> Option<char> {
+        match self.character_set {
+            CharacterSet::Cp437 | CharacterSet::Petscii => Some(b as char),
+            CharacterSet::Latin1 => Some(self.to_font(b as char)),
+            CharacterSet::Utf8 => self.push_utf8(b),
+        }
+    }
+
+    fn push_utf8(&mut self, b: u8) -> Option<char> {
+        if b & 0xC0 != 0x80 {
+            // a new sequence starts, an unfinished one is dropped
+            self.pending.clear();
+        } else if self.pending.is_empty() {
+            return Some('?');
+        }
+        self.pending.push(b);
+        match std::str::from_utf8(&self.pending) {
+            Ok(s) => {
+                let ch = s.chars().next().unwrap_or('?');
+                self.pending.clear();
+                Some(self.to_font(ch))
+            }
+            Err(err) if err.error_len().is_none() => None,
+            Err(_) => {
+                self.pending.clear();
+                Some('?')
+            }
+        }
+    }
+
+    /// Characters the font doesn't have are shown as '?'.
+    fn to_font(&self, ch: char) -> char {
+        if ch.is_ascii() {
+            return ch;
+        }
+        let font_char = self.converter.convert_from_unicode(ch, 0);
+        if font_char as u32 <= 0xFF && self.converter.convert_to_unicode(AttributedChar::new(font_char, TextAttribute::default())) == ch {
+            font_char
+        } else {
+            '?'
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(character_set: CharacterSet, data: &[u8]) -> String {
+        let mut decoder = CharacterSetDecoder::new(character_set);
+        data.iter().filter_map(|b| decoder.push_byte(*b)).collect()
+    }
+
+    #[test]
+    fn test_cp437_passes_through() {
+        assert_eq!("\u{1B}[0m\u{82}\u{DB}", decode(CharacterSet::Cp437, b"\x1B[0m\x82\xDB"));
+    }
+
+    #[test]
+    fn test_latin1() {
+        // é is 0x82 and ü 0x81 in CP437
+        assert_eq!("caf\u{82} \u{81}", decode(CharacterSet::Latin1, b"caf\xE9 \xFC"));
+    }
+
+    #[test]
+    fn test_utf8() {
+        // █ is 0xDB in CP437
+        assert_eq!("caf\u{82} \u{DB}", decode(CharacterSet::Utf8, "café █".as_bytes()));
+        assert_eq!("?", decode(CharacterSet::Utf8, "€".as_bytes()));
+    }
+
+    #[test]
+    fn test_utf8_split_sequence() {
+        let mut decoder = CharacterSetDecoder::new(CharacterSet::Utf8);
+        let bytes = "█".as_bytes();
+        assert_eq!(None, decoder.push_byte(bytes[0]));
+        assert_eq!(None, decoder.push_byte(bytes[1]));
+        assert_eq!(Some('\u{DB}'), decoder.push_byte(bytes[2]));
+    }
+
+    #[test]
+    fn test_utf8_invalid() {
+        assert_eq!("?a?", decode(CharacterSet::Utf8, b"\x80a\xFF"));
+        // the broken sequence is dropped, the next character is still shown
+        assert_eq!("a", decode(CharacterSet::Utf8, b"\xE2\x96a"));
+    }
+}
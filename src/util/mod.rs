@@ -6,3 +6,6 @@ pub use music::*;
 
 pub mod pattern_recognizer;
 pub use pattern_recognizer::*;
+
+pub mod character_set_decoder;
+pub use character_set_decoder::*;